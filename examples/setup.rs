@@ -11,11 +11,8 @@ extern crate shared_library;
 
 use gfx::traits::FactoryExt;
 use gfx_gtk::formats;
-use gfx_gtk::GlRenderContext;
 use gtk::traits::*;
-use gtk::{Inhibit, ObjectExt, Window};
-use std::cell::RefCell;
-use std::rc::Rc;
+use gtk::{Inhibit, Window};
 
 pub type PrimitiveIndex = i16;
 pub type VertexIndex = u16;
@@ -234,73 +231,20 @@ pub fn main() {
 		Inhibit(false)
 	});
 
-	let gfx_context: Rc<RefCell<Option<GlRenderContext<RenderColorFormat, RenderDepthFormat>>>> =
-		Rc::new(RefCell::new(None));
-	let render_callback: Rc<RefCell<Option<SimpleRenderCallback>>> = Rc::new(RefCell::new(None));
-
-	let glarea = gtk::GLArea::new();
-	glarea.connect_realize({
-		let gfx_context = gfx_context.clone();
-		let render_callback = render_callback.clone();
-
-		move |widget| {
-			if widget.get_realized() {
-				widget.make_current();
-			}
-
-			let allocation = widget.get_allocation();
-
-			let mut new_context =
-				gfx_gtk::GlRenderContext::new(MSAA, allocation.width, allocation.height, None).ok();
-			if let Some(ref mut new_context) = new_context {
-				let ref vp = new_context.viewport();
-				let ref mut ctx = new_context.gfx_context_mut();
-				*render_callback.borrow_mut() = SimpleRenderCallback::new(ctx, vp).ok();
-			}
-			*gfx_context.borrow_mut() = new_context;
-		}
-	});
-
-	glarea.connect_resize({
-		let gfx_context = gfx_context.clone();
-		let render_callback = render_callback.clone();
-
-		move |_widget, width, height| {
-			if let Some(ref mut context) = *gfx_context.borrow_mut() {
-				if let Some(ref mut render_callback) = *render_callback.borrow_mut() {
-					context.resize(width, height, Some(render_callback)).ok();
-				}
-			}
-		}
-	});
-
-	glarea.connect_render({
-		let gfx_context = gfx_context.clone();
-		let render_callback = render_callback.clone();
-
-		move |_widget, _gl_context| {
-			if let Some(ref mut context) = *gfx_context.borrow_mut() {
-				if let Some(ref mut render_callback) = *render_callback.borrow_mut() {
-					context.with_gfx(render_callback);
-				}
-			}
-
-			Inhibit(false)
-		}
-	});
+	let glarea = gfx_gtk::GfxGlArea::<RenderColorFormat, RenderDepthFormat>::new(
+		MSAA,
+		SimpleRenderCallback::new,
+	);
 
 	let slider = gtk::Scale::new_with_range(gtk::Orientation::Horizontal, -75.0, 75.0, 0.1);
 	slider.set_value(0.0);
 	slider.connect_value_changed({
-		let render_callback = render_callback.clone();
-		let glarea = glarea.downgrade();
+		let glarea = glarea.clone();
 		move |widget| {
-			if let Some(glarea) = glarea.upgrade() {
-				if let Some(ref mut render_callback) = *render_callback.borrow_mut() {
-					render_callback.model_yaw = cgmath::Deg(widget.get_value() as f32);
-					glarea.queue_draw();
-				}
-			}
+			glarea.with_render_callback(|render_callback: &mut SimpleRenderCallback| {
+				render_callback.model_yaw = cgmath::Deg(widget.get_value() as f32);
+			});
+			glarea.queue_render();
 		}
 	});
 
@@ -308,7 +252,7 @@ pub fn main() {
 	window.set_default_size(400, 400);
 	let v_box = gtk::Box::new(gtk::Orientation::Vertical, 0);
 	v_box.pack_start(&slider, false, false, 0);
-	v_box.pack_start(&glarea, true, true, 0);
+	v_box.pack_start(glarea.widget(), true, true, 0);
 
 	window.add(&v_box);
 	window.show_all();
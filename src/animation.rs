@@ -0,0 +1,65 @@
+//! Continuous animation, paced by the Gdk frame clock of the GlArea rather than by a timer,
+//! so that frames stay in sync with the display refresh
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+/// Timing of an animation frame, as reported by the frame clock
+pub struct FrameInfo {
+	/// Time of the frame in microseconds, from the monotonic clock of the frame clock
+	pub frame_time: i64,
+	/// Seconds elapsed since the previous frame, `0` for the first frame of the animation
+	pub delta: f32,
+	/// Number of frames since the animation was started, `0` for the first frame
+	pub frame: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Returned by [GlRenderCallback::animate()] to keep or stop rendering continuously, and by
+/// [GlRenderCallback::resume_animation()] to start again
+pub enum AnimationStatus {
+	/// Render another frame at the next tick of the frame clock
+	Continue,
+	/// Stop the animation, frames are rendered on demand only until it is started again
+	Stop,
+}
+
+/// Builds the [FrameInfo] of consecutive frames from the frame clock timestamps
+#[derive(Clone, Debug, Default)]
+pub struct FrameTimer {
+	last: Option<FrameInfo>,
+}
+
+impl FrameTimer {
+	/// Creates a timer, the next frame is the first of the animation
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Starts counting again from the first frame
+	pub fn reset(&mut self) {
+		self.last = None;
+	}
+
+	/// Returns the info of a new frame
+	/// * `frame_time` time of the frame in microseconds, e.g. from `gdk::FrameClock::get_frame_time()`
+	pub fn tick(&mut self, frame_time: i64) -> FrameInfo {
+		let info = match self.last {
+			Some(last) => FrameInfo {
+				frame_time,
+				delta: (frame_time - last.frame_time).max(0) as f32 * 1e-6,
+				frame: last.frame + 1,
+			},
+			None => FrameInfo {
+				frame_time,
+				delta: 0.,
+				frame: 0,
+			},
+		};
+		self.last = Some(info);
+		info
+	}
+
+	/// The info of the last frame, `None` before the first one
+	pub fn last(&self) -> Option<FrameInfo> {
+		self.last
+	}
+}
@@ -0,0 +1,240 @@
+//! Additional color attachments (normals, albedo, object IDs...) allocated and resized
+//! along with the main render target, for deferred shading and picking
+
+use super::*;
+use gfx::memory::Typed;
+
+#[derive(Clone)]
+/// An off-screen color target of arbitrary format, with the same size and AA mode
+/// as the main render target
+pub struct ColorAttachment<R>
+where
+	R: gfx::Resources,
+{
+	/// Format of the texture
+	format: gfx::format::Format,
+	/// The underlying texture
+	texture: gfx::handle::RawTexture<R>,
+	/// Shader resource view of the texture, for sampling in later passes
+	source: gfx::handle::RawShaderResourceView<R>,
+	/// Render target view of the texture
+	target: gfx::handle::RawRenderTargetView<R>,
+}
+
+impl<R> ColorAttachment<R>
+where
+	R: gfx::Resources,
+{
+	/// Allocates a new attachment
+	/// * `factory` the factory attached to the current Gl context
+	/// * `aa` antialiasing mode, must match the one of the other targets bound in the same pass
+	/// * `width` width of the attachment
+	/// * `height` height of the attachment
+	/// * `format` surface and channel type of the attachment
+	pub fn new<F>(
+		factory: &mut F,
+		aa: gfx::texture::AaMode,
+		width: gfx::texture::Size,
+		height: gfx::texture::Size,
+		format: gfx::format::Format,
+	) -> Result<Self>
+	where
+		F: gfx::Factory<R>,
+	{
+		let gfx::format::Format(surface, channel) = format;
+		let texture = factory.create_texture_raw(
+			gfx::texture::Info {
+				kind: gfx::texture::Kind::D2(width, height, aa),
+				levels: 1,
+				format: surface,
+				bind: gfx::memory::Bind::SHADER_RESOURCE
+					| gfx::memory::Bind::RENDER_TARGET
+					| gfx::memory::Bind::TRANSFER_SRC,
+				usage: gfx::memory::Usage::Data,
+			},
+			Some(channel),
+			None,
+		)?;
+		let source = factory.view_texture_as_shader_resource_raw(
+			&texture,
+			gfx::texture::ResourceDesc {
+				channel,
+				layer: None,
+				min: 0,
+				max: 0,
+				swizzle: gfx::format::Swizzle::new(),
+			},
+		)?;
+		let target = factory.view_texture_as_render_target_raw(
+			&texture,
+			gfx::texture::RenderDesc {
+				channel,
+				level: 0,
+				layer: None,
+			},
+		)?;
+		Ok(ColorAttachment {
+			format,
+			texture,
+			source,
+			target,
+		})
+	}
+
+	/// Returns the format the attachment was declared with
+	pub fn format(&self) -> gfx::format::Format {
+		self.format
+	}
+
+	/// Returns a typed render target view, or `None` if `T` is not the format of the attachment
+	pub fn target<T>(&self) -> Option<gfx::handle::RenderTargetView<R, T>>
+	where
+		T: gfx::format::RenderFormat,
+	{
+		if T::get_format() == self.format {
+			Some(Typed::new(self.target.clone()))
+		} else {
+			None
+		}
+	}
+
+	/// Returns a typed shader resource view, or `None` if `T` is not the format of the attachment
+	pub fn source<T>(&self) -> Option<gfx::handle::ShaderResourceView<R, T::View>>
+	where
+		T: gfx::format::Formatted,
+	{
+		if T::get_format() == self.format {
+			Some(Typed::new(self.source.clone()))
+		} else {
+			None
+		}
+	}
+
+	/// Returns the underlying texture
+	pub fn raw_texture(&self) -> &gfx::handle::RawTexture<R> {
+		&self.texture
+	}
+
+	/// Returns the untyped render target view
+	pub fn raw_target(&self) -> &gfx::handle::RawRenderTargetView<R> {
+		&self.target
+	}
+
+	/// Returns the untyped shader resource view
+	pub fn raw_source(&self) -> &gfx::handle::RawShaderResourceView<R> {
+		&self.source
+	}
+}
+
+#[derive(Clone)]
+/// The extra color attachments declared on a [RenderContext], in declaration order
+pub struct ColorAttachments<R>
+where
+	R: gfx::Resources,
+{
+	attachments: Vec<ColorAttachment<R>>,
+}
+
+impl<R> ColorAttachments<R>
+where
+	R: gfx::Resources,
+{
+	/// Creates an empty set of attachments
+	pub fn new() -> Self {
+		ColorAttachments {
+			attachments: Vec::new(),
+		}
+	}
+
+	/// Number of attachments
+	pub fn len(&self) -> usize {
+		self.attachments.len()
+	}
+
+	/// `true` if no attachments have been declared
+	pub fn is_empty(&self) -> bool {
+		self.attachments.is_empty()
+	}
+
+	/// Returns the attachment at `index`, as returned by [RenderContext::add_color_attachment()]
+	pub fn get(&self, index: usize) -> Option<&ColorAttachment<R>> {
+		self.attachments.get(index)
+	}
+
+	/// Returns a typed render target view of the attachment at `index`, or `None` if
+	/// there is no such attachment or its format is not `T`
+	pub fn target<T>(&self, index: usize) -> Option<gfx::handle::RenderTargetView<R, T>>
+	where
+		T: gfx::format::RenderFormat,
+	{
+		self.get(index).and_then(|a| a.target::<T>())
+	}
+
+	/// Returns a typed shader resource view of the attachment at `index`, or `None` if
+	/// there is no such attachment or its format is not `T`
+	pub fn source<T>(&self, index: usize) -> Option<gfx::handle::ShaderResourceView<R, T::View>>
+	where
+		T: gfx::format::Formatted,
+	{
+		self.get(index).and_then(|a| a.source::<T>())
+	}
+
+	/// Iterates over the attachments in declaration order
+	pub fn iter(&self) -> std::slice::Iter<'_, ColorAttachment<R>> {
+		self.attachments.iter()
+	}
+
+	/// Allocates a new attachment and returns its index
+	pub fn add<F>(
+		&mut self,
+		factory: &mut F,
+		viewport: &Viewport,
+		format: gfx::format::Format,
+	) -> Result<usize>
+	where
+		F: gfx::Factory<R>,
+	{
+		let attachment = ColorAttachment::new(
+			factory,
+			viewport.aa,
+			viewport.width as gfx::texture::Size,
+			viewport.height as gfx::texture::Size,
+			format,
+		)?;
+		self.attachments.push(attachment);
+		Ok(self.attachments.len() - 1)
+	}
+
+	/// Re-allocates all the attachments, keeping their formats, to match a new viewport
+	pub fn resize<F>(&mut self, factory: &mut F, viewport: &Viewport) -> Result<()>
+	where
+		F: gfx::Factory<R>,
+	{
+		let mut attachments = Vec::with_capacity(self.attachments.len());
+		for attachment in &self.attachments {
+			attachments.push(ColorAttachment::new(
+				factory,
+				viewport.aa,
+				viewport.width as gfx::texture::Size,
+				viewport.height as gfx::texture::Size,
+				attachment.format,
+			)?);
+		}
+		self.attachments = attachments;
+		Ok(())
+	}
+
+	/// Releases all the attachments
+	pub fn clear(&mut self) {
+		self.attachments.clear();
+	}
+}
+
+impl<R> Default for ColorAttachments<R>
+where
+	R: gfx::Resources,
+{
+	fn default() -> Self {
+		Self::new()
+	}
+}
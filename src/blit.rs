@@ -0,0 +1,167 @@
+//! The final copy of the postprocessed frame onto the GlArea framebuffer. Gtk backs its framebuffer
+//! with either a renderbuffer or a texture (when the GlArea has an alpha channel), and the copy
+//! only relies on direct state access when the context supports it, down to Gl 3.2 core and Gl ES 3.0
+
+use super::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// How the color attachment of the GlArea framebuffer is restored before the copy
+pub enum BlitPath {
+	/// `glNamedFramebufferRenderbuffer()`/`glNamedFramebufferTexture()`, Gl 4.5 or `GL_ARB_direct_state_access`
+	DirectStateAccess,
+	/// `glFramebufferRenderbuffer()`/`glFramebufferTexture2D()` on the bound framebuffer, Gl 3.0 and Gl ES 3.0
+	BindAttach,
+}
+
+impl BlitPath {
+	/// Picks the path supported by the context a device has been created with
+	/// * `info` the information returned by `GlDevice::get_info()`
+	pub fn detect(info: &gfx_device_gl::Info) -> Self {
+		if info.is_version_supported(4, 5)
+			|| info.is_extension_supported("GL_ARB_direct_state_access")
+		{
+			BlitPath::DirectStateAccess
+		} else {
+			BlitPath::BindAttach
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// The color attachment of a framebuffer
+pub enum ColorAttachmentObject {
+	/// Nothing attached, or the default framebuffer
+	None,
+	/// A renderbuffer, by name
+	Renderbuffer(u32),
+	/// A mipmap level of a 2D texture
+	Texture {
+		/// Name of the texture
+		name: u32,
+		/// Attached mipmap level
+		level: i32,
+	},
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// The framebuffer Gtk wants the GlArea contents in, saved before any Gfx rendering changes the bindings
+pub struct GtkFramebuffer {
+	/// Name of the framebuffer, 0 for the default one
+	pub name: u32,
+	/// Its first color attachment
+	pub color_attachment: ColorAttachmentObject,
+}
+
+impl GtkFramebuffer {
+	/// Queries the framebuffer currently bound for drawing, usually by the GlArea machinery
+	pub fn current() -> Self {
+		let mut name = 0;
+		unsafe {
+			gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut name);
+		}
+		let name = name as u32;
+		let color_attachment = if name == 0 {
+			ColorAttachmentObject::None
+		} else {
+			let get_parameter = |parameter| {
+				let mut value = 0;
+				unsafe {
+					gl::GetFramebufferAttachmentParameteriv(
+						gl::DRAW_FRAMEBUFFER,
+						gl::COLOR_ATTACHMENT0,
+						parameter,
+						&mut value,
+					);
+				}
+				value
+			};
+			let object_name = get_parameter(gl::FRAMEBUFFER_ATTACHMENT_OBJECT_NAME) as u32;
+			match get_parameter(gl::FRAMEBUFFER_ATTACHMENT_OBJECT_TYPE) as u32 {
+				gl::RENDERBUFFER => ColorAttachmentObject::Renderbuffer(object_name),
+				gl::TEXTURE => ColorAttachmentObject::Texture {
+					name: object_name,
+					level: get_parameter(gl::FRAMEBUFFER_ATTACHMENT_TEXTURE_LEVEL),
+				},
+				_ => ColorAttachmentObject::None,
+			}
+		};
+		GtkFramebuffer {
+			name,
+			color_attachment,
+		}
+	}
+
+	/// Binds the framebuffer for drawing and re-attaches its color attachment
+	/// * `path` the entry points available to re-attach it
+	pub fn bind(&self, path: BlitPath) {
+		unsafe {
+			gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.name);
+			match (path, self.color_attachment) {
+				(_, ColorAttachmentObject::None) => {}
+				(
+					BlitPath::DirectStateAccess,
+					ColorAttachmentObject::Renderbuffer(renderbuffer),
+				) => gl::NamedFramebufferRenderbuffer(
+					self.name,
+					gl::COLOR_ATTACHMENT0,
+					gl::RENDERBUFFER,
+					renderbuffer,
+				),
+				(BlitPath::DirectStateAccess, ColorAttachmentObject::Texture { name, level }) => {
+					gl::NamedFramebufferTexture(self.name, gl::COLOR_ATTACHMENT0, name, level)
+				}
+				(BlitPath::BindAttach, ColorAttachmentObject::Renderbuffer(renderbuffer)) => {
+					gl::FramebufferRenderbuffer(
+						gl::DRAW_FRAMEBUFFER,
+						gl::COLOR_ATTACHMENT0,
+						gl::RENDERBUFFER,
+						renderbuffer,
+					)
+				}
+				(BlitPath::BindAttach, ColorAttachmentObject::Texture { name, level }) => {
+					gl::FramebufferTexture2D(
+						gl::DRAW_FRAMEBUFFER,
+						gl::COLOR_ATTACHMENT0,
+						gl::TEXTURE_2D,
+						name,
+						level,
+					)
+				}
+			}
+		}
+	}
+
+	/// Copies the color of the framebuffer currently bound for drawing, e.g. the Gfx postprocess target,
+	/// onto this framebuffer
+	/// * `path` the entry points available to re-attach the color attachment
+	/// * `width` width of the copied area, in pixels
+	/// * `height` height of the copied area, in pixels
+	pub fn blit_from_current(&self, path: BlitPath, width: i32, height: i32) {
+		let mut source_name = 0;
+		unsafe {
+			gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut source_name);
+			// the framebuffer from Gfx is the blit source
+			gl::BindFramebuffer(gl::READ_FRAMEBUFFER, source_name as u32);
+		}
+		// and the framebuffer from Gtk the destination
+		self.bind(path);
+		unsafe {
+			// This is wasteful as the GlArea code already does this for its own off-screen
+			// framebuffer target but we have no means to blit directly to the screen backbuffer
+			// as it happens under the hood within the GlArea rendering code
+			gl::BlitFramebuffer(
+				0,
+				0,
+				width,
+				height,
+				0,
+				0,
+				width,
+				height,
+				gl::COLOR_BUFFER_BIT,
+				gl::NEAREST,
+			);
+			gl::Flush();
+		}
+	}
+}
@@ -0,0 +1,1070 @@
+//! Reusable camera controllers, driven by the input events of the GlArea via [GlInputCallback]
+//! and producing view and projection matrices for the current [Viewport]
+//!
+//! All the matrices are column-major, right-handed and map depth to the -1..1 Gl clip range.
+//! Input moves a goal pose, which the camera approaches smoothly in [Camera::update()]
+
+use super::*;
+use std::f32::consts::PI;
+
+type Vec3 = [f32; 3];
+
+fn add(a: Vec3, b: Vec3) -> Vec3 {
+	[a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+	[a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: Vec3, s: f32) -> Vec3 {
+	[a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn dot(a: Vec3, b: Vec3) -> f32 {
+	a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+	[
+		a[1] * b[2] - a[2] * b[1],
+		a[2] * b[0] - a[0] * b[2],
+		a[0] * b[1] - a[1] * b[0],
+	]
+}
+
+fn length(a: Vec3) -> f32 {
+	dot(a, a).sqrt()
+}
+
+fn normalize(a: Vec3) -> Vec3 {
+	let l = length(a);
+	if l > 0. {
+		scale(a, 1. / l)
+	} else {
+		a
+	}
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+	a + (b - a) * t
+}
+
+fn lerp3(a: Vec3, b: Vec3, t: f32) -> Vec3 {
+	[
+		lerp(a[0], b[0], t),
+		lerp(a[1], b[1], t),
+		lerp(a[2], b[2], t),
+	]
+}
+
+const UP: Vec3 = [0., 1., 0.];
+
+/// Differences below this are considered settled by the damping
+const EPSILON: f32 = 1e-4;
+
+/// Fraction of the remaining distance to the goal covered in `dt` seconds
+fn damping_factor(damping: f32, dt: f32) -> f32 {
+	if damping <= 0. {
+		1.
+	} else {
+		1. - (-dt / damping).exp()
+	}
+}
+
+/// Unit vector pointing from the center of rotation towards the eye
+fn orbit_direction(yaw: f32, pitch: f32) -> Vec3 {
+	[
+		pitch.cos() * yaw.sin(),
+		pitch.sin(),
+		pitch.cos() * yaw.cos(),
+	]
+}
+
+/// Limit of the pitch, to avoid the degenerate view straight up or down
+const MAX_PITCH: f32 = PI / 2. - 0.01;
+
+/// Right-handed view matrix looking from `eye` towards `center`
+pub fn look_at(eye: Vec3, center: Vec3, up: Vec3) -> Matrix4 {
+	let f = normalize(sub(center, eye));
+	let s = normalize(cross(f, up));
+	let u = cross(s, f);
+	[
+		[s[0], u[0], -f[0], 0.],
+		[s[1], u[1], -f[1], 0.],
+		[s[2], u[2], -f[2], 0.],
+		[-dot(s, eye), -dot(u, eye), dot(f, eye), 1.],
+	]
+}
+
+/// Perspective projection matrix
+/// * `fov_y` vertical field of view, in radians
+/// * `aspect` width over height of the viewport
+/// * `near` distance of the near clipping plane
+/// * `far` distance of the far clipping plane
+pub fn perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> Matrix4 {
+	let f = 1. / (fov_y / 2.).tan();
+	[
+		[f / aspect, 0., 0., 0.],
+		[0., f, 0., 0.],
+		[0., 0., (far + near) / (near - far), -1.],
+		[0., 0., 2. * far * near / (near - far), 0.],
+	]
+}
+
+/// Orthographic projection matrix of the box `left..right`, `bottom..top`, `-near..-far`
+pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Matrix4 {
+	[
+		[2. / (right - left), 0., 0., 0.],
+		[0., 2. / (top - bottom), 0., 0.],
+		[0., 0., -2. / (far - near), 0.],
+		[
+			-(right + left) / (right - left),
+			-(top + bottom) / (top - bottom),
+			-(far + near) / (far - near),
+			1.,
+		],
+	]
+}
+
+/// Distance from the center of a sphere of `radius` at which it fits a perspective view
+fn fit_distance(radius: f32, fov_y: f32, aspect: f32) -> f32 {
+	let fov_x = 2. * ((fov_y / 2.).tan() * aspect).atan();
+	radius / (fov_y.min(fov_x) / 2.).sin()
+}
+
+/// A camera controller, fed with the input events of the GlArea
+pub trait Camera: GlInputCallback {
+	/// The current view matrix
+	fn view(&self) -> Matrix4;
+
+	/// The current projection matrix
+	/// * `viewport` size of the GlArea, providing the aspect ratio
+	fn projection(&self, viewport: &Viewport) -> Matrix4;
+
+	/// Moves the camera towards its goal pose, should be invoked once per frame.
+	/// Returns `true` if the camera is still moving, and another frame is needed
+	/// * `dt` time elapsed since the last update, in seconds
+	fn update(&mut self, dt: f32) -> bool;
+
+	/// Moves the camera so that the axis aligned box `min..max` is entirely visible, and makes
+	/// the new pose the one restored by `reset`
+	/// * `min` minimum corner of the box, in world space
+	/// * `max` maximum corner of the box, in world space
+	/// * `viewport` size of the GlArea
+	fn fit_bounds(&mut self, min: [f32; 3], max: [f32; 3], viewport: &Viewport);
+
+	/// Moves the camera back to its initial pose, or the one set by the last `fit_bounds`
+	fn reset(&mut self);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct OrbitPose {
+	target: Vec3,
+	distance: f32,
+	yaw: f32,
+	pitch: f32,
+}
+
+impl OrbitPose {
+	fn approach(&mut self, goal: &OrbitPose, t: f32) -> bool {
+		self.target = lerp3(self.target, goal.target, t);
+		self.distance = lerp(self.distance, goal.distance, t);
+		self.yaw = lerp(self.yaw, goal.yaw, t);
+		self.pitch = lerp(self.pitch, goal.pitch, t);
+		let moving = length(sub(self.target, goal.target)) > EPSILON * self.distance
+			|| (self.distance - goal.distance).abs() > EPSILON * self.distance
+			|| (self.yaw - goal.yaw).abs() > EPSILON
+			|| (self.pitch - goal.pitch).abs() > EPSILON;
+		if !moving {
+			*self = *goal;
+		}
+		moving
+	}
+
+	fn eye(&self) -> Vec3 {
+		add(
+			self.target,
+			scale(orbit_direction(self.yaw, self.pitch), self.distance),
+		)
+	}
+
+	/// Right and up unit vectors of the image plane
+	fn axes(&self) -> (Vec3, Vec3) {
+		let forward = scale(orbit_direction(self.yaw, self.pitch), -1.);
+		let right = normalize(cross(forward, UP));
+		(right, cross(right, forward))
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Drag {
+	None,
+	Rotate,
+	Pan,
+}
+
+#[derive(Clone, Debug)]
+/// Orbits around a target point. Dragging with the left button rotates, with the middle button
+/// (or left button and Shift) pans, and scrolling zooms towards the point under the cursor
+pub struct OrbitCamera {
+	/// Vertical field of view, in radians
+	pub fov_y: f32,
+	/// Distance of the near clipping plane
+	pub near: f32,
+	/// Distance of the far clipping plane
+	pub far: f32,
+	/// Time constant of the smoothing, in seconds. `0` follows the input immediately
+	pub damping: f32,
+	/// Rotation for each pixel of pointer motion, in radians
+	pub rotate_speed: f32,
+	/// Zoom factor for each scroll step
+	pub zoom_speed: f32,
+	/// Closest and farthest distance from the target
+	pub distance_range: (f32, f32),
+	current: OrbitPose,
+	goal: OrbitPose,
+	home: OrbitPose,
+	drag: Drag,
+	last_pixel: (f64, f64),
+}
+
+impl OrbitCamera {
+	/// Creates a camera looking at `target` from `distance`, along the negative Z axis
+	pub fn new(target: [f32; 3], distance: f32) -> Self {
+		let pose = OrbitPose {
+			target,
+			distance,
+			yaw: 0.,
+			pitch: 0.,
+		};
+		OrbitCamera {
+			fov_y: PI / 4.,
+			near: 0.1,
+			far: 1000.,
+			damping: 0.08,
+			rotate_speed: 0.01,
+			zoom_speed: 1.1,
+			distance_range: (0.01, 10000.),
+			current: pose,
+			goal: pose,
+			home: pose,
+			drag: Drag::None,
+			last_pixel: (0., 0.),
+		}
+	}
+
+	/// The point the camera orbits around
+	pub fn target(&self) -> [f32; 3] {
+		self.current.target
+	}
+
+	/// The position of the camera
+	pub fn eye(&self) -> [f32; 3] {
+		self.current.eye()
+	}
+
+	/// Rotates the goal pose
+	/// * `yaw` rotation around the vertical axis, in radians
+	/// * `pitch` elevation, in radians
+	pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+		self.goal.yaw += yaw;
+		self.goal.pitch = (self.goal.pitch + pitch).max(-MAX_PITCH).min(MAX_PITCH);
+	}
+
+	/// Pans the goal pose by a pointer motion of `dx`, `dy` framebuffer pixels
+	fn pan(&mut self, dx: f64, dy: f64, viewport: &Viewport) {
+		let (right, up) = self.goal.axes();
+		let world_per_pixel = 2. * self.goal.distance * (self.fov_y / 2.).tan()
+			/ viewport.target_height.max(1) as f32;
+		let offset = add(scale(right, dx as f32), scale(up, dy as f32));
+		self.goal.target = sub(self.goal.target, scale(offset, world_per_pixel));
+	}
+
+	/// Zooms the goal pose by `factor`, keeping the point under `ndc` fixed on screen
+	fn zoom(&mut self, factor: f32, ndc: (f32, f32), viewport: &Viewport) {
+		let (min, max) = self.distance_range;
+		let distance = (self.goal.distance * factor).max(min).min(max);
+		let factor = distance / self.goal.distance;
+		// the point under the cursor, on the plane through the target facing the camera
+		let (right, up) = self.goal.axes();
+		let half_height = self.goal.distance * (self.fov_y / 2.).tan();
+		let half_width = half_height * viewport.aspect_ratio();
+		let cursor = add(
+			self.goal.target,
+			add(
+				scale(right, ndc.0 * half_width),
+				scale(up, ndc.1 * half_height),
+			),
+		);
+		self.goal.target = add(cursor, scale(sub(self.goal.target, cursor), factor));
+		self.goal.distance = distance;
+	}
+}
+
+impl GlInputCallback for OrbitCamera {
+	fn pointer_motion(&mut self, viewport: &Viewport, motion: &PointerMotion) -> bool {
+		let (x, y) = motion.position.pixel;
+		let (dx, dy) = (x - self.last_pixel.0, y - self.last_pixel.1);
+		self.last_pixel = motion.position.pixel;
+		match self.drag {
+			Drag::Rotate => {
+				let speed = self.rotate_speed / viewport.scale_factor.max(1) as f32;
+				self.rotate(-dx as f32 * speed, -dy as f32 * speed);
+				true
+			}
+			Drag::Pan => {
+				self.pan(dx, dy, viewport);
+				true
+			}
+			Drag::None => false,
+		}
+	}
+
+	fn pointer_button(&mut self, _viewport: &Viewport, button: &PointerButton) -> bool {
+		self.last_pixel = button.position.pixel;
+		self.drag = match (button.action, button.button) {
+			(ButtonAction::Press, 1)
+				if button.modifiers.contains(gdk::ModifierType::SHIFT_MASK) =>
+			{
+				Drag::Pan
+			}
+			(ButtonAction::Press, 1) => Drag::Rotate,
+			(ButtonAction::Press, 2) => Drag::Pan,
+			(ButtonAction::Release, _) => Drag::None,
+			_ => self.drag,
+		};
+		false
+	}
+
+	fn scroll(&mut self, viewport: &Viewport, scroll: &Scroll) -> bool {
+		if scroll.delta.1 == 0. {
+			return false;
+		}
+		let factor = self.zoom_speed.powf(scroll.delta.1 as f32);
+		self.zoom(factor, scroll.position.ndc, viewport);
+		true
+	}
+
+	fn focus(&mut self, _viewport: &Viewport, _focused: bool) -> bool {
+		self.drag = Drag::None;
+		false
+	}
+
+	/// One finger rotates, pinching zooms towards the fingers, twisting turns around the vertical axis
+	fn gesture(&mut self, viewport: &Viewport, gesture: &Gesture) -> bool {
+		match *gesture {
+			Gesture::Drag { delta, .. } => {
+				let speed = self.rotate_speed / viewport.scale_factor.max(1) as f32;
+				self.rotate(-delta.0 as f32 * speed, -delta.1 as f32 * speed);
+				true
+			}
+			Gesture::Pinch {
+				center,
+				scale_delta,
+				..
+			} if scale_delta > 0. => {
+				self.zoom(1. / scale_delta as f32, center.ndc, viewport);
+				true
+			}
+			Gesture::Rotate { angle_delta, .. } => {
+				self.rotate(angle_delta as f32, 0.);
+				true
+			}
+			_ => false,
+		}
+	}
+}
+
+impl Camera for OrbitCamera {
+	fn view(&self) -> Matrix4 {
+		look_at(self.current.eye(), self.current.target, UP)
+	}
+
+	fn projection(&self, viewport: &Viewport) -> Matrix4 {
+		perspective(self.fov_y, viewport.aspect_ratio(), self.near, self.far)
+	}
+
+	fn update(&mut self, dt: f32) -> bool {
+		let t = damping_factor(self.damping, dt);
+		self.current.approach(&self.goal, t)
+	}
+
+	fn fit_bounds(&mut self, min: [f32; 3], max: [f32; 3], viewport: &Viewport) {
+		let radius = (length(sub(max, min)) / 2.).max(EPSILON);
+		self.goal.target = scale(add(min, max), 0.5);
+		self.goal.distance = fit_distance(radius, self.fov_y, viewport.aspect_ratio());
+		self.home = self.goal;
+	}
+
+	fn reset(&mut self) {
+		self.goal = self.home;
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct FlyPose {
+	position: Vec3,
+	yaw: f32,
+	pitch: f32,
+}
+
+impl FlyPose {
+	fn forward(&self) -> Vec3 {
+		scale(orbit_direction(self.yaw, -self.pitch), -1.)
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct FlyKeys {
+	forward: bool,
+	backward: bool,
+	left: bool,
+	right: bool,
+	up: bool,
+	down: bool,
+	boost: bool,
+}
+
+#[derive(Clone, Debug)]
+/// First person camera. W, A, S, D move, Q and E move down and up, Shift moves faster.
+/// Dragging with the right (or left) button looks around, scrolling changes the speed
+pub struct FlyCamera {
+	/// Vertical field of view, in radians
+	pub fov_y: f32,
+	/// Distance of the near clipping plane
+	pub near: f32,
+	/// Distance of the far clipping plane
+	pub far: f32,
+	/// Time constant of the smoothing of motion and mouse look, in seconds. `0` follows the input immediately
+	pub damping: f32,
+	/// Rotation for each pixel of pointer motion, in radians
+	pub look_speed: f32,
+	/// Speed, in world units per second
+	pub speed: f32,
+	/// Speed multiplier while Shift is held
+	pub boost: f32,
+	current: FlyPose,
+	goal: FlyPose,
+	home: FlyPose,
+	velocity: Vec3,
+	keys: FlyKeys,
+	looking: bool,
+	last_pixel: (f64, f64),
+}
+
+impl FlyCamera {
+	/// Creates a camera at `position`, looking along the negative Z axis
+	pub fn new(position: [f32; 3]) -> Self {
+		let pose = FlyPose {
+			position,
+			yaw: 0.,
+			pitch: 0.,
+		};
+		FlyCamera {
+			fov_y: PI / 4.,
+			near: 0.1,
+			far: 1000.,
+			damping: 0.05,
+			look_speed: 0.004,
+			speed: 5.,
+			boost: 4.,
+			current: pose,
+			goal: pose,
+			home: pose,
+			velocity: [0.; 3],
+			keys: FlyKeys::default(),
+			looking: false,
+			last_pixel: (0., 0.),
+		}
+	}
+
+	/// The position of the camera
+	pub fn position(&self) -> [f32; 3] {
+		self.current.position
+	}
+
+	/// The unit vector the camera is looking along
+	pub fn forward(&self) -> [f32; 3] {
+		self.current.forward()
+	}
+
+	/// Velocity requested by the keys being held
+	fn goal_velocity(&self) -> Vec3 {
+		let forward = self.current.forward();
+		let right = normalize(cross(forward, UP));
+		let axis = |positive: bool, negative: bool| match (positive, negative) {
+			(true, false) => 1.,
+			(false, true) => -1.,
+			_ => 0.,
+		};
+		let k = &self.keys;
+		let direction = add(
+			add(
+				scale(forward, axis(k.forward, k.backward)),
+				scale(right, axis(k.right, k.left)),
+			),
+			scale(UP, axis(k.up, k.down)),
+		);
+		let speed = if k.boost {
+			self.speed * self.boost
+		} else {
+			self.speed
+		};
+		scale(normalize(direction), speed)
+	}
+}
+
+impl GlInputCallback for FlyCamera {
+	fn pointer_motion(&mut self, viewport: &Viewport, motion: &PointerMotion) -> bool {
+		let (x, y) = motion.position.pixel;
+		let (dx, dy) = (x - self.last_pixel.0, y - self.last_pixel.1);
+		self.last_pixel = motion.position.pixel;
+		if self.looking {
+			let speed = self.look_speed / viewport.scale_factor.max(1) as f32;
+			self.goal.yaw -= dx as f32 * speed;
+			self.goal.pitch = (self.goal.pitch + dy as f32 * speed)
+				.max(-MAX_PITCH)
+				.min(MAX_PITCH);
+			true
+		} else {
+			false
+		}
+	}
+
+	fn pointer_button(&mut self, _viewport: &Viewport, button: &PointerButton) -> bool {
+		self.last_pixel = button.position.pixel;
+		match (button.action, button.button) {
+			(ButtonAction::Press, 1) | (ButtonAction::Press, 3) => self.looking = true,
+			(ButtonAction::Release, 1) | (ButtonAction::Release, 3) => self.looking = false,
+			_ => {}
+		}
+		false
+	}
+
+	fn scroll(&mut self, _viewport: &Viewport, scroll: &Scroll) -> bool {
+		self.speed *= 1.1f32.powf(-scroll.delta.1 as f32);
+		false
+	}
+
+	fn key(&mut self, _viewport: &Viewport, key: &Key) -> bool {
+		use gdk::enums::key;
+		let held = match key.keyval {
+			key::w | key::W | key::Up => &mut self.keys.forward,
+			key::s | key::S | key::Down => &mut self.keys.backward,
+			key::a | key::A | key::Left => &mut self.keys.left,
+			key::d | key::D | key::Right => &mut self.keys.right,
+			key::e | key::E => &mut self.keys.up,
+			key::q | key::Q => &mut self.keys.down,
+			key::Shift_L | key::Shift_R => &mut self.keys.boost,
+			_ => return false,
+		};
+		let changed = *held != key.pressed;
+		*held = key.pressed;
+		changed
+	}
+
+	fn focus(&mut self, _viewport: &Viewport, focused: bool) -> bool {
+		if !focused {
+			self.keys = FlyKeys::default();
+			self.looking = false;
+		}
+		false
+	}
+
+	/// One finger looks around, pinching moves forward and backward
+	fn gesture(&mut self, viewport: &Viewport, gesture: &Gesture) -> bool {
+		match *gesture {
+			Gesture::Drag { delta, .. } => {
+				let speed = self.look_speed / viewport.scale_factor.max(1) as f32;
+				self.goal.yaw -= delta.0 as f32 * speed;
+				self.goal.pitch = (self.goal.pitch + delta.1 as f32 * speed)
+					.max(-MAX_PITCH)
+					.min(MAX_PITCH);
+				true
+			}
+			Gesture::Pinch { scale_delta, .. } if scale_delta > 0. => {
+				let step = scale(self.goal.forward(), self.speed * (scale_delta as f32).ln());
+				self.goal.position = add(self.goal.position, step);
+				self.current.position = self.goal.position;
+				true
+			}
+			_ => false,
+		}
+	}
+}
+
+impl Camera for FlyCamera {
+	fn view(&self) -> Matrix4 {
+		let position = self.current.position;
+		look_at(position, add(position, self.current.forward()), UP)
+	}
+
+	fn projection(&self, viewport: &Viewport) -> Matrix4 {
+		perspective(self.fov_y, viewport.aspect_ratio(), self.near, self.far)
+	}
+
+	fn update(&mut self, dt: f32) -> bool {
+		let t = damping_factor(self.damping, dt);
+		self.velocity = lerp3(self.velocity, self.goal_velocity(), t);
+		if length(self.velocity) < EPSILON {
+			self.velocity = [0.; 3];
+		}
+		self.goal.position = add(self.goal.position, scale(self.velocity, dt));
+		self.current.position = self.goal.position;
+		self.current.yaw = lerp(self.current.yaw, self.goal.yaw, t);
+		self.current.pitch = lerp(self.current.pitch, self.goal.pitch, t);
+		let turning = (self.current.yaw - self.goal.yaw).abs() > EPSILON
+			|| (self.current.pitch - self.goal.pitch).abs() > EPSILON;
+		if !turning {
+			self.current = self.goal;
+		}
+		turning || self.velocity != [0.; 3] || self.goal_velocity() != [0.; 3]
+	}
+
+	fn fit_bounds(&mut self, min: [f32; 3], max: [f32; 3], viewport: &Viewport) {
+		let radius = (length(sub(max, min)) / 2.).max(EPSILON);
+		let distance = fit_distance(radius, self.fov_y, viewport.aspect_ratio());
+		let center = scale(add(min, max), 0.5);
+		self.goal.position = sub(center, scale(self.goal.forward(), distance));
+		self.current.position = self.goal.position;
+		self.velocity = [0.; 3];
+		self.home = self.goal;
+	}
+
+	fn reset(&mut self) {
+		self.goal = self.home;
+		self.current.position = self.goal.position;
+		self.velocity = [0.; 3];
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct PanZoomPose {
+	center: [f32; 2],
+	zoom: f32,
+}
+
+#[derive(Clone, Debug)]
+/// 2D camera for images and plots. Dragging with any button pans, scrolling zooms towards
+/// the point under the cursor. One world unit covers `zoom` framebuffer pixels, so at zoom `1`
+/// the mapping is pixel exact: with integer `center` coordinates, the boundaries of world units
+/// fall onto pixel edges. `center` is on the pixel edge nearest to the middle of the framebuffer,
+/// half a pixel off along the odd sides
+pub struct PanZoomCamera {
+	/// Time constant of the smoothing, in seconds. `0` follows the input immediately
+	pub damping: f32,
+	/// Zoom factor for each scroll step
+	pub zoom_speed: f32,
+	/// Minimum and maximum zoom, in framebuffer pixels per world unit
+	pub zoom_range: (f32, f32),
+	current: PanZoomPose,
+	goal: PanZoomPose,
+	home: PanZoomPose,
+	dragging: bool,
+	last_pixel: (f64, f64),
+}
+
+impl PanZoomCamera {
+	/// Creates a camera centered on `center`, showing `zoom` framebuffer pixels per world unit
+	pub fn new(center: [f32; 2], zoom: f32) -> Self {
+		let pose = PanZoomPose { center, zoom };
+		PanZoomCamera {
+			damping: 0.05,
+			zoom_speed: 1.1,
+			zoom_range: (1e-3, 1e3),
+			current: pose,
+			goal: pose,
+			home: pose,
+			dragging: false,
+			last_pixel: (0., 0.),
+		}
+	}
+
+	/// The world position at the center of the viewport
+	pub fn center(&self) -> [f32; 2] {
+		self.current.center
+	}
+
+	/// Framebuffer pixels per world unit
+	pub fn zoom(&self) -> f32 {
+		self.current.zoom
+	}
+
+	/// Jumps to a zoom of exactly 1 pixel per unit, rounding the center to whole units
+	pub fn set_pixel_exact(&mut self) {
+		self.goal = PanZoomPose {
+			center: [self.goal.center[0].round(), self.goal.center[1].round()],
+			zoom: 1.,
+		};
+		self.current = self.goal;
+	}
+
+	/// Converts framebuffer pixel coordinates (origin bottom left) into world coordinates
+	/// * `viewport` size of the GlArea
+	/// * `pixel` position in framebuffer pixels, e.g. [PointerPosition::pixel]
+	pub fn pixel_to_world(&self, viewport: &Viewport, pixel: (f64, f64)) -> [f32; 2] {
+		Self::to_world(&self.current, viewport, pixel)
+	}
+
+	/// Pans the goal pose by a pointer motion of `delta` framebuffer pixels
+	fn pan(&mut self, delta: (f64, f64)) {
+		self.goal.center[0] -= delta.0 as f32 / self.goal.zoom;
+		self.goal.center[1] -= delta.1 as f32 / self.goal.zoom;
+		// panning follows the pointer exactly
+		self.current.center = self.goal.center;
+	}
+
+	/// Zooms the goal pose by `factor`, keeping the world point under `pixel` fixed
+	fn zoom_at(&mut self, factor: f32, pixel: (f64, f64), viewport: &Viewport) {
+		let (min, max) = self.zoom_range;
+		let zoom = (self.goal.zoom * factor).max(min).min(max);
+		let cursor = Self::to_world(&self.goal, viewport, pixel);
+		self.goal.zoom = zoom;
+		let moved = Self::to_world(&self.goal, viewport, pixel);
+		self.goal.center[0] += cursor[0] - moved[0];
+		self.goal.center[1] += cursor[1] - moved[1];
+	}
+
+	fn to_world(pose: &PanZoomPose, viewport: &Viewport, pixel: (f64, f64)) -> [f32; 2] {
+		let origin = Self::origin(viewport);
+		[
+			pose.center[0] + (pixel.0 as f32 - origin.0) / pose.zoom,
+			pose.center[1] + (pixel.1 as f32 - origin.1) / pose.zoom,
+		]
+	}
+
+	/// The framebuffer pixel edge `center` is mapped onto
+	fn origin(viewport: &Viewport) -> (f32, f32) {
+		(
+			(viewport.target_width / 2) as f32,
+			(viewport.target_height / 2) as f32,
+		)
+	}
+}
+
+impl GlInputCallback for PanZoomCamera {
+	fn pointer_motion(&mut self, _viewport: &Viewport, motion: &PointerMotion) -> bool {
+		let (x, y) = motion.position.pixel;
+		let (dx, dy) = (x - self.last_pixel.0, y - self.last_pixel.1);
+		self.last_pixel = motion.position.pixel;
+		if self.dragging {
+			self.pan((dx, dy));
+			true
+		} else {
+			false
+		}
+	}
+
+	fn pointer_button(&mut self, _viewport: &Viewport, button: &PointerButton) -> bool {
+		self.last_pixel = button.position.pixel;
+		match button.action {
+			ButtonAction::Press => self.dragging = true,
+			ButtonAction::Release => self.dragging = false,
+			_ => {}
+		}
+		false
+	}
+
+	fn scroll(&mut self, viewport: &Viewport, scroll: &Scroll) -> bool {
+		if scroll.delta.1 == 0. {
+			return false;
+		}
+		let factor = self.zoom_speed.powf(-scroll.delta.1 as f32);
+		self.zoom_at(factor, scroll.position.pixel, viewport);
+		true
+	}
+
+	fn focus(&mut self, _viewport: &Viewport, _focused: bool) -> bool {
+		self.dragging = false;
+		false
+	}
+
+	/// One finger pans, pinching zooms around the fingers
+	fn gesture(&mut self, viewport: &Viewport, gesture: &Gesture) -> bool {
+		match *gesture {
+			Gesture::Drag { delta, .. } => {
+				self.pan(delta);
+				true
+			}
+			Gesture::Pinch {
+				center,
+				scale_delta,
+				..
+			} if scale_delta > 0. => {
+				self.zoom_at(scale_delta as f32, center.pixel, viewport);
+				// pinching follows the fingers exactly
+				self.current = self.goal;
+				true
+			}
+			_ => false,
+		}
+	}
+}
+
+impl Camera for PanZoomCamera {
+	fn view(&self) -> Matrix4 {
+		let (x, y) = (self.current.center[0], self.current.center[1]);
+		[
+			[1., 0., 0., 0.],
+			[0., 1., 0., 0.],
+			[0., 0., 1., 0.],
+			[-x, -y, 0., 1.],
+		]
+	}
+
+	fn projection(&self, viewport: &Viewport) -> Matrix4 {
+		let zoom = self.current.zoom;
+		let origin = Self::origin(viewport);
+		orthographic(
+			-origin.0 / zoom,
+			(viewport.target_width as f32 - origin.0) / zoom,
+			-origin.1 / zoom,
+			(viewport.target_height as f32 - origin.1) / zoom,
+			-1.,
+			1.,
+		)
+	}
+
+	fn update(&mut self, dt: f32) -> bool {
+		let t = damping_factor(self.damping, dt);
+		// the zoom is interpolated geometrically, so that it changes at a steady pace
+		let zoom = (lerp(self.current.zoom.ln(), self.goal.zoom.ln(), t)).exp();
+		// and the center along with it, keeping fixed the point the goal zoom converges to
+		let progress = if (self.goal.zoom - self.current.zoom).abs() > EPSILON * self.goal.zoom {
+			(1. / zoom - 1. / self.current.zoom) / (1. / self.goal.zoom - 1. / self.current.zoom)
+		} else {
+			t
+		};
+		for (c, g) in self.current.center.iter_mut().zip(self.goal.center.iter()) {
+			*c = lerp(*c, *g, progress);
+		}
+		self.current.zoom = zoom;
+		let moving = (self.current.zoom - self.goal.zoom).abs() > EPSILON * self.goal.zoom
+			|| (self.current.center[0] - self.goal.center[0]).abs() * self.goal.zoom > EPSILON
+			|| (self.current.center[1] - self.goal.center[1]).abs() * self.goal.zoom > EPSILON;
+		if !moving {
+			self.current = self.goal;
+		}
+		moving
+	}
+
+	fn fit_bounds(&mut self, min: [f32; 3], max: [f32; 3], viewport: &Viewport) {
+		let width = (max[0] - min[0]).max(EPSILON);
+		let height = (max[1] - min[1]).max(EPSILON);
+		let (min_zoom, max_zoom) = self.zoom_range;
+		self.goal = PanZoomPose {
+			center: [(min[0] + max[0]) / 2., (min[1] + max[1]) / 2.],
+			zoom: (viewport.target_width as f32 / width)
+				.min(viewport.target_height as f32 / height)
+				.max(min_zoom)
+				.min(max_zoom),
+		};
+		self.home = self.goal;
+	}
+
+	fn reset(&mut self) {
+		self.goal = self.home;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Transforms a world space point into normalized device coordinates
+	fn project(p: Vec3, projection: &Matrix4, view: &Matrix4) -> Vec3 {
+		let transform = |m: &Matrix4, v: [f32; 4]| {
+			let mut r = [0.; 4];
+			for (i, value) in r.iter_mut().enumerate() {
+				*value = (0..4).map(|k| m[k][i] * v[k]).sum();
+			}
+			r
+		};
+		let clip = transform(projection, transform(view, [p[0], p[1], p[2], 1.]));
+		[clip[0] / clip[3], clip[1] / clip[3], clip[2] / clip[3]]
+	}
+
+	fn assert_close(a: Vec3, b: Vec3, tolerance: f32) {
+		assert!(
+			length(sub(a, b)) <= tolerance,
+			"{:?} != {:?} within {}",
+			a,
+			b,
+			tolerance
+		);
+	}
+
+	fn viewport(width: i32, height: i32) -> Viewport {
+		Viewport::with_aa(gfx::texture::AaMode::Single, width, height)
+	}
+
+	#[test]
+	fn look_at_moves_the_eye_to_the_origin() {
+		let view = look_at([1., 2., 3.], [1., 2., -7.], UP);
+		// the center straight ahead, at a distance of 10 along -Z
+		let center = transform_point(&view, [1., 2., -7.]);
+		assert_close(center, [0., 0., -10.], 1e-5);
+		let eye = transform_point(&view, [1., 2., 3.]);
+		assert_close(eye, [0., 0., 0.], 1e-5);
+		let right = transform_point(&view, [2., 2., 3.]);
+		assert_close(right, [1., 0., 0.], 1e-5);
+	}
+
+	fn transform_point(m: &Matrix4, p: Vec3) -> Vec3 {
+		let mut r = [0.; 3];
+		for (i, value) in r.iter_mut().enumerate() {
+			*value = m[0][i] * p[0] + m[1][i] * p[1] + m[2][i] * p[2] + m[3][i];
+		}
+		r
+	}
+
+	#[test]
+	fn perspective_maps_the_clipping_planes_to_the_depth_range() {
+		let projection = perspective(PI / 3., 2., 0.5, 50.);
+		let view = look_at([0.; 3], [0., 0., -1.], UP);
+		assert!((project([0., 0., -0.5], &projection, &view)[2] + 1.).abs() < 1e-5);
+		assert!((project([0., 0., -50.], &projection, &view)[2] - 1.).abs() < 1e-4);
+		// the top edge of the field of view at distance 1
+		let top = (PI / 6.).tan();
+		let p = project([0., top, -1.], &projection, &view);
+		assert!((p[1] - 1.).abs() < 1e-5);
+		let p = project([2. * top, 0., -1.], &projection, &view);
+		assert!((p[0] - 1.).abs() < 1e-5);
+	}
+
+	#[test]
+	fn orthographic_maps_the_box_to_the_unit_cube() {
+		let projection = orthographic(-2., 6., -1., 3., 1., 5.);
+		let view = look_at([0.; 3], [0., 0., -1.], UP);
+		assert_close(
+			project([-2., -1., -1.], &projection, &view),
+			[-1., -1., -1.],
+			1e-5,
+		);
+		assert_close(
+			project([6., 3., -5.], &projection, &view),
+			[1., 1., 1.],
+			1e-5,
+		);
+	}
+
+	#[test]
+	fn unproject_inverts_project() {
+		let mut camera = OrbitCamera::new([1., -2., 3.], 8.);
+		camera.damping = 0.;
+		camera.rotate(0.7, -0.4);
+		camera.update(0.1);
+		let viewport = viewport(640, 480);
+		let (projection, view) = (camera.projection(&viewport), camera.view());
+		for p in &[[1., -2., 3.], [0., 0., 0.], [2.5, -1., 4.], [-1., 0.5, 2.]] {
+			let ndc = project(*p, &projection, &view);
+			let world = depth::unproject(ndc, &projection, &view).unwrap();
+			assert_close(world, *p, 1e-3);
+		}
+	}
+
+	#[test]
+	fn orbit_camera_looks_at_the_target() {
+		let mut camera = OrbitCamera::new([0., 0., 0.], 5.);
+		assert_close(camera.eye(), [0., 0., 5.], 1e-6);
+		camera.damping = 0.;
+		camera.rotate(PI / 2., 0.);
+		assert!(!camera.update(0.1));
+		assert_close(camera.eye(), [5., 0., 0.], 1e-5);
+		let viewport = viewport(100, 100);
+		let ndc = project(
+			camera.target(),
+			&camera.projection(&viewport),
+			&camera.view(),
+		);
+		assert_close([ndc[0], ndc[1], 0.], [0., 0., 0.], 1e-5);
+	}
+
+	#[test]
+	fn orbit_camera_settles_on_the_goal() {
+		let mut camera = OrbitCamera::new([0., 0., 0.], 5.);
+		camera.rotate(1., 0.5);
+		let mut frames = 0;
+		while camera.update(1. / 60.) {
+			frames += 1;
+			assert!(frames < 1000, "the camera never settles");
+		}
+		assert!(frames > 1);
+		assert_eq!(camera.current, camera.goal);
+	}
+
+	#[test]
+	fn fit_bounds_shows_the_whole_box() {
+		let viewport = viewport(800, 400);
+		let (min, max) = ([-1., -2., -3.], [4., 1., 0.]);
+		let mut orbit = OrbitCamera::new([0.; 3], 1.);
+		orbit.damping = 0.;
+		orbit.fit_bounds(min, max, &viewport);
+		orbit.update(0.1);
+		let mut fly = FlyCamera::new([0.; 3]);
+		fly.fit_bounds(min, max, &viewport);
+		for camera in &[&orbit as &dyn Camera, &fly as &dyn Camera] {
+			let (projection, view) = (camera.projection(&viewport), camera.view());
+			for i in 0..8 {
+				let corner = [
+					if i & 1 == 0 { min[0] } else { max[0] },
+					if i & 2 == 0 { min[1] } else { max[1] },
+					if i & 4 == 0 { min[2] } else { max[2] },
+				];
+				let ndc = project(corner, &projection, &view);
+				assert!(ndc.iter().all(|c| c.abs() <= 1.), "{:?} is clipped", ndc);
+			}
+		}
+	}
+
+	#[test]
+	fn pan_zoom_camera_maps_pixels_to_world() {
+		for &(width, height) in &[(640, 480), (641, 481), (7, 4)] {
+			let viewport = viewport(width, height);
+			let mut camera = PanZoomCamera::new([10., -3.], 2.5);
+			camera.zoom_at(1.7, (5., 3.), &viewport);
+			camera.current = camera.goal;
+			let (projection, view) = (camera.projection(&viewport), camera.view());
+			for &pixel in &[(0., 0.), (3.5, 2.), (width as f64, height as f64)] {
+				let world = camera.pixel_to_world(&viewport, pixel);
+				let ndc = project([world[0], world[1], 0.], &projection, &view);
+				let expected = [
+					pixel.0 as f32 / width as f32 * 2. - 1.,
+					pixel.1 as f32 / height as f32 * 2. - 1.,
+					0.,
+				];
+				assert_close(ndc, expected, 1e-4);
+			}
+		}
+	}
+
+	#[test]
+	fn pan_zoom_camera_is_pixel_exact_on_odd_sizes() {
+		for &(width, height) in &[(640, 480), (641, 481), (5, 3)] {
+			let viewport = viewport(width, height);
+			let mut camera = PanZoomCamera::new([0.3, 7.8], 3.);
+			camera.set_pixel_exact();
+			for x in 0..=width {
+				let world = camera.pixel_to_world(&viewport, (x as f64, 0.));
+				assert_eq!(world[0], world[0].round());
+			}
+			for y in 0..=height {
+				let world = camera.pixel_to_world(&viewport, (0., y as f64));
+				assert_eq!(world[1], world[1].round());
+			}
+		}
+	}
+
+	#[test]
+	fn zoom_keeps_the_point_under_the_cursor() {
+		let viewport = viewport(300, 200);
+		let mut camera = PanZoomCamera::new([0., 0.], 1.);
+		let cursor = (40., 170.);
+		let before = camera.pixel_to_world(&viewport, cursor);
+		camera.zoom_at(3., cursor, &viewport);
+		camera.current = camera.goal;
+		let after = camera.pixel_to_world(&viewport, cursor);
+		assert_close([before[0], before[1], 0.], [after[0], after[1], 0.], 1e-4);
+	}
+}
@@ -0,0 +1,180 @@
+//! Eyedropper: reads back the color of the pixels under the cursor, both as presented
+//! on screen and as rendered before postprocessing
+
+use super::*;
+use gfx::memory::Typed;
+
+// Averages a rectangle of the (possibly multisampled) render target into a single float texel
+gfx_pipeline!(color_read {
+		vbuf: gfx::VertexBuffer<BlitVertex> = (),
+		src: gfx::TextureSampler<[f32; 4]> = "t_Source",
+		rect: gfx::Global<[i32; 4]> = "u_Rect",
+		samples: gfx::Global<i32> = "u_Samples",
+		dst: gfx::RenderTarget<formats::ColorReadFormat> = "o_Color",
+	}
+);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// The color of a pixel, or the average color of a neighbourhood, of the last rendered frame
+pub struct PickedColor {
+	/// 8 bit value as displayed, after the output transfer of the postprocessing stage.
+	/// sRGB encoded only with [OutputTransfer::Srgb]
+	pub output: [u8; 4],
+	/// Floating point value in the render target, before postprocessing
+	pub linear: Rgba,
+}
+
+/// The resources needed to read colors back from the render and postprocessing targets
+pub struct ColorPickerContext<D>
+where
+	D: gfx::Device,
+{
+	/// pipeline state object averaging the render target texels, resolving them if multisampled
+	pso: gfx::PipelineState<D::Resources, color_read::Meta>,
+	/// nearest sampler, texels are fetched directly
+	sampler: gfx::handle::Sampler<D::Resources>,
+	/// number of samples per pixel of the render target
+	samples: i32,
+	/// 1x1 texture receiving the averaged linear color
+	texture: gfx::handle::Texture<
+		D::Resources,
+		<formats::ColorReadFormat as gfx::format::Formatted>::Surface,
+	>,
+	/// render target view of `texture`
+	target: gfx::handle::RenderTargetView<D::Resources, formats::ColorReadFormat>,
+}
+
+impl ColorPickerContext<GlDevice> {
+	/// Creates the read back PSO and target
+	/// * `factory` the factory attached to the current Gl context
+	/// * `aa` antialiasing mode of the render target
+	pub fn new(factory: &mut GlFactory, aa: gfx::texture::AaMode) -> Result<Self> {
+		use gfx::traits::FactoryExt;
+
+		let (pixel_shader_code, samples) = match aa {
+			gfx::texture::AaMode::Multi(samples) => (
+				shaders::COLOR_READ_PIXEL_SHADER_MSAA.as_bytes(),
+				i32::from(samples.max(1)),
+			),
+			_ => (shaders::COLOR_READ_PIXEL_SHADER.as_bytes(), 1),
+		};
+		let pso = factory.create_pipeline_simple(
+			shaders::POST_VERTEX_SHADER.as_bytes(),
+			pixel_shader_code,
+			color_read::new(),
+		)?;
+		let sampler = factory.create_sampler(gfx::texture::SamplerInfo::new(
+			gfx::texture::FilterMethod::Scale,
+			gfx::texture::WrapMode::Clamp,
+		));
+		let texture = factory.create_texture(
+			gfx::texture::Kind::D2(1, 1, gfx::texture::AaMode::Single),
+			1,
+			gfx::memory::Bind::RENDER_TARGET | gfx::memory::Bind::TRANSFER_SRC,
+			gfx::memory::Usage::Data,
+			Some(<formats::ColorReadFormat as gfx::format::Formatted>::get_format().1),
+		)?;
+		let target = factory.view_texture_as_render_target(&texture, 0, None)?;
+		Ok(ColorPickerContext {
+			pso,
+			sampler,
+			samples,
+			texture,
+			target,
+		})
+	}
+
+	/// Reads the average color of `rect`, blocking until the GPU has completed the copies
+	/// * `gfx_context` Gfx device, factory, encoder attached to the current Gl context
+	/// * `postprocess_context` provides the full screen geometry for the averaging pass
+	/// * `render_target_source` texture view of the render target, before postprocessing
+	/// * `postprocess_texture` the postprocessing target, as presented on screen
+	/// * `rect` `(x, y, width, height)` of the region to read, bottom-left origin, already clipped
+	pub fn read(
+		&self,
+		gfx_context: &mut GlGfxContext,
+		postprocess_context: &GlPostprocessContext,
+		render_target_source: &gfx::handle::ShaderResourceView<GlResources, [f32; 4]>,
+		postprocess_texture: &gfx::handle::RawTexture<GlResources>,
+		rect: (i32, i32, i32, i32),
+	) -> Result<PickedColor> {
+		use gfx::traits::FactoryExt;
+
+		let (x, y, width, height) = rect;
+		gfx_context.encoder.draw(
+			&postprocess_context.ibuf,
+			&self.pso,
+			&color_read::Data {
+				vbuf: postprocess_context.vbuf.clone(),
+				src: (render_target_source.clone(), self.sampler.clone()),
+				rect: [x, y, width, height],
+				samples: self.samples,
+				dst: self.target.clone(),
+			},
+		);
+		let linear_buffer = gfx_context.factory.create_download_buffer::<Rgba>(1)?;
+		gfx_context
+			.encoder
+			.copy_texture_to_buffer_raw(
+				self.texture.raw(),
+				None,
+				gfx::texture::RawImageInfo {
+					xoffset: 0,
+					yoffset: 0,
+					zoffset: 0,
+					width: 1,
+					height: 1,
+					depth: 1,
+					format: <formats::ColorReadFormat as gfx::format::Formatted>::get_format(),
+					mipmap: 0,
+				},
+				linear_buffer.raw(),
+				0,
+			)
+			.map_err(|e| Error::GenericError(format!("{:?}", e)))?;
+		let output_buffer = gfx_context
+			.factory
+			.create_download_buffer::<[u8; 4]>((width * height) as usize)?;
+		gfx_context
+			.encoder
+			.copy_texture_to_buffer_raw(
+				postprocess_texture,
+				None,
+				gfx::texture::RawImageInfo {
+					xoffset: x as u16,
+					yoffset: y as u16,
+					zoffset: 0,
+					width: width as u16,
+					height: height as u16,
+					depth: 1,
+					format: <formats::GtkTargetColorFormat as gfx::format::Formatted>::get_format(),
+					mipmap: 0,
+				},
+				output_buffer.raw(),
+				0,
+			)
+			.map_err(|e| Error::GenericError(format!("{:?}", e)))?;
+		gfx_context.flush();
+
+		let linear = gfx_context
+			.factory
+			.read_mapping(&linear_buffer)
+			.map_err(|e| Error::GenericError(format!("{:?}", e)))?[0];
+		let reader = gfx_context
+			.factory
+			.read_mapping(&output_buffer)
+			.map_err(|e| Error::GenericError(format!("{:?}", e)))?;
+		let mut sum = [0u32; 4];
+		for texel in reader.iter() {
+			for (s, c) in sum.iter_mut().zip(texel.iter()) {
+				*s += u32::from(*c);
+			}
+		}
+		let count = reader.len().max(1) as u32;
+		let mut output = [0u8; 4];
+		for (c, s) in output.iter_mut().zip(sum.iter()) {
+			*c = ((*s + count / 2) / count) as u8;
+		}
+		Ok(PickedColor { output, linear })
+	}
+}
@@ -0,0 +1,343 @@
+//! Read back of the main depth buffer, and reconstruction of world space positions
+//! from a depth value, for "what is under the cursor" queries
+
+use super::*;
+use gfx::memory::Typed;
+
+// Copies (or resolves) a single depth texel into a floating point target
+gfx_pipeline!(depth_read {
+		vbuf: gfx::VertexBuffer<BlitVertex> = (),
+		depth: gfx::RawShaderResource = "t_Depth",
+		sampler: gfx::Sampler = "t_Depth",
+		pixel: gfx::Global<[i32; 2]> = "u_Pixel",
+		samples: gfx::Global<i32> = "u_Samples",
+		dst: gfx::RenderTarget<formats::DepthReadFormat> = "o_Depth",
+	}
+);
+
+/// The resources needed to read depth values back from the main depth buffer
+pub struct DepthReadContext<D>
+where
+	D: gfx::Device,
+{
+	/// pipeline state object copying the depth texel, resolving it if multisampled
+	pso: gfx::PipelineState<D::Resources, depth_read::Meta>,
+	/// nearest sampler, the depth texel is fetched directly
+	sampler: gfx::handle::Sampler<D::Resources>,
+	/// number of samples per pixel of the depth buffer
+	samples: i32,
+	/// 1x1 texture receiving the depth value
+	texture: gfx::handle::Texture<
+		D::Resources,
+		<formats::DepthReadFormat as gfx::format::Formatted>::Surface,
+	>,
+	/// render target view of `texture`
+	target: gfx::handle::RenderTargetView<D::Resources, formats::DepthReadFormat>,
+}
+
+impl DepthReadContext<GlDevice> {
+	/// Creates the read back PSO and target
+	/// * `factory` the factory attached to the current Gl context
+	/// * `aa` antialiasing mode of the depth buffer
+	pub fn new(factory: &mut GlFactory, aa: gfx::texture::AaMode) -> Result<Self> {
+		use gfx::traits::FactoryExt;
+
+		let (pixel_shader_code, samples) = match aa {
+			gfx::texture::AaMode::Multi(samples) => (
+				shaders::DEPTH_READ_PIXEL_SHADER_MSAA.as_bytes(),
+				i32::from(samples.max(1)),
+			),
+			_ => (shaders::DEPTH_READ_PIXEL_SHADER.as_bytes(), 1),
+		};
+		let pso = factory.create_pipeline_simple(
+			shaders::POST_VERTEX_SHADER.as_bytes(),
+			pixel_shader_code,
+			depth_read::new(),
+		)?;
+		let sampler = factory.create_sampler(gfx::texture::SamplerInfo::new(
+			gfx::texture::FilterMethod::Scale,
+			gfx::texture::WrapMode::Clamp,
+		));
+		let texture = factory.create_texture(
+			gfx::texture::Kind::D2(1, 1, gfx::texture::AaMode::Single),
+			1,
+			gfx::memory::Bind::RENDER_TARGET | gfx::memory::Bind::TRANSFER_SRC,
+			gfx::memory::Usage::Data,
+			Some(<formats::DepthReadFormat as gfx::format::Formatted>::get_format().1),
+		)?;
+		let target = factory.view_texture_as_render_target(&texture, 0, None)?;
+		Ok(DepthReadContext {
+			pso,
+			sampler,
+			samples,
+			texture,
+			target,
+		})
+	}
+
+	/// Reads the depth value stored at `pixel`, blocking until the GPU has completed the copy.
+	/// Multisampled depth buffers are resolved by taking the nearest of the samples
+	/// * `gfx_context` Gfx device, factory, encoder attached to the current Gl context
+	/// * `postprocess_context` provides the full screen geometry for the copy pass
+	/// * `depth_source` shader resource view of the depth buffer
+	/// * `pixel` `(x, y)` position of the texel, bottom-left origin
+	pub fn read(
+		&self,
+		gfx_context: &mut GlGfxContext,
+		postprocess_context: &GlPostprocessContext,
+		depth_source: &gfx::handle::RawShaderResourceView<GlResources>,
+		pixel: (i32, i32),
+	) -> Result<Depth> {
+		use gfx::traits::FactoryExt;
+
+		let (x, y) = pixel;
+		gfx_context.encoder.draw(
+			&postprocess_context.ibuf,
+			&self.pso,
+			&depth_read::Data {
+				vbuf: postprocess_context.vbuf.clone(),
+				depth: depth_source.clone(),
+				sampler: self.sampler.clone(),
+				pixel: [x, y],
+				samples: self.samples,
+				dst: self.target.clone(),
+			},
+		);
+		let buffer = gfx_context.factory.create_download_buffer::<Depth>(1)?;
+		gfx_context
+			.encoder
+			.copy_texture_to_buffer_raw(
+				self.texture.raw(),
+				None,
+				gfx::texture::RawImageInfo {
+					xoffset: 0,
+					yoffset: 0,
+					zoffset: 0,
+					width: 1,
+					height: 1,
+					depth: 1,
+					format: <formats::DepthReadFormat as gfx::format::Formatted>::get_format(),
+					mipmap: 0,
+				},
+				buffer.raw(),
+				0,
+			)
+			.map_err(|e| Error::GenericError(format!("{:?}", e)))?;
+		gfx_context.flush();
+		let reader = gfx_context
+			.factory
+			.read_mapping(&buffer)
+			.map_err(|e| Error::GenericError(format!("{:?}", e)))?;
+		Ok(reader[0])
+	}
+}
+
+/// Multiplies two column-major 4x4 matrices
+fn multiply(a: &Matrix4, b: &Matrix4) -> [[f64; 4]; 4] {
+	let mut m = [[0.; 4]; 4];
+	for (c, column) in m.iter_mut().enumerate() {
+		for (r, value) in column.iter_mut().enumerate() {
+			*value = (0..4)
+				.map(|k| f64::from(a[k][r]) * f64::from(b[c][k]))
+				.sum();
+		}
+	}
+	m
+}
+
+/// Inverts a column-major 4x4 matrix by Gauss-Jordan elimination, `None` if singular
+fn invert(m: &[[f64; 4]; 4]) -> Option<[[f64; 4]; 4]> {
+	// row-major augmented copy, [m | I]
+	let mut a = [[0.; 8]; 4];
+	for r in 0..4 {
+		for c in 0..4 {
+			a[r][c] = m[c][r];
+		}
+		a[r][4 + r] = 1.;
+	}
+	for c in 0..4 {
+		let pivot = (c..4).max_by(|i, j| {
+			a[*i][c]
+				.abs()
+				.partial_cmp(&a[*j][c].abs())
+				.unwrap_or(std::cmp::Ordering::Equal)
+		})?;
+		if a[pivot][c].abs() < 1e-12 {
+			return None;
+		}
+		a.swap(c, pivot);
+		let p = a[c][c];
+		for value in a[c].iter_mut() {
+			*value /= p;
+		}
+		let pivot_row = a[c];
+		for (r, row) in a.iter_mut().enumerate() {
+			if r != c {
+				let f = row[c];
+				for (value, p) in row.iter_mut().zip(pivot_row.iter()) {
+					*value -= f * p;
+				}
+			}
+		}
+	}
+	let mut inverse = [[0.; 4]; 4];
+	for r in 0..4 {
+		for c in 0..4 {
+			inverse[c][r] = a[r][4 + c];
+		}
+	}
+	Some(inverse)
+}
+
+/// Transforms a point in normalized device coordinates back into world space.
+/// Returns `None` if the matrices are singular or the point lies on the camera plane
+/// * `ndc` position in normalized device coordinates, each axis in the range -1..1
+/// * `projection` column-major projection matrix
+/// * `view` column-major view matrix
+pub fn unproject(ndc: [f32; 3], projection: &Matrix4, view: &Matrix4) -> Option<[f32; 3]> {
+	let inverse = invert(&multiply(projection, view))?;
+	let p = [f64::from(ndc[0]), f64::from(ndc[1]), f64::from(ndc[2]), 1.];
+	let mut world = [0.; 4];
+	for (r, value) in world.iter_mut().enumerate() {
+		*value = (0..4).map(|k| inverse[k][r] * p[k]).sum();
+	}
+	if world[3].abs() < 1e-12 {
+		return None;
+	}
+	Some([
+		(world[0] / world[3]) as f32,
+		(world[1] / world[3]) as f32,
+		(world[2] / world[3]) as f32,
+	])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn projection() -> Matrix4 {
+		camera::perspective(1., 1.5, 0.1, 100.)
+	}
+
+	const VIEW: Matrix4 = [
+		[0.8, 0., 0.6, 0.],
+		[0., 1., 0., 0.],
+		[-0.6, 0., 0.8, 0.],
+		[1., -2., -5., 1.],
+	];
+
+	fn to_f64(m: &Matrix4) -> [[f64; 4]; 4] {
+		let mut r = [[0.; 4]; 4];
+		for (c, column) in r.iter_mut().enumerate() {
+			for (i, value) in column.iter_mut().enumerate() {
+				*value = f64::from(m[c][i]);
+			}
+		}
+		r
+	}
+
+	fn project(p: [f32; 3]) -> [f32; 3] {
+		let m = multiply(&projection(), &VIEW);
+		let v = [f64::from(p[0]), f64::from(p[1]), f64::from(p[2]), 1.];
+		let mut clip = [0.; 4];
+		for (r, value) in clip.iter_mut().enumerate() {
+			*value = (0..4).map(|k| m[k][r] * v[k]).sum();
+		}
+		[
+			(clip[0] / clip[3]) as f32,
+			(clip[1] / clip[3]) as f32,
+			(clip[2] / clip[3]) as f32,
+		]
+	}
+
+	#[test]
+	fn invert_gives_the_inverse() {
+		for m in &[projection(), VIEW] {
+			let m = to_f64(m);
+			let inverse = invert(&m).unwrap();
+			for i in 0..16 {
+				let (c, r) = (i / 4, i % 4);
+				let product: f64 = (0..4).map(|k| m[k][r] * inverse[c][k]).sum();
+				let expected = if r == c { 1. } else { 0. };
+				assert!(
+					(product - expected).abs() < 1e-9,
+					"{} at {},{}",
+					product,
+					c,
+					r
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn invert_needs_pivoting() {
+		// a permutation, with zeros on the diagonal
+		let m = [
+			[0., 1., 0., 0.],
+			[0., 0., 1., 0.],
+			[1., 0., 0., 0.],
+			[0., 0., 0., 1.],
+		];
+		let inverse = invert(&m).unwrap();
+		assert_eq!(inverse[0], [0., 0., 1., 0.]);
+		assert_eq!(inverse[1], [1., 0., 0., 0.]);
+		assert_eq!(inverse[2], [0., 1., 0., 0.]);
+	}
+
+	#[test]
+	fn invert_rejects_singular_matrices() {
+		let mut m = to_f64(&VIEW);
+		m[2] = m[0];
+		assert!(invert(&m).is_none());
+		assert!(invert(&[[0.; 4]; 4]).is_none());
+	}
+
+	#[test]
+	fn unproject_inverts_project() {
+		for p in &[
+			[0., 0., 0.],
+			[1., -2., 3.],
+			[-4., 1.5, -2.],
+			[0.25, 0.5, -20.],
+		] {
+			let world = unproject(project(*p), &projection(), &VIEW).unwrap();
+			for (a, b) in world.iter().zip(p.iter()) {
+				assert!((a - b).abs() < 1e-3, "{:?} != {:?}", world, p);
+			}
+		}
+	}
+
+	#[test]
+	fn unproject_maps_the_depth_range_to_the_clipping_planes() {
+		let eye = look_from(&VIEW);
+		for &(depth, distance) in &[(-1., 0.1), (1., 100.)] {
+			let world = unproject([0., 0., depth], &projection(), &VIEW).unwrap();
+			let d = (0..3)
+				.map(|i| (world[i] - eye[i]).powi(2))
+				.sum::<f32>()
+				.sqrt();
+			assert!(
+				(d - distance).abs() < distance * 1e-3,
+				"{} != {}",
+				d,
+				distance
+			);
+		}
+	}
+
+	/// The position of the eye of a rigid view matrix
+	fn look_from(view: &Matrix4) -> [f32; 3] {
+		let t = view[3];
+		let mut eye = [0.; 3];
+		for (i, value) in eye.iter_mut().enumerate() {
+			*value = -(0..3).map(|k| view[i][k] * t[k]).sum::<f32>();
+		}
+		eye
+	}
+
+	#[test]
+	fn unproject_rejects_singular_matrices() {
+		assert!(unproject([0.; 3], &[[0.; 4]; 4], &VIEW).is_none());
+	}
+}
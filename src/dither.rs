@@ -0,0 +1,211 @@
+//! Ordered dithering applied by the built-in postprocessing shaders, to hide the banding
+//! introduced when quantising to the 8 bit [formats::GtkTargetColorFormat]
+//!
+//! The threshold maps are computed on the CPU and uploaded as a float texture, tiled over the
+//! screen. [quantize()] performs the same arithmetic as the shader, and can be used to build
+//! reference images
+
+use super::*;
+
+/// Edge of the Bayer threshold map
+pub const BAYER_SIZE: usize = 8;
+/// Edge of the blue noise threshold map
+pub const BLUE_NOISE_SIZE: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Dithering pattern added to the output color before quantisation
+pub enum DitherMode {
+	/// No dithering
+	None,
+	/// 8x8 Bayer ordered dithering, cheap and regular
+	Bayer,
+	/// 64x64 blue noise, generated with the void-and-cluster method, less visible than `Bayer`
+	BlueNoise,
+}
+
+impl Default for DitherMode {
+	fn default() -> Self {
+		DitherMode::None
+	}
+}
+
+/// Returns the edge and the row-major thresholds (in the range 0..1) of the map for `mode`.
+/// `None` yields a single 0.5 threshold, which leaves colors unchanged
+pub fn threshold_map(mode: DitherMode) -> (usize, Vec<f32>) {
+	let (size, ranks) = match mode {
+		DitherMode::None => return (1, vec![0.5]),
+		DitherMode::Bayer => (BAYER_SIZE, bayer_ranks(BAYER_SIZE)),
+		DitherMode::BlueNoise => (BLUE_NOISE_SIZE, blue_noise_ranks(BLUE_NOISE_SIZE)),
+	};
+	let count = (size * size) as f32;
+	(
+		size,
+		ranks.iter().map(|r| (*r as f32 + 0.5) / count).collect(),
+	)
+}
+
+/// Reference implementation of the shader dithering step: quantises `value`
+/// (a color channel in display space, 0..1) to 8 bits for the pixel at `x`, `y`
+/// * `map` a threshold map as returned by [threshold_map()]
+pub fn quantize(map: &(usize, Vec<f32>), x: usize, y: usize, value: f32) -> u8 {
+	let (size, ref thresholds) = *map;
+	let threshold = thresholds[(y % size) * size + x % size];
+	let dithered = value + (threshold - 0.5) / 255.;
+	(dithered.max(0.).min(1.) * 255. + 0.5) as u8
+}
+
+/// Uploads the threshold map for `mode` into a texture, tiled over the screen by the shader
+/// * `factory` the factory attached to the current Gl context
+pub fn create_texture<R, F>(
+	factory: &mut F,
+	mode: DitherMode,
+) -> Result<gfx::handle::ShaderResourceView<R, formats::DitherView>>
+where
+	R: gfx::Resources,
+	F: gfx::Factory<R>,
+{
+	let (size, thresholds) = threshold_map(mode);
+	let size = size as gfx::texture::Size;
+	let (_, view) = factory.create_texture_immutable_u8::<formats::DitherFormat>(
+		gfx::texture::Kind::D2(size, size, gfx::texture::AaMode::Single),
+		gfx::texture::Mipmap::Provided,
+		&[gfx::memory::cast_slice(&thresholds)],
+	)?;
+	Ok(view)
+}
+
+/// Recursive Bayer matrix, `size` must be a power of two
+fn bayer_ranks(size: usize) -> Vec<usize> {
+	let mut ranks = vec![0];
+	let mut n = 1;
+	while n < size {
+		let mut next = vec![0; 4 * n * n];
+		for y in 0..n {
+			for x in 0..n {
+				let r = 4 * ranks[y * n + x];
+				next[y * 2 * n + x] = r;
+				next[y * 2 * n + x + n] = r + 2;
+				next[(y + n) * 2 * n + x] = r + 3;
+				next[(y + n) * 2 * n + x + n] = r + 1;
+			}
+		}
+		ranks = next;
+		n *= 2;
+	}
+	ranks
+}
+
+/// Void-and-cluster blue noise ranks (Ulichney 1993), on a toroidal `size` x `size` grid
+fn blue_noise_ranks(size: usize) -> Vec<usize> {
+	const SIGMA: f32 = 1.5;
+	let count = size * size;
+
+	// gaussian weights within 4 sigma, further contributions are negligible
+	let radius = (4. * SIGMA).ceil() as usize;
+	let side = 2 * radius + 1;
+	let mut kernel = vec![0.; side * side];
+	for dy in 0..side {
+		for dx in 0..side {
+			let x = dx as f32 - radius as f32;
+			let y = dy as f32 - radius as f32;
+			kernel[dy * side + dx] = (-(x * x + y * y) / (2. * SIGMA * SIGMA)).exp();
+		}
+	}
+
+	let splat = |energy: &mut Vec<f32>, index: usize, sign: f32| {
+		let (px, py) = (index % size, index / size);
+		for dy in 0..side {
+			let y = (py + size + dy - radius) % size;
+			for dx in 0..side {
+				let x = (px + size + dx - radius) % size;
+				energy[y * size + x] += sign * kernel[dy * side + dx];
+			}
+		}
+	};
+
+	// the set pixel with the highest energy (set == true),
+	// or the unset pixel with the lowest energy (set == false)
+	let extreme = |pattern: &Vec<bool>, energy: &Vec<f32>, set: bool| {
+		let mut best = None;
+		let mut best_energy = 0.;
+		for i in 0..count {
+			if pattern[i] == set {
+				let e = if set { energy[i] } else { -energy[i] };
+				if best.is_none() || e > best_energy {
+					best = Some(i);
+					best_energy = e;
+				}
+			}
+		}
+		best.unwrap()
+	};
+	let tightest_cluster = |pattern: &Vec<bool>, energy: &Vec<f32>| extreme(pattern, energy, true);
+	let largest_void = |pattern: &Vec<bool>, energy: &Vec<f32>| extreme(pattern, energy, false);
+
+	// deterministic initial pattern with about 10% of the pixels set
+	let mut seed: u32 = 0x2545_f491;
+	let mut pattern = vec![false; count];
+	let mut energy = vec![0.; count];
+	let mut ones = 0;
+	while ones < count / 10 {
+		seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+		let i = (seed >> 8) as usize % count;
+		if !pattern[i] {
+			pattern[i] = true;
+			splat(&mut energy, i, 1.);
+			ones += 1;
+		}
+	}
+
+	// move the tightest cluster into the largest void until they coincide
+	for _ in 0..count {
+		let cluster = tightest_cluster(&pattern, &energy);
+		pattern[cluster] = false;
+		splat(&mut energy, cluster, -1.);
+		let void = largest_void(&pattern, &energy);
+		pattern[void] = true;
+		splat(&mut energy, void, 1.);
+		if void == cluster {
+			break;
+		}
+	}
+
+	let mut ranks = vec![0; count];
+
+	// phase 1: rank the initial pattern by removing the tightest clusters
+	{
+		let mut pattern = pattern.clone();
+		let mut energy = energy.clone();
+		for rank in (0..ones).rev() {
+			let cluster = tightest_cluster(&pattern, &energy);
+			pattern[cluster] = false;
+			splat(&mut energy, cluster, -1.);
+			ranks[cluster] = rank;
+		}
+	}
+
+	// phase 2: fill the largest voids until half of the grid is set
+	for rank in ones..count / 2 {
+		let void = largest_void(&pattern, &energy);
+		pattern[void] = true;
+		splat(&mut energy, void, 1.);
+		ranks[void] = rank;
+	}
+
+	// phase 3: the unset pixels are now the minority, fill their tightest clusters. With their
+	// energy negated, the tightest cluster of unset pixels is the largest void
+	let mut energy = vec![0.; count];
+	for i in 0..count {
+		if !pattern[i] {
+			splat(&mut energy, i, -1.);
+		}
+	}
+	for rank in count / 2..count {
+		let cluster = largest_void(&pattern, &energy);
+		pattern[cluster] = true;
+		splat(&mut energy, cluster, 1.);
+		ranks[cluster] = rank;
+	}
+
+	ranks
+}
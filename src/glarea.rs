@@ -0,0 +1,878 @@
+//! A `gtk::GLArea` wrapper owning the [GlRenderContext] and the render callback, with the
+//! `realize`, `unrealize`, `resize` and `render` signals already connected
+//!
+//! The Gl context of a GlArea is destroyed whenever the widget is unrealized, e.g. when moved
+//! to another window or notebook page. The render context is then dropped with the Gl context still
+//! current, and the render callback is given a chance to release its resources
+//! ([GlRenderCallback::release()]) and re-create them in the new context ([GlRenderCallback::restore()]),
+//! otherwise it is re-created from scratch. The same happens after a Gl context reset, if the driver
+//! reports them (see [ContextReset])
+
+use super::*;
+use gdk::FrameClockExt;
+use gtk::prelude::*;
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use std::sync::{Arc, Mutex};
+
+/// Creates the render callback once the Gl context of the widget is available
+type CallbackFactory<CF, DF> =
+	Box<dyn FnMut(&mut GlGfxContext, &Viewport) -> Result<Box<dyn GlCallback<CF, DF>>>>;
+
+#[derive(Default)]
+/// Requirements of the Gl context and why the last one couldn't be created, shared with the
+/// `create-context` signal handler, which must be `Send`
+struct ContextSetup {
+	config: GlContextConfig,
+	error: Option<GlContextError>,
+}
+
+/// State shared between the [GfxGlArea] and the signal handlers of its widget
+struct GfxGlAreaState<CF, DF>
+where
+	CF: gfx::format::Formatted<View = formats::GtkTargetColorView>,
+	CF::Channel: gfx::format::TextureChannel + gfx::format::RenderChannel,
+	CF::Surface: gfx::format::RenderSurface + gfx::format::TextureSurface,
+	DF: gfx::format::Formatted,
+	DF::Channel: gfx::format::TextureChannel + gfx::format::RenderChannel,
+	DF::Surface: gfx::format::DepthSurface + gfx::format::TextureSurface,
+{
+	aa: gfx::texture::AaMode,
+	postprocess_shader: Option<Vec<u8>>,
+	create_callback: CallbackFactory<CF, DF>,
+	render_context: Option<GlRenderContext<CF, DF>>,
+	render_callback: Option<Box<dyn GlCallback<CF, DF>>>,
+	/// gesture recognisers attached to the widget, which doesn't keep them alive
+	gestures: Vec<gtk::Gesture>,
+	gesture_tracker: input::GestureTracker,
+	/// whether rendering continuously, see [GfxGlArea::start_animation()]
+	animating: bool,
+	/// the tick callback driving the animation, while attached
+	tick_callback: Option<u32>,
+	frame_timer: animation::FrameTimer,
+	/// handle for the redraw requests of other threads, created on demand
+	redraw_handle: Option<RedrawHandle>,
+	/// group joined by the render context every time it is created
+	share_group: Option<ShareGroup>,
+	context_setup: Arc<Mutex<ContextSetup>>,
+	error: Option<Error>,
+}
+
+impl<CF, DF> GfxGlAreaState<CF, DF>
+where
+	CF: gfx::format::Formatted<View = formats::GtkTargetColorView>,
+	CF::Channel: gfx::format::TextureChannel + gfx::format::RenderChannel,
+	CF::Surface: gfx::format::RenderSurface + gfx::format::TextureSurface,
+	DF: gfx::format::Formatted,
+	DF::Channel: gfx::format::TextureChannel + gfx::format::RenderChannel,
+	DF::Surface: gfx::format::DepthSurface + gfx::format::TextureSurface,
+{
+	fn realize(&mut self, widget: &gtk::GLArea) -> Result<()> {
+		if let Some(e) = widget.get_error() {
+			return Err(match self.context_setup.lock().unwrap().error {
+				Some(ref e) => Error::GlContextError(e.clone()),
+				None => e.into(),
+			});
+		}
+		widget.make_current();
+		let scale_factor = widget.get_scale_factor();
+		let allocation = widget.get_allocation();
+		let mut render_context = GlRenderContext::new(
+			self.aa,
+			allocation.width * scale_factor,
+			allocation.height * scale_factor,
+			self.postprocess_shader.as_ref().map(|s| s.as_slice()),
+		)?;
+		render_context.set_scale_factor(scale_factor);
+		render_context.set_share_group(self.share_group.as_ref())?;
+		let viewport = render_context.viewport();
+		let restored = match self.render_callback {
+			Some(ref mut render_callback) => render_callback
+				.restore(render_context.gfx_context_mut(), &viewport)
+				.is_ok(),
+			None => false,
+		};
+		if !restored {
+			self.render_callback = None;
+			self.render_callback = Some((self.create_callback)(
+				render_context.gfx_context_mut(),
+				&viewport,
+			)?);
+		}
+		self.render_context = Some(render_context);
+		Ok(())
+	}
+
+	fn unrealize(&mut self, widget: &gtk::GLArea) {
+		widget.make_current();
+		// the callback may hold resources of the context, release them first
+		if let Some(ref mut render_context) = self.render_context {
+			let released = match self.render_callback {
+				Some(ref mut render_callback) => {
+					match render_callback.release(render_context.gfx_context_mut()) {
+						Ok(GlRenderCallbackStatus::Continue) => true,
+						_ => false,
+					}
+				}
+				None => false,
+			};
+			if !released {
+				self.render_callback = None;
+			}
+		}
+		self.render_context = None;
+	}
+
+	fn resize(&mut self, widget: &gtk::GLArea, width: i32, height: i32) -> Result<()> {
+		if let (Some(render_context), Some(render_callback)) =
+			(self.render_context.as_mut(), self.render_callback.as_mut())
+		{
+			render_context.set_scale_factor(widget.get_scale_factor());
+			render_context.resize(width, height, Some(&mut **render_callback))?;
+		}
+		Ok(())
+	}
+
+	/// Renders a frame, queueing the next one if the contents are still settling. While not animating,
+	/// asks the callback whether to resume, the tick callback is attached by the caller.
+	/// Returns `false` if the Gl context has been reset
+	fn render(&mut self, widget: &gtk::GLArea) -> bool {
+		if let (Some(render_context), Some(render_callback)) =
+			(self.render_context.as_mut(), self.render_callback.as_mut())
+		{
+			render_context.with_gfx(&mut **render_callback);
+			if render_context.scheduler_mut().end_frame() {
+				// a render queued from within the render signal is lost once the frame is done,
+				// queue it afterwards
+				let widget = widget.downgrade();
+				gtk::idle_add(move || {
+					if let Some(widget) = widget.upgrade() {
+						widget.queue_render();
+					}
+					gtk::Continue(false)
+				});
+			}
+			if !self.animating && render_callback.resume_animation() == AnimationStatus::Continue {
+				self.animating = true;
+			}
+			return render_context.context_reset().is_none();
+		}
+		true
+	}
+
+	/// Queues a render, unless one is already queued
+	fn request_redraw(&mut self, widget: &gtk::GLArea) {
+		let queue = match self.render_context {
+			Some(ref mut render_context) => render_context.request_redraw(),
+			None => true,
+		};
+		if queue {
+			widget.queue_render();
+		}
+	}
+
+	/// Forwards an input event to the render callback, if realized and of type `R`, and queues a
+	/// render if the callback asks for it. The state is not borrowed while the callback runs, so it
+	/// can use the [GfxGlArea], e.g. to queue a render or pick, except for
+	/// [GfxGlArea::with_render_callback()], which doesn't find it
+	fn dispatch_input<R, F>(state: &Rc<RefCell<Self>>, widget: &gtk::GLArea, f: F)
+	where
+		R: GlInputCallback + 'static,
+		F: FnOnce(&mut R, &Viewport) -> bool,
+	{
+		let (mut render_callback, viewport) = {
+			let mut state = state.borrow_mut();
+			let viewport = match state.render_context {
+				Some(ref render_context) => render_context.viewport(),
+				None => return,
+			};
+			match state.render_callback.take() {
+				Some(render_callback) => (render_callback, viewport),
+				None => return,
+			}
+		};
+		let redraw = match render_callback.as_any_mut().downcast_mut::<R>() {
+			Some(input_callback) => f(input_callback, &viewport),
+			None => false,
+		};
+		let mut state = state.borrow_mut();
+		// unless the callback has been re-created meanwhile, e.g. realizing the widget again
+		if state.render_callback.is_none() {
+			state.render_callback = Some(render_callback);
+		}
+		if redraw {
+			state.request_redraw(widget);
+		}
+	}
+
+	/// Converts the values reported by a gesture recogniser via the tracker, and forwards the
+	/// resulting gesture to the render callback if of type `R`, as [GfxGlAreaState::dispatch_input()]
+	/// * `state` the state, not kept alive by the recognisers it owns
+	/// * `widget` the widget the recogniser is attached to, looked up on demand for the same reason
+	fn dispatch_gesture<R, F>(state: &Weak<RefCell<Self>>, widget: Option<gtk::Widget>, f: F)
+	where
+		R: GlInputCallback + 'static,
+		F: FnOnce(&mut input::GestureTracker, &Viewport) -> Option<Gesture>,
+	{
+		let (state, widget) = match (
+			state.upgrade(),
+			widget.and_then(|w| w.downcast::<gtk::GLArea>().ok()),
+		) {
+			(Some(state), Some(widget)) => (state, widget),
+			_ => return,
+		};
+		let gesture = {
+			let mut state = state.borrow_mut();
+			let viewport = match state.render_context {
+				Some(ref render_context) => render_context.viewport(),
+				None => return,
+			};
+			f(&mut state.gesture_tracker, &viewport)
+		};
+		if let Some(gesture) = gesture {
+			Self::dispatch_input(&state, &widget, |r: &mut R, viewport| {
+				r.gesture(viewport, &gesture)
+			});
+		}
+	}
+
+	/// Stops receiving the ticks of the frame clock
+	fn detach_tick_callback(&mut self, widget: &gtk::GLArea) {
+		if let Some(tick_callback) = self.tick_callback.take() {
+			widget.remove_tick_callback(tick_callback);
+		}
+	}
+
+	/// Invoked at every tick of the frame clock, lets the callback animate and schedules the next frame.
+	/// Returns `false` once the animation has stopped, which removes the tick callback
+	fn tick(&mut self, widget: &gtk::GLArea, frame_time: i64) -> bool {
+		let frame = self.frame_timer.tick(frame_time);
+		let status = match self.render_callback {
+			Some(ref mut render_callback) => render_callback.animate(&frame),
+			None => AnimationStatus::Continue,
+		};
+		match status {
+			AnimationStatus::Continue => {
+				self.request_redraw(widget);
+				true
+			}
+			AnimationStatus::Stop => {
+				self.animating = false;
+				self.tick_callback = None;
+				false
+			}
+		}
+	}
+
+	fn record<T>(&mut self, result: Result<T>) {
+		if let Err(e) = result {
+			self.error = Some(e);
+		}
+	}
+}
+
+/// A `gtk::GLArea` rendering via gfx. Owns the [GlRenderContext] and a boxed render callback,
+/// and takes care of (re-)creating them when the widget is realized, resizing them along with
+/// the widget and rendering on demand.
+/// * `CF` color format of the offline render target
+/// * `DF` depth format of the offline render target
+pub struct GfxGlArea<CF, DF>
+where
+	CF: gfx::format::Formatted<View = formats::GtkTargetColorView>,
+	CF::Channel: gfx::format::TextureChannel + gfx::format::RenderChannel,
+	CF::Surface: gfx::format::RenderSurface + gfx::format::TextureSurface,
+	DF: gfx::format::Formatted,
+	DF::Channel: gfx::format::TextureChannel + gfx::format::RenderChannel,
+	DF::Surface: gfx::format::DepthSurface + gfx::format::TextureSurface,
+{
+	widget: gtk::GLArea,
+	state: Rc<RefCell<GfxGlAreaState<CF, DF>>>,
+}
+
+impl<CF, DF> Clone for GfxGlArea<CF, DF>
+where
+	CF: gfx::format::Formatted<View = formats::GtkTargetColorView>,
+	CF::Channel: gfx::format::TextureChannel + gfx::format::RenderChannel,
+	CF::Surface: gfx::format::RenderSurface + gfx::format::TextureSurface,
+	DF: gfx::format::Formatted,
+	DF::Channel: gfx::format::TextureChannel + gfx::format::RenderChannel,
+	DF::Surface: gfx::format::DepthSurface + gfx::format::TextureSurface,
+{
+	/// Returns another handle to the same widget and render context
+	fn clone(&self) -> Self {
+		GfxGlArea {
+			widget: self.widget.clone(),
+			state: self.state.clone(),
+		}
+	}
+}
+
+/// A [GfxGlArea] with the default color and depth formats
+pub type DefaultGfxGlArea =
+	GfxGlArea<formats::DefaultRenderColorFormat, formats::DefaultRenderDepthFormat>;
+
+impl<CF, DF> GfxGlArea<CF, DF>
+where
+	CF: gfx::format::Formatted<View = formats::GtkTargetColorView> + 'static,
+	CF::Channel: gfx::format::TextureChannel + gfx::format::RenderChannel,
+	CF::Surface: gfx::format::RenderSurface + gfx::format::TextureSurface,
+	DF: gfx::format::Formatted + 'static,
+	DF::Channel: gfx::format::TextureChannel + gfx::format::RenderChannel,
+	DF::Surface: gfx::format::DepthSurface + gfx::format::TextureSurface,
+{
+	/// Creates a new `gtk::GLArea` and connects its signals
+	/// * `aa` antialiasing mode, currently supported `Single` and `Multi(4)`
+	/// * `create_callback` invoked with the Gl context current every time the widget is realized,
+	/// to create the render callback and its resources
+	pub fn new<F, R>(aa: gfx::texture::AaMode, mut create_callback: F) -> Self
+	where
+		F: FnMut(&mut GlGfxContext, &Viewport) -> Result<R> + 'static,
+		R: GlCallback<CF, DF> + 'static,
+	{
+		let state = Rc::new(RefCell::new(GfxGlAreaState {
+			aa,
+			postprocess_shader: None,
+			create_callback: Box::new(move |gfx_context, viewport| {
+				create_callback(gfx_context, viewport)
+					.map(|r| Box::new(r) as Box<dyn GlCallback<CF, DF>>)
+			}),
+			render_context: None,
+			render_callback: None,
+			gestures: Vec::new(),
+			gesture_tracker: input::GestureTracker::new(),
+			animating: false,
+			tick_callback: None,
+			frame_timer: animation::FrameTimer::new(),
+			redraw_handle: None,
+			share_group: None,
+			context_setup: Arc::new(Mutex::new(ContextSetup::default())),
+			error: None,
+		}));
+
+		let widget = gtk::GLArea::new();
+		// only render when asked to, the last frame is reused to redraw the widget otherwise
+		widget.set_auto_render(false);
+
+		let create_context = widget.connect("create-context", false, {
+			let context_setup = state.borrow().context_setup.clone();
+			move |values| {
+				let widget = values[0].get::<gtk::GLArea>()?;
+				let mut context_setup = context_setup.lock().unwrap();
+				let result = match widget.get_window() {
+					Some(window) => glcontext::create_context(&window, &context_setup.config),
+					None => Err(GlContextError::NotAvailable(
+						"The GlArea has no window".to_string(),
+					)),
+				};
+				match result {
+					Ok(context) => {
+						context_setup.error = None;
+						Some(context.to_value())
+					}
+					Err(e) => {
+						// the GlArea shows the message in place of its contents
+						widget.set_error(&e.to_glib_error());
+						context_setup.error = Some(e);
+						Some(None::<gdk::GLContext>.to_value())
+					}
+				}
+			}
+		});
+		state
+			.borrow_mut()
+			.record(create_context.map_err(Error::from));
+
+		widget.connect_realize({
+			let state = state.clone();
+			move |widget| {
+				{
+					let mut state = state.borrow_mut();
+					let result = state.realize(widget);
+					state.record(result);
+				}
+				Self::attach_tick_callback(&state, widget);
+			}
+		});
+
+		widget.connect_unrealize({
+			let state = state.clone();
+			move |widget| state.borrow_mut().unrealize(widget)
+		});
+
+		widget.connect_unmap({
+			let state = state.clone();
+			move |_widget| {
+				// an unmapped GlArea doesn't render, a queued render would never start
+				if let Some(ref mut render_context) = state.borrow_mut().render_context {
+					render_context.scheduler_mut().cancel_pending();
+				}
+			}
+		});
+
+		widget.connect_resize({
+			let state = state.clone();
+			move |widget, width, height| {
+				let mut state = state.borrow_mut();
+				let result = state.resize(widget, width, height);
+				state.record(result);
+			}
+		});
+
+		widget.connect_render({
+			let state = state.clone();
+			move |widget, _gl_context| {
+				let rendered = state.borrow_mut().render(widget);
+				Self::attach_tick_callback(&state, widget);
+				if !rendered {
+					// a new Gl context is only created when the widget is realized again
+					let widget = widget.clone();
+					gtk::idle_add(move || {
+						recreate_context(&widget);
+						gtk::Continue(false)
+					});
+				}
+				gtk::Inhibit(false)
+			}
+		});
+
+		GfxGlArea { widget, state }
+	}
+
+	/// Returns the wrapped widget, to be added to a container
+	pub fn widget(&self) -> &gtk::GLArea {
+		&self.widget
+	}
+
+	/// Schedules the rendering of a new frame. Requests made before the frame starts are coalesced,
+	/// so this can be called freely whenever something changes, including from within the callbacks
+	pub fn queue_render(&self) {
+		self.with_state_deferred(|state, widget| state.request_redraw(widget));
+	}
+
+	/// Keeps rendering for at least `frames` more frames, without further requests.
+	/// See [RenderScheduler::settle()]
+	/// * `frames` number of extra frames, e.g. while a damped motion settles
+	pub fn settle(&self, frames: u32) {
+		self.with_state_deferred(move |state, widget| {
+			if let Some(ref mut render_context) = state.render_context {
+				render_context.scheduler_mut().settle(frames);
+			}
+			state.request_redraw(widget);
+		});
+	}
+
+	/// Invokes `f` with the state now or, if it is already borrowed, e.g. by the render signal
+	/// handler running the callback, once the main loop is idle
+	fn with_state_deferred<F>(&self, f: F)
+	where
+		F: FnOnce(&mut GfxGlAreaState<CF, DF>, &gtk::GLArea) + 'static,
+	{
+		if let Ok(mut state) = self.state.try_borrow_mut() {
+			f(&mut state, &self.widget);
+			return;
+		}
+		let (widget, state) = (self.widget.downgrade(), Rc::downgrade(&self.state));
+		let mut f = Some(f);
+		gtk::idle_add(move || {
+			if let (Some(widget), Some(state), Some(f)) =
+				(widget.upgrade(), state.upgrade(), f.take())
+			{
+				f(&mut state.borrow_mut(), &widget);
+			}
+			gtk::Continue(false)
+		});
+	}
+
+	/// Returns a `Send` handle that worker threads can use to request a redraw, coalesced with the
+	/// other requests. The handle doesn't keep the widget alive, requests are ignored once it is gone
+	pub fn redraw_handle(&self) -> RedrawHandle {
+		let mut state = self.state.borrow_mut();
+		if state.redraw_handle.is_none() {
+			let widget = self.widget.downgrade();
+			let weak_state = Rc::downgrade(&self.state);
+			state.redraw_handle = Some(RedrawHandle::new(move || {
+				if let (Some(widget), Some(state)) = (widget.upgrade(), weak_state.upgrade()) {
+					state.borrow_mut().request_redraw(&widget);
+				}
+			}));
+		}
+		state.redraw_handle.clone().unwrap()
+	}
+
+	/// Changes the requirements of the Gl context. If the widget is realized, the Gl context, the
+	/// render context and the render callback are re-created. If they can't be satisfied, the GlArea
+	/// shows the reason in place of its contents, and [GfxGlArea::take_error()] returns it as
+	/// [Error::GlContextError]
+	/// * `config` required version, profile and flags
+	pub fn set_context_config(&self, config: GlContextConfig) {
+		self.state.borrow().context_setup.lock().unwrap().config = config;
+		if self.widget.get_realized() {
+			recreate_context(&self.widget);
+		}
+	}
+
+	/// Returns the requirements of the Gl context
+	pub fn context_config(&self) -> GlContextConfig {
+		self.state.borrow().context_setup.lock().unwrap().config
+	}
+
+	/// Returns why the Gl context couldn't be created the last time the widget was realized, if it failed
+	pub fn context_error(&self) -> Option<GlContextError> {
+		self.state
+			.borrow()
+			.context_setup
+			.lock()
+			.unwrap()
+			.error
+			.clone()
+	}
+
+	/// Shares Gl objects with the other GlAreas of `share_group`, now if the widget is realized and
+	/// whenever it is realized again. The GlAreas must be in the same toplevel window. The render
+	/// callback is not re-created, resources it created with the previous factory remain valid
+	/// * `share_group` the group to join, `None` to leave the current one
+	pub fn set_share_group(&self, share_group: Option<&ShareGroup>) -> Result<()> {
+		let mut state = self.state.borrow_mut();
+		state.share_group = share_group.cloned();
+		if let Some(ref mut render_context) = state.render_context {
+			self.widget.make_current();
+			render_context.set_share_group(share_group)?;
+		}
+		Ok(())
+	}
+
+	/// Creates a [ResourceLoader], uploading resources on a worker thread with a Gl context sharing
+	/// its objects with the one of the widget. Fails if the widget is not realized, or on X11
+	pub fn resource_loader(&self) -> Result<ResourceLoader> {
+		match self.widget.get_window() {
+			Some(window) => ResourceLoader::new(&window),
+			None => Err(Error::GenericError(
+				"The GlArea must be realized to share its Gl context".to_string(),
+			)),
+		}
+	}
+
+	/// `true` if no frame is queued and nothing is settling or animating
+	pub fn is_idle(&self) -> bool {
+		let state = self.state.borrow();
+		!state.animating
+			&& state
+				.render_context
+				.as_ref()
+				.map(|r| r.scheduler().is_idle())
+				.unwrap_or(true)
+	}
+
+	/// Returns the current antialiasing mode
+	pub fn aa(&self) -> gfx::texture::AaMode {
+		self.state.borrow().aa
+	}
+
+	/// Changes the antialiasing mode. If the widget is realized, the render context and
+	/// the render callback are re-created
+	/// * `aa` antialiasing mode, currently supported `Single` and `Multi(4)`
+	pub fn set_aa(&self, aa: gfx::texture::AaMode) -> Result<()> {
+		let mut state = self.state.borrow_mut();
+		state.aa = aa;
+		if state.render_context.is_some() {
+			state.unrealize(&self.widget);
+			state.realize(&self.widget)?;
+			self.widget.queue_render();
+		}
+		Ok(())
+	}
+
+	/// Replaces the postprocessing pixel shader, now and whenever the widget is realized again
+	/// * `postprocess_shader` source code of the pixel shader, the built-in shaders are used if `None`
+	pub fn set_postprocess_shader(&self, postprocess_shader: Option<&[u8]>) -> Result<()> {
+		let mut state = self.state.borrow_mut();
+		state.postprocess_shader = postprocess_shader.map(|s| s.to_vec());
+		if let Some(ref mut render_context) = state.render_context {
+			self.widget.make_current();
+			render_context.set_postprocess_shader(postprocess_shader)?;
+			self.widget.queue_render();
+		}
+		Ok(())
+	}
+
+	/// Invokes `f` with the Gl context current and the render context, e.g. to pick objects
+	/// or change the postprocessing settings. Returns `None` if the widget is not realized.
+	/// Must not be called from within the render callback
+	pub fn with_render_context<T, F>(&self, f: F) -> Option<T>
+	where
+		F: FnOnce(&mut GlRenderContext<CF, DF>) -> T,
+	{
+		let mut state = self.state.borrow_mut();
+		match state.render_context {
+			Some(ref mut render_context) => {
+				self.widget.make_current();
+				Some(f(render_context))
+			}
+			None => None,
+		}
+	}
+
+	/// Invokes `f` with the render callback, downcast to its concrete type `R`.
+	/// Returns `None` if the widget is not realized or `R` is not the type of the callback, and
+	/// from within the input callbacks, as the callback is already borrowed.
+	/// Must not be called from within the render callback
+	pub fn with_render_callback<R, T, F>(&self, f: F) -> Option<T>
+	where
+		R: 'static,
+		F: FnOnce(&mut R) -> T,
+	{
+		let mut state = self.state.borrow_mut();
+		state
+			.render_callback
+			.as_mut()
+			.and_then(|c| c.as_any_mut().downcast_mut::<R>())
+			.map(f)
+	}
+
+	/// Forwards pointer, button, scroll, key and focus events received by the widget to the render
+	/// callback, if its concrete type is `R`, and queues a render whenever the callback asks for it.
+	/// Enables the required events on the widget and lets it take the keyboard focus when clicked.
+	/// The input callbacks can use the [GfxGlArea], e.g. to queue a render or read back pixels.
+	/// Must be called only once
+	pub fn enable_input<R>(&self)
+	where
+		R: GlInputCallback + 'static,
+	{
+		let widget = &self.widget;
+		widget.add_events(input::event_mask().bits() as i32);
+		widget.set_can_focus(true);
+
+		widget.connect_motion_notify_event({
+			let state = self.state.clone();
+			move |widget, event| {
+				GfxGlAreaState::dispatch_input(&state, widget, |r: &mut R, viewport| {
+					r.pointer_motion(viewport, &PointerMotion::new(event, viewport))
+				});
+				gtk::Inhibit(false)
+			}
+		});
+
+		let on_button = {
+			let state = self.state.clone();
+			move |widget: &gtk::GLArea, event: &gdk::EventButton| {
+				if event.get_event_type() == gdk::EventType::ButtonPress {
+					widget.grab_focus();
+				}
+				GfxGlAreaState::dispatch_input(&state, widget, |r: &mut R, viewport| {
+					r.pointer_button(viewport, &PointerButton::new(event, viewport))
+				});
+				gtk::Inhibit(false)
+			}
+		};
+		widget.connect_button_press_event(on_button.clone());
+		widget.connect_button_release_event(on_button);
+
+		widget.connect_scroll_event({
+			let state = self.state.clone();
+			move |widget, event| {
+				GfxGlAreaState::dispatch_input(&state, widget, |r: &mut R, viewport| {
+					r.scroll(viewport, &Scroll::new(event, viewport))
+				});
+				gtk::Inhibit(false)
+			}
+		});
+
+		let on_key = {
+			let state = self.state.clone();
+			move |widget: &gtk::GLArea, event: &gdk::EventKey| {
+				GfxGlAreaState::dispatch_input(&state, widget, |r: &mut R, viewport| {
+					r.key(viewport, &Key::new(event))
+				});
+				gtk::Inhibit(false)
+			}
+		};
+		widget.connect_key_press_event(on_key.clone());
+		widget.connect_key_release_event(on_key);
+
+		let on_focus = |focused: bool| {
+			let state = self.state.clone();
+			move |widget: &gtk::GLArea, _event: &gdk::EventFocus| {
+				GfxGlAreaState::dispatch_input(&state, widget, |r: &mut R, viewport| {
+					r.focus(viewport, focused)
+				});
+				gtk::Inhibit(false)
+			}
+		};
+		widget.connect_focus_in_event(on_focus(true));
+		widget.connect_focus_out_event(on_focus(false));
+	}
+
+	/// Recognises pinch, two finger rotate, single finger drag and swipe touch gestures over the widget,
+	/// and forwards them to the render callback as [Gesture] events, if its concrete type is `R`.
+	/// Single finger drags are only recognised on touchscreens, mouse drags are still delivered
+	/// as pointer events by [GfxGlArea::enable_input()]. Must be called only once
+	pub fn enable_gestures<R>(&self)
+	where
+		R: GlInputCallback + 'static,
+	{
+		let widget = &self.widget;
+		widget.add_events(gdk::EventMask::TOUCH_MASK.bits() as i32);
+
+		let zoom = gtk::GestureZoom::new(widget);
+		zoom.connect_scale_changed({
+			let state = Rc::downgrade(&self.state);
+			move |gesture, scale| {
+				let center = gesture.get_bounding_box_center().unwrap_or((0., 0.));
+				GfxGlAreaState::dispatch_gesture::<R, _>(
+					&state,
+					gesture.get_widget(),
+					|tracker, viewport| Some(tracker.pinch(center, scale, viewport)),
+				);
+			}
+		});
+		zoom.connect_end({
+			let state = Rc::downgrade(&self.state);
+			move |gesture, _sequence| {
+				GfxGlAreaState::dispatch_gesture::<R, _>(
+					&state,
+					gesture.get_widget(),
+					|tracker, viewport| tracker.pinch_end(viewport),
+				);
+			}
+		});
+
+		let rotate = gtk::GestureRotate::new(widget);
+		rotate.connect_angle_changed({
+			let state = Rc::downgrade(&self.state);
+			move |gesture, angle, _angle_delta| {
+				let center = gesture.get_bounding_box_center().unwrap_or((0., 0.));
+				GfxGlAreaState::dispatch_gesture::<R, _>(
+					&state,
+					gesture.get_widget(),
+					|tracker, viewport| Some(tracker.rotate(center, angle, viewport)),
+				);
+			}
+		});
+		rotate.connect_end({
+			let state = Rc::downgrade(&self.state);
+			move |gesture, _sequence| {
+				GfxGlAreaState::dispatch_gesture::<R, _>(
+					&state,
+					gesture.get_widget(),
+					|tracker, viewport| tracker.rotate_end(viewport),
+				);
+			}
+		});
+
+		let drag = gtk::GestureDrag::new(widget);
+		drag.set_touch_only(true);
+		drag.connect_drag_begin({
+			let state = Rc::downgrade(&self.state);
+			move |gesture, x, y| {
+				GfxGlAreaState::dispatch_gesture::<R, _>(
+					&state,
+					gesture.get_widget(),
+					|tracker, viewport| Some(tracker.drag_begin((x, y), viewport)),
+				);
+			}
+		});
+		drag.connect_drag_update({
+			let state = Rc::downgrade(&self.state);
+			move |gesture, x, y| {
+				GfxGlAreaState::dispatch_gesture::<R, _>(
+					&state,
+					gesture.get_widget(),
+					|tracker, viewport| tracker.drag_update((x, y), viewport),
+				);
+			}
+		});
+		drag.connect_drag_end({
+			let state = Rc::downgrade(&self.state);
+			move |gesture, x, y| {
+				GfxGlAreaState::dispatch_gesture::<R, _>(
+					&state,
+					gesture.get_widget(),
+					|tracker, viewport| tracker.drag_end((x, y), viewport),
+				);
+			}
+		});
+
+		let swipe = gtk::GestureSwipe::new(widget);
+		swipe.set_touch_only(true);
+		swipe.connect_swipe({
+			let state = Rc::downgrade(&self.state);
+			move |gesture, velocity_x, velocity_y| {
+				let position = gesture.get_point(None).unwrap_or((0., 0.));
+				GfxGlAreaState::dispatch_gesture::<R, _>(
+					&state,
+					gesture.get_widget(),
+					|tracker, viewport| {
+						Some(tracker.swipe(position, (velocity_x, velocity_y), viewport))
+					},
+				);
+			}
+		});
+
+		let mut state = self.state.borrow_mut();
+		state.gestures.push(zoom.upcast());
+		state.gestures.push(rotate.upcast());
+		state.gestures.push(drag.upcast());
+		state.gestures.push(swipe.upcast());
+	}
+
+	/// Starts rendering continuously, a frame for every tick of the frame clock of the widget, until
+	/// stopped. Before each frame, [GlRenderCallback::animate()] is invoked with the time of the frame,
+	/// and can stop the animation
+	pub fn start_animation(&self) {
+		self.state.borrow_mut().animating = true;
+		Self::attach_tick_callback(&self.state, &self.widget);
+	}
+
+	/// Stops rendering continuously, frames are rendered on demand only
+	pub fn stop_animation(&self) {
+		let mut state = self.state.borrow_mut();
+		state.animating = false;
+		state.detach_tick_callback(&self.widget);
+	}
+
+	/// `true` while rendering continuously
+	pub fn is_animating(&self) -> bool {
+		self.state.borrow().animating
+	}
+
+	/// The timing of the last animation frame, `None` if no frame has been animated yet
+	pub fn frame_info(&self) -> Option<FrameInfo> {
+		self.state.borrow().frame_timer.last()
+	}
+
+	/// Starts receiving the ticks of the frame clock, if animating. Gtk follows the widget
+	/// to the frame clock of its toplevel window. The callback holds the state weakly,
+	/// and removes itself once the state is gone
+	fn attach_tick_callback(state: &Rc<RefCell<GfxGlAreaState<CF, DF>>>, widget: &gtk::GLArea) {
+		let mut state_mut = state.borrow_mut();
+		if !state_mut.animating || state_mut.tick_callback.is_some() {
+			return;
+		}
+		state_mut.frame_timer.reset();
+		let state = Rc::downgrade(state);
+		state_mut.tick_callback = Some(widget.add_tick_callback(move |widget, frame_clock| {
+			gtk::Continue(match state.upgrade() {
+				Some(state) => state
+					.borrow_mut()
+					.tick(widget, frame_clock.get_frame_time()),
+				None => false,
+			})
+		}));
+	}
+
+	/// Returns and clears the last error raised while realizing, resizing or reconfiguring the widget
+	pub fn take_error(&self) -> Option<Error> {
+		self.state.borrow_mut().error.take()
+	}
+}
+
+/// Unrealizes and realizes the widget again, which creates a new Gl context
+fn recreate_context(widget: &gtk::GLArea) {
+	let mapped = widget.get_mapped();
+	widget.unrealize();
+	widget.realize();
+	if mapped {
+		widget.map();
+	}
+	widget.queue_render();
+}
@@ -0,0 +1,199 @@
+//! Configuration of the Gdk Gl context of a GlArea: required version, core or compatibility
+//! profile or Gl ES, debug and forward compatible flags. Gdk only offers the defaults otherwise,
+//! which may not satisfy the requirements of the render context
+
+use super::*;
+use gdk::{GLContextExt, WindowExt};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// The Gl API and profile requested
+pub enum GlProfile {
+	/// Desktop Gl, core profile. A legacy context is rejected
+	Core,
+	/// Desktop Gl, either core or legacy. Gdk only falls back to a legacy context when
+	/// a core one can't be created
+	Compatibility,
+	/// Gl ES, 3.0 or later. The built-in postprocessing shaders switch to GLSL ES,
+	/// see [shaders::ShaderDialect]. Requires Gtk 3.22 and the `gles` feature, otherwise
+	/// the context is reported as [GlContextError::Unsupported]. Picking, depth and color
+	/// read back and the selection outline are not available
+	Es,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Requirements of the Gdk Gl context, see [GfxGlArea::set_context_config()]
+pub struct GlContextConfig {
+	/// Minimum `(major, minor)` version
+	pub version: (i32, i32),
+	/// API and profile
+	pub profile: GlProfile,
+	/// Creates a debug context, which reports errors and performance warnings
+	pub debug: bool,
+	/// Creates a forward compatible context, without any deprecated functionality
+	pub forward_compatible: bool,
+}
+
+impl Default for GlContextConfig {
+	/// Gl 3.2 core profile, as required by the built-in shaders
+	fn default() -> Self {
+		GlContextConfig {
+			version: (3, 2),
+			profile: GlProfile::Core,
+			debug: false,
+			forward_compatible: false,
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Why a Gl context satisfying a [GlContextConfig] couldn't be created
+pub enum GlContextError {
+	/// Gdk can't create any Gl context for the window, e.g. Gl is disabled or not supported
+	NotAvailable(String),
+	/// The context can't be created with the requested version, profile or flags
+	Unsupported {
+		/// The requested configuration
+		config: GlContextConfig,
+		/// The message reported by Gdk
+		message: String,
+	},
+	/// The context has been created, but with an older version than required
+	VersionTooOld {
+		/// The minimum version required
+		required: (i32, i32),
+		/// The version of the context
+		actual: (i32, i32),
+	},
+	/// A core profile was required, but only a legacy context is available
+	LegacyProfile,
+}
+
+impl fmt::Display for GlContextError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			GlContextError::NotAvailable(ref message) => {
+				write!(f, "OpenGL is not available: {}", message)
+			}
+			GlContextError::Unsupported {
+				ref config,
+				ref message,
+			} => write!(
+				f,
+				"{} {}.{} is required, but not supported by this system: {}",
+				match config.profile {
+					GlProfile::Core => "OpenGL core profile",
+					GlProfile::Compatibility => "OpenGL",
+					GlProfile::Es => "OpenGL ES",
+				},
+				config.version.0,
+				config.version.1,
+				message
+			),
+			GlContextError::VersionTooOld { required, actual } => write!(
+				f,
+				"OpenGL {}.{} is required, but only {}.{} is available",
+				required.0, required.1, actual.0, actual.1
+			),
+			GlContextError::LegacyProfile => write!(
+				f,
+				"An OpenGL core profile is required, but only a legacy context is available"
+			),
+		}
+	}
+}
+
+impl GlContextError {
+	/// The Gdk error reported by the GlArea, whose message is shown in place of its contents
+	pub fn to_glib_error(&self) -> glib::Error {
+		let kind = match *self {
+			GlContextError::NotAvailable(_) => gdk::GLError::NotAvailable,
+			_ => gdk::GLError::UnsupportedProfile,
+		};
+		glib::Error::new(kind, &self.to_string())
+	}
+}
+
+/// Creates and realizes a Gl context for `window` satisfying `config`
+/// * `window` the Gdk window of the GlArea
+/// * `config` the requirements of the context
+pub fn create_context(
+	window: &gdk::Window,
+	config: &GlContextConfig,
+) -> std::result::Result<gdk::GLContext, GlContextError> {
+	let context = window
+		.create_gl_context()
+		.map_err(|e| GlContextError::NotAvailable(e.to_string()))?;
+	context.set_required_version(config.version.0, config.version.1);
+	set_use_es(&context, config)?;
+	context.set_debug_enabled(config.debug);
+	context.set_forward_compatible(config.forward_compatible);
+	context.realize().map_err(|e| GlContextError::Unsupported {
+		config: *config,
+		message: e.to_string(),
+	})?;
+	let actual = context.get_version();
+	if actual < config.version {
+		Err(GlContextError::VersionTooOld {
+			required: config.version,
+			actual,
+		})
+	} else if config.profile == GlProfile::Core && is_legacy(&context) {
+		Err(GlContextError::LegacyProfile)
+	} else {
+		Ok(context)
+	}
+}
+
+/// Requests a Gl ES context or a desktop Gl one
+#[cfg(feature = "gles")]
+fn set_use_es(
+	context: &gdk::GLContext,
+	config: &GlContextConfig,
+) -> std::result::Result<(), GlContextError> {
+	context.set_use_es(if config.profile == GlProfile::Es {
+		1
+	} else {
+		0
+	});
+	Ok(())
+}
+
+/// Gl ES contexts can only be requested from Gtk 3.22, with the `gles` feature
+#[cfg(not(feature = "gles"))]
+fn set_use_es(
+	_context: &gdk::GLContext,
+	config: &GlContextConfig,
+) -> std::result::Result<(), GlContextError> {
+	if config.profile == GlProfile::Es {
+		Err(GlContextError::Unsupported {
+			config: *config,
+			message: "Gl ES contexts require Gtk 3.22 and the gles feature of gfx-gtk".to_string(),
+		})
+	} else {
+		Ok(())
+	}
+}
+
+/// `true` if the realized `context` has no core profile
+#[cfg(feature = "gles")]
+fn is_legacy(context: &gdk::GLContext) -> bool {
+	context.is_legacy()
+}
+
+/// `true` if the realized `context` has no core profile. Gdk only reports it from Gtk 3.20,
+/// the profile is queried with the context current
+#[cfg(not(feature = "gles"))]
+fn is_legacy(context: &gdk::GLContext) -> bool {
+	let previous = gdk::GLContext::get_current();
+	context.make_current();
+	let mut profile = 0;
+	unsafe {
+		gl::GetIntegerv(gl::CONTEXT_PROFILE_MASK, &mut profile);
+	}
+	match previous {
+		Some(previous) => previous.make_current(),
+		None => gdk::GLContext::clear_current(),
+	}
+	profile as u32 & gl::CONTEXT_CORE_PROFILE_BIT == 0
+}
@@ -0,0 +1,552 @@
+//! Performance overlay: frame rate, frame time graph, CPU and GPU timings from the frame
+//! statistics, viewport size, AA mode and formats, drawn with a built-in bitmap font onto the
+//! postprocessing target at native resolution, after the postprocessing stage
+
+use super::*;
+
+// Overlay gfx vertex structure
+gfx_vertex_struct!(HudVertex {
+	pos: [f32; 2] = "a_Pos",
+	tex_coord: [f32; 2] = "a_TexCoord",
+	color: [f32; 4] = "a_Color",
+});
+
+// Overlay gfx pipeline definitions
+gfx_pipeline!(hud {
+		vbuf: gfx::VertexBuffer<HudVertex> = (),
+		font: gfx::TextureSampler<formats::HudFontView> = "t_Font",
+		dst: gfx::BlendTarget<formats::GtkTargetColorFormat> = ("o_Color", gfx::state::ColorMask::all(), gfx::preset::blend::ALPHA),
+	}
+);
+
+/// Width of a glyph in font texels
+const GLYPH_WIDTH: usize = 5;
+/// Height of a glyph in font texels
+const GLYPH_HEIGHT: usize = 7;
+/// Horizontal distance between glyphs in font texels
+const CELL_WIDTH: usize = GLYPH_WIDTH + 1;
+/// Vertical distance between lines in font texels
+const CELL_HEIGHT: usize = GLYPH_HEIGHT + 2;
+/// Padding around the contents of the overlay in font texels
+const PADDING: usize = 3;
+/// Height of the frame time graph in font texels
+const GRAPH_HEIGHT: usize = 32;
+/// Frame time at the top of the graph, in milliseconds
+const GRAPH_MAX_MS: f32 = 1000. / 30.;
+/// Frame time of the reference line in the graph, in milliseconds
+const GRAPH_TARGET_MS: f32 = 1000. / 60.;
+/// Maximum number of vertices drawn per frame
+const MAX_VERTICES: usize = 6 * 2048;
+/// Number of frames retained by the frame statistics enabled along with the overlay
+pub const DEFAULT_STATS_CAPACITY: usize = 240;
+
+/// Glyphs of the built-in font, rows top to bottom, bits left to right. Lower case letters
+/// are drawn as upper case, missing characters as `?`
+const FONT: &[(char, [u8; GLYPH_HEIGHT])] = &[
+	(' ', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+	('?', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04]),
+	('0', [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E]),
+	('1', [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+	('2', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F]),
+	('3', [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E]),
+	('4', [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02]),
+	('5', [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E]),
+	('6', [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E]),
+	('7', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+	('8', [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E]),
+	('9', [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C]),
+	('A', [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11]),
+	('B', [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E]),
+	('C', [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E]),
+	('D', [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C]),
+	('E', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F]),
+	('F', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10]),
+	('G', [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F]),
+	('H', [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11]),
+	('I', [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+	('J', [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C]),
+	('K', [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11]),
+	('L', [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F]),
+	('M', [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11]),
+	('N', [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11]),
+	('O', [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+	('P', [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10]),
+	('Q', [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D]),
+	('R', [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11]),
+	('S', [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E]),
+	('T', [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+	('U', [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+	('V', [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04]),
+	('W', [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A]),
+	('X', [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11]),
+	('Y', [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04]),
+	('Z', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F]),
+	('.', [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C]),
+	(',', [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08]),
+	(':', [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00]),
+	('/', [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00]),
+	('-', [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00]),
+	('+', [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00]),
+	('=', [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00]),
+	('_', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F]),
+	('@', [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E]),
+	('%', [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03]),
+	('(', [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02]),
+	(')', [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08]),
+];
+
+/// Index of the glyph of `c` in [FONT]
+fn glyph_index(c: char) -> usize {
+	let c = c.to_ascii_uppercase();
+	FONT.iter().position(|&(g, _)| g == c).unwrap_or(1)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Corner of the GlArea the overlay is anchored to
+pub enum HudCorner {
+	/// Top left corner
+	TopLeft,
+	/// Top right corner
+	TopRight,
+	/// Bottom left corner
+	BottomLeft,
+	/// Bottom right corner
+	BottomRight,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Appearance of the performance overlay
+pub struct HudSettings {
+	/// Corner the overlay is anchored to
+	pub corner: HudCorner,
+	/// Size of a font texel in framebuffer pixels, `0` to follow the scale factor of the GlArea
+	pub scale: u32,
+	/// Color of the text, already in display (sRGB) space. Alpha is used for blending
+	pub text_color: Rgba,
+	/// Color of the panel behind the text, already in display (sRGB) space
+	pub background_color: Rgba,
+}
+
+impl Default for HudSettings {
+	fn default() -> Self {
+		HudSettings {
+			corner: HudCorner::TopLeft,
+			scale: 0,
+			text_color: [1., 1., 1., 1.],
+			background_color: [0., 0., 0., 0.6],
+		}
+	}
+}
+
+/// The resources needed to draw the performance overlay
+pub struct HudContext<D>
+where
+	D: gfx::Device,
+{
+	/// current appearance of the overlay
+	pub settings: HudSettings,
+	/// pipeline state object blending the overlay onto the postprocessing target
+	pso: gfx::PipelineState<D::Resources, hud::Meta>,
+	/// glyph atlas of the built-in font
+	font: gfx::handle::ShaderResourceView<D::Resources, formats::HudFontView>,
+	/// nearest sampler, font texels are mapped onto whole pixels
+	sampler: gfx::handle::Sampler<D::Resources>,
+	/// vertices of the overlay, updated every frame
+	vbuf: gfx::handle::Buffer<D::Resources, HudVertex>,
+	/// CPU copy of the vertices being built
+	vertices: Vec<HudVertex>,
+}
+
+/// Builds the quads of the overlay, in framebuffer pixels
+struct QuadBuilder<'a> {
+	vertices: &'a mut Vec<HudVertex>,
+	width: f32,
+	height: f32,
+	scale: f32,
+}
+
+impl<'a> QuadBuilder<'a> {
+	/// Adds a quad, `x`, `y` is the bottom left corner in framebuffer pixels. Solid if
+	/// `tex_coords` is `None`, otherwise masked by the atlas rectangle `[left, top, right, bottom]`
+	fn quad(&mut self, x: f32, y: f32, w: f32, h: f32, tex_coords: Option<[f32; 4]>, color: Rgba) {
+		if self.vertices.len() + 6 > MAX_VERTICES {
+			return;
+		}
+		let uv = tex_coords.unwrap_or([-1.; 4]);
+		let (u0, v0, u1, v1) = (uv[0], uv[1], uv[2], uv[3]);
+		let (width, height) = (self.width, self.height);
+		let ndc = |px: f32, py: f32| [px / width * 2. - 1., py / height * 2. - 1.];
+		let corners = [
+			(x, y, u0, v1),
+			(x + w, y, u1, v1),
+			(x + w, y + h, u1, v0),
+			(x, y, u0, v1),
+			(x + w, y + h, u1, v0),
+			(x, y + h, u0, v0),
+		];
+		for &(px, py, u, v) in corners.iter() {
+			self.vertices.push(HudVertex {
+				pos: ndc(px, py),
+				tex_coord: [u, v],
+				color,
+			});
+		}
+	}
+
+	/// Adds a line of text, `x`, `y` is the top left corner in framebuffer pixels
+	fn text(&mut self, x: f32, y: f32, text: &str, color: Rgba) {
+		let s = self.scale;
+		let atlas_width = (FONT.len() * CELL_WIDTH) as f32;
+		for (i, c) in text.chars().enumerate() {
+			if c == ' ' {
+				continue;
+			}
+			let left = (glyph_index(c) * CELL_WIDTH) as f32;
+			self.quad(
+				x + (i * CELL_WIDTH) as f32 * s,
+				y - GLYPH_HEIGHT as f32 * s,
+				GLYPH_WIDTH as f32 * s,
+				GLYPH_HEIGHT as f32 * s,
+				Some([
+					left / atlas_width,
+					0.,
+					(left + GLYPH_WIDTH as f32) / atlas_width,
+					GLYPH_HEIGHT as f32 / (GLYPH_HEIGHT + 1) as f32,
+				]),
+				color,
+			);
+		}
+	}
+}
+
+/// Milliseconds in a duration
+fn ms(d: std::time::Duration) -> f32 {
+	d.as_secs() as f32 * 1e3 + d.subsec_nanos() as f32 * 1e-6
+}
+
+/// Upper case name of a format, e.g. `R8_G8_B8_A8 UNORM`
+fn format_name(format: gfx::format::Format) -> String {
+	format!("{:?} {:?}", format.0, format.1).to_uppercase()
+}
+
+impl HudContext<GlDevice> {
+	/// Creates the overlay PSO, the font atlas and the vertex buffer
+	/// * `factory` the factory attached to the current Gl context
+	/// * `settings` initial appearance of the overlay
+	/// * `dialect` shading language of the Gl context
+	pub fn new(
+		factory: &mut GlFactory,
+		settings: HudSettings,
+		dialect: ShaderDialect,
+	) -> Result<Self> {
+		use gfx::traits::FactoryExt;
+
+		let (vertex_shader, pixel_shader) = dialect.hud_shaders();
+		let pso = factory.create_pipeline_simple(
+			vertex_shader.as_bytes(),
+			pixel_shader.as_bytes(),
+			hud::new(),
+		)?;
+		let atlas_width = FONT.len() * CELL_WIDTH;
+		let atlas_height = GLYPH_HEIGHT + 1;
+		let mut texels = vec![0u8; atlas_width * atlas_height];
+		for (g, &(_, rows)) in FONT.iter().enumerate() {
+			for (y, row) in rows.iter().enumerate() {
+				for x in 0..GLYPH_WIDTH {
+					if row & (0x10 >> x) != 0 {
+						texels[y * atlas_width + g * CELL_WIDTH + x] = 0xff;
+					}
+				}
+			}
+		}
+		let (_, font) = factory.create_texture_immutable_u8::<formats::HudFontFormat>(
+			gfx::texture::Kind::D2(
+				atlas_width as gfx::texture::Size,
+				atlas_height as gfx::texture::Size,
+				gfx::texture::AaMode::Single,
+			),
+			gfx::texture::Mipmap::Provided,
+			&[&texels],
+		)?;
+		let sampler = factory.create_sampler(gfx::texture::SamplerInfo::new(
+			gfx::texture::FilterMethod::Scale,
+			gfx::texture::WrapMode::Clamp,
+		));
+		let vbuf = factory.create_buffer(
+			MAX_VERTICES,
+			gfx::buffer::Role::Vertex,
+			gfx::memory::Usage::Dynamic,
+			gfx::memory::Bind::empty(),
+		)?;
+		Ok(HudContext {
+			settings,
+			pso,
+			font,
+			sampler,
+			vbuf,
+			vertices: Vec::with_capacity(MAX_VERTICES),
+		})
+	}
+
+	/// The lines of text shown by the overlay
+	fn lines(
+		viewport: &Viewport,
+		frame_stats: Option<&stats::FrameStatsHistory>,
+		color_format: gfx::format::Format,
+		depth_format: gfx::format::Format,
+	) -> Vec<String> {
+		let mut lines = Vec::new();
+		match frame_stats {
+			Some(frame_stats) => {
+				lines.push(match frame_stats.fps() {
+					Some(fps) => format!("FPS {:.1}", fps),
+					None => "FPS -".to_string(),
+				});
+				if let (Some(median), Some(worst)) = (
+					frame_stats.frame_time_percentile(50.),
+					frame_stats.frame_time_percentile(99.),
+				) {
+					lines.push(format!(
+						"FRAME {:.2} MS P99 {:.2} MS",
+						ms(median),
+						ms(worst)
+					));
+				}
+				if let Some(last) = frame_stats.last() {
+					let cpu = last.cpu;
+					lines.push(format!(
+						"CPU R {:.2} P {:.2} B {:.2} MS",
+						ms(cpu.render),
+						ms(cpu.postprocess),
+						ms(cpu.blit)
+					));
+				}
+				match frame_stats.last_gpu().and_then(|s| s.gpu) {
+					Some(gpu) => lines.push(format!(
+						"GPU R {:.2} P {:.2} B {:.2} MS",
+						ms(gpu.render),
+						ms(gpu.postprocess),
+						ms(gpu.blit)
+					)),
+					None if frame_stats.gpu_timing() => lines.push("GPU -".to_string()),
+					None => lines.push("GPU N/A".to_string()),
+				}
+			}
+			None => lines.push("STATS OFF".to_string()),
+		}
+		let aa = match viewport.aa {
+			gfx::texture::AaMode::Single => "NO AA".to_string(),
+			gfx::texture::AaMode::Multi(samples) => format!("MSAA {}X", samples),
+			aa => format!("{:?}", aa).to_uppercase(),
+		};
+		lines.push(format!(
+			"{}X{} @{} {}",
+			viewport.target_width, viewport.target_height, viewport.scale_factor, aa
+		));
+		lines.push(format!("COLOR {}", format_name(color_format)));
+		lines.push(format!("DEPTH {}", format_name(depth_format)));
+		lines
+	}
+
+	/// Blends the overlay onto the postprocessing target
+	/// * `encoder` the encoder of the current Gl context
+	/// * `post_target` the postprocessing target, at the native resolution of the GlArea
+	/// * `viewport` size of the GlArea
+	/// * `frame_stats` statistics of the last frames, if enabled
+	/// * `color_format` format of the render target, as shown by the overlay
+	/// * `depth_format` format of the depth buffer, as shown by the overlay
+	pub fn draw(
+		&mut self,
+		encoder: &mut GlEncoder,
+		post_target: &GlFrameBuffer<formats::GtkTargetColorFormat>,
+		viewport: &Viewport,
+		frame_stats: Option<&stats::FrameStatsHistory>,
+		color_format: gfx::format::Format,
+		depth_format: gfx::format::Format,
+	) -> Result<()> {
+		let lines = Self::lines(viewport, frame_stats, color_format, depth_format);
+		let settings = self.settings;
+		let s = if settings.scale > 0 {
+			settings.scale
+		} else {
+			viewport.scale_factor.max(1) as u32
+		} as f32;
+		let graph_frames = frame_stats.map(|f| f.history().len()).unwrap_or(0);
+		let columns = lines.iter().map(|l| l.len()).max().unwrap_or(0);
+		let content_width = (columns * CELL_WIDTH).max(graph_frames.min(DEFAULT_STATS_CAPACITY));
+		let graph_height = if graph_frames > 0 {
+			GRAPH_HEIGHT + PADDING
+		} else {
+			0
+		};
+		let panel_width = (content_width + 2 * PADDING) as f32 * s;
+		let panel_height = (lines.len() * CELL_HEIGHT + graph_height + 2 * PADDING) as f32 * s;
+		let (width, height) = (viewport.target_width as f32, viewport.target_height as f32);
+		let margin = PADDING as f32 * s;
+		let x0 = match settings.corner {
+			HudCorner::TopLeft | HudCorner::BottomLeft => margin,
+			HudCorner::TopRight | HudCorner::BottomRight => width - margin - panel_width,
+		};
+		let y0 = match settings.corner {
+			HudCorner::BottomLeft | HudCorner::BottomRight => margin,
+			HudCorner::TopLeft | HudCorner::TopRight => height - margin - panel_height,
+		};
+
+		self.vertices.clear();
+		{
+			let mut builder = QuadBuilder {
+				vertices: &mut self.vertices,
+				width,
+				height,
+				scale: s,
+			};
+			builder.quad(
+				x0,
+				y0,
+				panel_width,
+				panel_height,
+				None,
+				settings.background_color,
+			);
+			let left = x0 + PADDING as f32 * s;
+			let top = y0 + panel_height - PADDING as f32 * s;
+			for (i, line) in lines.iter().enumerate() {
+				builder.text(
+					left,
+					top - (i * CELL_HEIGHT) as f32 * s,
+					line,
+					settings.text_color,
+				);
+			}
+			if let Some(frame_stats) = frame_stats {
+				// one bar per frame, most recent on the right
+				let bottom = y0 + PADDING as f32 * s;
+				let graph_pixels = GRAPH_HEIGHT as f32 * s;
+				let history = frame_stats.history();
+				let skip = history.len().saturating_sub(content_width);
+				for (i, frame) in history.iter().skip(skip).enumerate() {
+					if let Some(interval) = frame.interval {
+						let t = ms(interval);
+						let color = if t <= GRAPH_TARGET_MS * 1.05 {
+							[0.3, 0.9, 0.3, 0.9]
+						} else if t <= GRAPH_MAX_MS * 1.05 {
+							[0.9, 0.8, 0.2, 0.9]
+						} else {
+							[0.9, 0.3, 0.2, 0.9]
+						};
+						builder.quad(
+							left + i as f32 * s,
+							bottom,
+							s,
+							(t / GRAPH_MAX_MS).min(1.) * graph_pixels,
+							None,
+							color,
+						);
+					}
+				}
+				builder.quad(
+					left,
+					bottom + GRAPH_TARGET_MS / GRAPH_MAX_MS * graph_pixels,
+					content_width as f32 * s,
+					s,
+					None,
+					[1., 1., 1., 0.5],
+				);
+			}
+		}
+
+		encoder
+			.update_buffer(&self.vbuf, &self.vertices, 0)
+			.map_err(|e| Error::GenericError(format!("{:?}", e)))?;
+		let slice = gfx::Slice {
+			start: 0,
+			end: self.vertices.len() as u32,
+			base_vertex: 0,
+			instances: None,
+			buffer: gfx::IndexBuffer::Auto,
+		};
+		encoder.draw(
+			&slice,
+			&self.pso,
+			&hud::Data {
+				vbuf: self.vbuf.clone(),
+				font: (self.font.clone(), self.sampler.clone()),
+				dst: post_target.clone(),
+			},
+		);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use gfx::format::Formatted;
+
+	fn glyph(c: char) -> char {
+		FONT[glyph_index(c)].0
+	}
+
+	#[test]
+	fn lower_case_letters_use_the_upper_case_glyphs() {
+		assert_eq!(glyph('a'), 'A');
+		assert_eq!(glyph('z'), 'Z');
+		assert_eq!(glyph('Q'), 'Q');
+		assert_eq!(glyph('7'), '7');
+		assert_eq!(glyph(' '), ' ');
+		assert_eq!(glyph('%'), '%');
+	}
+
+	#[test]
+	fn missing_glyphs_fall_back_to_the_question_mark() {
+		assert_eq!(glyph('?'), '?');
+		assert_eq!(glyph('#'), '?');
+		assert_eq!(glyph('é'), '?');
+		assert_eq!(glyph('\n'), '?');
+	}
+
+	#[test]
+	fn format_names_are_upper_case() {
+		assert_eq!(
+			format_name(<formats::DefaultRenderColorFormat as Formatted>::get_format()),
+			"R8_G8_B8_A8 UNORM"
+		);
+		assert_eq!(
+			format_name(<formats::DefaultRenderDepthFormat as Formatted>::get_format()),
+			"D24_S8 UNORM"
+		);
+	}
+
+	#[test]
+	fn lines_describe_the_viewport_and_formats() {
+		let color_format = <formats::DefaultRenderColorFormat as Formatted>::get_format();
+		let depth_format = <formats::DefaultRenderDepthFormat as Formatted>::get_format();
+		let mut viewport = Viewport::with_aa(gfx::texture::AaMode::Multi(4), 640, 480);
+		viewport.scale_factor = 2;
+		assert_eq!(
+			HudContext::<GlDevice>::lines(&viewport, None, color_format, depth_format),
+			vec![
+				"STATS OFF",
+				"640X480 @2 MSAA 4X",
+				"COLOR R8_G8_B8_A8 UNORM",
+				"DEPTH D24_S8 UNORM",
+			]
+		);
+
+		let viewport = Viewport::with_aa(gfx::texture::AaMode::Single, 320, 200);
+		let mut frame_stats = stats::FrameStatsHistory::new(10, false);
+		frame_stats.begin_frame();
+		frame_stats.end_frame();
+		let lines = HudContext::<GlDevice>::lines(
+			&viewport,
+			Some(&frame_stats),
+			color_format,
+			depth_format,
+		);
+		// a single frame has no frame rate, nor frame time
+		assert_eq!(lines[0], "FPS -");
+		assert!(lines[1].starts_with("CPU R "));
+		assert_eq!(lines[2], "GPU N/A");
+		assert_eq!(lines[3], "320X200 @1 NO AA");
+	}
+}
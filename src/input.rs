@@ -0,0 +1,559 @@
+//! Input events received by a GlArea, converted into [Viewport] space and forwarded to a
+//! [GlInputCallback]
+
+use super::*;
+
+/// The events a GlArea must receive to forward them to a [GlInputCallback]
+pub fn event_mask() -> gdk::EventMask {
+	gdk::EventMask::POINTER_MOTION_MASK
+		| gdk::EventMask::BUTTON_PRESS_MASK
+		| gdk::EventMask::BUTTON_RELEASE_MASK
+		| gdk::EventMask::SCROLL_MASK
+		| gdk::EventMask::SMOOTH_SCROLL_MASK
+		| gdk::EventMask::KEY_PRESS_MASK
+		| gdk::EventMask::KEY_RELEASE_MASK
+		| gdk::EventMask::FOCUS_CHANGE_MASK
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Position of the pointer over the GlArea
+pub struct PointerPosition {
+	/// Widget coordinates, origin top left, as reported by Gdk
+	pub widget: (f64, f64),
+	/// Framebuffer pixel coordinates, origin bottom left, as in Gl. Takes into account the
+	/// scale factor, not clipped to the framebuffer
+	pub pixel: (f64, f64),
+	/// Normalized device coordinates, each axis in the range -1..1 within the framebuffer
+	pub ndc: (f32, f32),
+}
+
+impl PointerPosition {
+	/// Converts a position in widget coordinates
+	/// * `position` horizontal and vertical widget coordinates
+	/// * `viewport` the viewport of the GlArea receiving the event
+	pub fn new(position: (f64, f64), viewport: &Viewport) -> Self {
+		let (x, y) = position;
+		let scale = viewport.scale_factor as f64;
+		let ndc = viewport.widget_to_ndc(x, y, 0.);
+		PointerPosition {
+			widget: position,
+			pixel: (x * scale, viewport.target_height as f64 - y * scale),
+			ndc: (ndc[0], ndc[1]),
+		}
+	}
+
+	/// The framebuffer pixel under the pointer, as accepted by the read back functions of
+	/// [RenderContext], `None` if the pointer is outside the framebuffer
+	/// * `viewport` the viewport of the GlArea receiving the event
+	pub fn pixel_index(&self, viewport: &Viewport) -> Option<(i32, i32)> {
+		viewport.widget_to_pixel(self.widget.0, self.widget.1)
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// The pointer has moved over the GlArea
+pub struct PointerMotion {
+	/// Position of the pointer
+	pub position: PointerPosition,
+	/// Modifier keys and mouse buttons held during the motion
+	pub modifiers: gdk::ModifierType,
+}
+
+impl PointerMotion {
+	/// Converts a Gdk motion event
+	/// * `event` the event, as received by the GlArea
+	/// * `viewport` the viewport of the GlArea
+	pub fn new(event: &gdk::EventMotion, viewport: &Viewport) -> Self {
+		PointerMotion {
+			position: PointerPosition::new(event.get_position(), viewport),
+			modifiers: event.get_state(),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Kind of a button event
+pub enum ButtonAction {
+	/// The button has been pressed
+	Press,
+	/// The button has been pressed twice in a short time, follows the second `Press`
+	DoublePress,
+	/// The button has been pressed three times in a short time, follows the third `Press`
+	TriplePress,
+	/// The button has been released
+	Release,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// A mouse button has been pressed or released over the GlArea
+pub struct PointerButton {
+	/// Position of the pointer
+	pub position: PointerPosition,
+	/// Button number, 1 is the left (primary) button, 2 the middle one, 3 the right one
+	pub button: u32,
+	/// Whether the button has been pressed or released
+	pub action: ButtonAction,
+	/// Modifier keys and mouse buttons held when the event was generated
+	pub modifiers: gdk::ModifierType,
+}
+
+impl PointerButton {
+	/// Converts a Gdk button event
+	/// * `event` the event, as received by the GlArea
+	/// * `viewport` the viewport of the GlArea
+	pub fn new(event: &gdk::EventButton, viewport: &Viewport) -> Self {
+		let action = match event.get_event_type() {
+			gdk::EventType::DoubleButtonPress => ButtonAction::DoublePress,
+			gdk::EventType::TripleButtonPress => ButtonAction::TriplePress,
+			gdk::EventType::ButtonRelease => ButtonAction::Release,
+			_ => ButtonAction::Press,
+		};
+		PointerButton {
+			position: PointerPosition::new(event.get_position(), viewport),
+			button: event.get_button(),
+			action,
+			modifiers: event.get_state(),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// The mouse wheel or touchpad has been scrolled over the GlArea
+pub struct Scroll {
+	/// Position of the pointer
+	pub position: PointerPosition,
+	/// Horizontal and vertical scroll amount, positive right and down. Discrete wheel
+	/// clicks are reported as steps of 1
+	pub delta: (f64, f64),
+	/// `true` if the delta comes from a device with smooth scrolling (e.g. a touchpad),
+	/// `false` for discrete wheel clicks
+	pub smooth: bool,
+	/// Modifier keys and mouse buttons held when the event was generated
+	pub modifiers: gdk::ModifierType,
+}
+
+impl Scroll {
+	/// Converts a Gdk scroll event
+	/// * `event` the event, as received by the GlArea
+	/// * `viewport` the viewport of the GlArea
+	pub fn new(event: &gdk::EventScroll, viewport: &Viewport) -> Self {
+		let (delta, smooth) = match event.get_direction() {
+			gdk::ScrollDirection::Up => ((0., -1.), false),
+			gdk::ScrollDirection::Down => ((0., 1.), false),
+			gdk::ScrollDirection::Left => ((-1., 0.), false),
+			gdk::ScrollDirection::Right => ((1., 0.), false),
+			_ => (event.get_delta(), true),
+		};
+		Scroll {
+			position: PointerPosition::new(event.get_position(), viewport),
+			delta,
+			smooth,
+			modifiers: event.get_state(),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// A key has been pressed or released while the GlArea has the keyboard focus
+pub struct Key {
+	/// Key symbol, see `gdk::enums::key`
+	pub keyval: u32,
+	/// Platform dependent key code, independent from the keyboard layout
+	pub hardware_keycode: u16,
+	/// `true` if the key has been pressed (or auto-repeated), `false` if released
+	pub pressed: bool,
+	/// Modifier keys and mouse buttons held when the event was generated
+	pub modifiers: gdk::ModifierType,
+}
+
+impl Key {
+	/// Converts a Gdk key event
+	/// * `event` the event, as received by the GlArea
+	pub fn new(event: &gdk::EventKey) -> Self {
+		Key {
+			keyval: event.get_keyval(),
+			hardware_keycode: event.get_hardware_keycode(),
+			pressed: event.get_event_type() != gdk::EventType::KeyRelease,
+			modifiers: event.get_state(),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Stage of a continuous gesture
+pub enum GesturePhase {
+	/// The gesture has been recognised, no change yet
+	Begin,
+	/// The gesture is in progress
+	Update,
+	/// The fingers have been lifted, no change since the last `Update`
+	End,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// A touch gesture recognised over the GlArea
+pub enum Gesture {
+	/// Two fingers moving apart or together
+	Pinch {
+		/// Stage of the gesture
+		phase: GesturePhase,
+		/// Midpoint of the fingers
+		center: PointerPosition,
+		/// Distance between the fingers, relative to the beginning of the gesture
+		scale: f64,
+		/// Change of `scale` since the last event, as a factor
+		scale_delta: f64,
+	},
+	/// Two fingers rotating around their midpoint
+	Rotate {
+		/// Stage of the gesture
+		phase: GesturePhase,
+		/// Midpoint of the fingers
+		center: PointerPosition,
+		/// Rotation since the beginning of the gesture, counter-clockwise on screen, in radians
+		angle: f64,
+		/// Change of `angle` since the last event, in radians
+		angle_delta: f64,
+	},
+	/// A single finger dragged over the GlArea
+	Drag {
+		/// Stage of the gesture
+		phase: GesturePhase,
+		/// Where the finger touched the GlArea
+		start: PointerPosition,
+		/// Current position of the finger
+		position: PointerPosition,
+		/// Motion since the last event, in framebuffer pixels, y up
+		delta: (f64, f64),
+	},
+	/// A quick single finger flick, reported when the finger is lifted
+	Swipe {
+		/// Position where the finger was lifted
+		position: PointerPosition,
+		/// Velocity of the finger in framebuffer pixels per second, y up
+		velocity: (f64, f64),
+	},
+}
+
+#[derive(Clone, Debug, Default)]
+/// Turns the raw values reported by the Gtk gesture recognisers into [Gesture] events,
+/// keeping track of the changes between events. Has no Gtk dependencies, so it can be
+/// fed with synthetic values
+pub struct GestureTracker {
+	/// scale and center of the last pinch event, if in progress
+	pinch: Option<(f64, (f64, f64))>,
+	/// angle and center of the last rotate event, if in progress
+	rotate: Option<(f64, (f64, f64))>,
+	/// start and offset of the last drag event, if in progress
+	drag: Option<((f64, f64), (f64, f64))>,
+}
+
+impl GestureTracker {
+	/// Creates a tracker with no gesture in progress
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Reports the scale of a pinch, begins it if not in progress
+	/// * `center` midpoint of the fingers, in widget coordinates
+	/// * `scale` distance between the fingers relative to the beginning of the gesture
+	/// * `viewport` the viewport of the GlArea
+	pub fn pinch(&mut self, center: (f64, f64), scale: f64, viewport: &Viewport) -> Gesture {
+		let (phase, previous) = match self.pinch {
+			Some((previous, _)) => (GesturePhase::Update, previous),
+			None => (GesturePhase::Begin, scale),
+		};
+		self.pinch = Some((scale, center));
+		Gesture::Pinch {
+			phase,
+			center: PointerPosition::new(center, viewport),
+			scale,
+			scale_delta: if previous != 0. { scale / previous } else { 1. },
+		}
+	}
+
+	/// Ends the pinch in progress, if any
+	/// * `viewport` the viewport of the GlArea
+	pub fn pinch_end(&mut self, viewport: &Viewport) -> Option<Gesture> {
+		self.pinch.take().map(|(scale, center)| Gesture::Pinch {
+			phase: GesturePhase::End,
+			center: PointerPosition::new(center, viewport),
+			scale,
+			scale_delta: 1.,
+		})
+	}
+
+	/// Reports the angle of a two finger rotation, begins it if not in progress
+	/// * `center` midpoint of the fingers, in widget coordinates
+	/// * `angle` rotation since the beginning of the gesture, clockwise on screen as reported by Gtk
+	/// * `viewport` the viewport of the GlArea
+	pub fn rotate(&mut self, center: (f64, f64), angle: f64, viewport: &Viewport) -> Gesture {
+		let (phase, previous) = match self.rotate {
+			Some((previous, _)) => (GesturePhase::Update, previous),
+			None => (GesturePhase::Begin, angle),
+		};
+		self.rotate = Some((angle, center));
+		Gesture::Rotate {
+			phase,
+			center: PointerPosition::new(center, viewport),
+			angle: -angle,
+			angle_delta: previous - angle,
+		}
+	}
+
+	/// Ends the rotation in progress, if any
+	/// * `viewport` the viewport of the GlArea
+	pub fn rotate_end(&mut self, viewport: &Viewport) -> Option<Gesture> {
+		self.rotate.take().map(|(angle, center)| Gesture::Rotate {
+			phase: GesturePhase::End,
+			center: PointerPosition::new(center, viewport),
+			angle: -angle,
+			angle_delta: 0.,
+		})
+	}
+
+	/// Begins a single finger drag
+	/// * `start` where the finger touched, in widget coordinates
+	/// * `viewport` the viewport of the GlArea
+	pub fn drag_begin(&mut self, start: (f64, f64), viewport: &Viewport) -> Gesture {
+		self.drag = Some((start, (0., 0.)));
+		let start = PointerPosition::new(start, viewport);
+		Gesture::Drag {
+			phase: GesturePhase::Begin,
+			start,
+			position: start,
+			delta: (0., 0.),
+		}
+	}
+
+	/// Reports the position of a drag. Ignored if no drag is in progress
+	/// * `offset` offset of the finger from the start, in widget coordinates
+	/// * `viewport` the viewport of the GlArea
+	pub fn drag_update(&mut self, offset: (f64, f64), viewport: &Viewport) -> Option<Gesture> {
+		self.drag_to(offset, GesturePhase::Update, viewport)
+	}
+
+	/// Ends the drag in progress, if any
+	/// * `offset` final offset of the finger from the start, in widget coordinates
+	/// * `viewport` the viewport of the GlArea
+	pub fn drag_end(&mut self, offset: (f64, f64), viewport: &Viewport) -> Option<Gesture> {
+		let gesture = self.drag_to(offset, GesturePhase::End, viewport);
+		self.drag = None;
+		gesture
+	}
+
+	fn drag_to(
+		&mut self,
+		offset: (f64, f64),
+		phase: GesturePhase,
+		viewport: &Viewport,
+	) -> Option<Gesture> {
+		let (start, previous) = self.drag?;
+		self.drag = Some((start, offset));
+		let scale = viewport.scale_factor as f64;
+		Some(Gesture::Drag {
+			phase,
+			start: PointerPosition::new(start, viewport),
+			position: PointerPosition::new((start.0 + offset.0, start.1 + offset.1), viewport),
+			delta: (
+				(offset.0 - previous.0) * scale,
+				(previous.1 - offset.1) * scale,
+			),
+		})
+	}
+
+	/// Reports a swipe
+	/// * `position` where the finger was lifted, in widget coordinates
+	/// * `velocity` velocity in widget coordinates per second, as reported by Gtk
+	/// * `viewport` the viewport of the GlArea
+	pub fn swipe(
+		&mut self,
+		position: (f64, f64),
+		velocity: (f64, f64),
+		viewport: &Viewport,
+	) -> Gesture {
+		let scale = viewport.scale_factor as f64;
+		Gesture::Swipe {
+			position: PointerPosition::new(position, viewport),
+			velocity: (velocity.0 * scale, -velocity.1 * scale),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A 200x100 pixel framebuffer on a HiDPI screen, 100x50 in widget coordinates
+	fn viewport() -> Viewport {
+		let mut viewport = Viewport::with_aa(gfx::texture::AaMode::Single, 200, 100);
+		viewport.scale_factor = 2;
+		viewport
+	}
+
+	#[test]
+	fn positions_are_converted_to_pixels_and_ndc() {
+		let position = PointerPosition::new((25., 10.), &viewport());
+		assert_eq!(position.widget, (25., 10.));
+		assert_eq!(position.pixel, (50., 80.));
+		assert_eq!(position.ndc, (-0.5, 0.6));
+		assert_eq!(position.pixel_index(&viewport()), Some((50, 79)));
+		assert_eq!(
+			PointerPosition::new((100., 10.), &viewport()).pixel_index(&viewport()),
+			None
+		);
+	}
+
+	#[test]
+	fn pinch_reports_the_scale_change_between_events() {
+		let viewport = viewport();
+		let mut tracker = GestureTracker::new();
+		assert_eq!(tracker.pinch_end(&viewport), None);
+		let phases: Vec<_> = [(1., 1.), (1.5, 1.5), (0.75, 0.5)]
+			.iter()
+			.map(
+				|&(scale, expected_delta)| match tracker.pinch((50., 25.), scale, &viewport) {
+					Gesture::Pinch {
+						phase,
+						center,
+						scale: reported,
+						scale_delta,
+					} => {
+						assert_eq!(reported, scale);
+						assert_eq!(scale_delta, expected_delta);
+						assert_eq!(center.ndc, (0., 0.));
+						phase
+					}
+					gesture => panic!("unexpected {:?}", gesture),
+				},
+			)
+			.collect();
+		assert_eq!(
+			phases,
+			[
+				GesturePhase::Begin,
+				GesturePhase::Update,
+				GesturePhase::Update
+			]
+		);
+		match tracker.pinch_end(&viewport) {
+			Some(Gesture::Pinch {
+				phase: GesturePhase::End,
+				scale,
+				scale_delta,
+				..
+			}) => {
+				assert_eq!(scale, 0.75);
+				assert_eq!(scale_delta, 1.);
+			}
+			gesture => panic!("unexpected {:?}", gesture),
+		}
+		assert_eq!(tracker.pinch_end(&viewport), None);
+		// a new pinch begins from scratch
+		match tracker.pinch((0., 0.), 2., &viewport) {
+			Gesture::Pinch {
+				phase: GesturePhase::Begin,
+				scale_delta,
+				..
+			} => assert_eq!(scale_delta, 1.),
+			gesture => panic!("unexpected {:?}", gesture),
+		}
+	}
+
+	#[test]
+	fn rotate_is_counter_clockwise() {
+		let viewport = viewport();
+		let mut tracker = GestureTracker::new();
+		let expected = [
+			(0.25, GesturePhase::Begin, -0.25, 0.),
+			(0.5, GesturePhase::Update, -0.5, -0.25),
+			(0.25, GesturePhase::Update, -0.25, 0.25),
+		];
+		for &(angle, phase, expected_angle, expected_delta) in &expected {
+			assert_eq!(
+				tracker.rotate((25., 25.), angle, &viewport),
+				Gesture::Rotate {
+					phase,
+					center: PointerPosition::new((25., 25.), &viewport),
+					angle: expected_angle,
+					angle_delta: expected_delta,
+				}
+			);
+		}
+		assert_eq!(
+			tracker.rotate_end(&viewport),
+			Some(Gesture::Rotate {
+				phase: GesturePhase::End,
+				center: PointerPosition::new((25., 25.), &viewport),
+				angle: -0.25,
+				angle_delta: 0.,
+			})
+		);
+		assert_eq!(tracker.rotate_end(&viewport), None);
+	}
+
+	#[test]
+	fn drag_reports_pixel_deltas_y_up() {
+		let viewport = viewport();
+		let mut tracker = GestureTracker::new();
+		assert_eq!(tracker.drag_update((1., 1.), &viewport), None);
+		let start = PointerPosition::new((10., 20.), &viewport);
+		assert_eq!(
+			tracker.drag_begin((10., 20.), &viewport),
+			Gesture::Drag {
+				phase: GesturePhase::Begin,
+				start,
+				position: start,
+				delta: (0., 0.),
+			}
+		);
+		assert_eq!(
+			tracker.drag_update((5., 5.), &viewport),
+			Some(Gesture::Drag {
+				phase: GesturePhase::Update,
+				start,
+				position: PointerPosition::new((15., 25.), &viewport),
+				delta: (10., -10.),
+			})
+		);
+		assert_eq!(
+			tracker.drag_end((2., 10.), &viewport),
+			Some(Gesture::Drag {
+				phase: GesturePhase::End,
+				start,
+				position: PointerPosition::new((12., 30.), &viewport),
+				delta: (-6., -10.),
+			})
+		);
+		assert_eq!(tracker.drag_update((3., 3.), &viewport), None);
+		assert_eq!(tracker.drag_end((3., 3.), &viewport), None);
+	}
+
+	#[test]
+	fn swipe_reports_pixel_velocity_y_up() {
+		let viewport = viewport();
+		let mut tracker = GestureTracker::new();
+		assert_eq!(
+			tracker.swipe((40., 30.), (100., 50.), &viewport),
+			Gesture::Swipe {
+				position: PointerPosition::new((40., 30.), &viewport),
+				velocity: (200., -100.),
+			}
+		);
+	}
+
+	#[test]
+	fn gestures_are_tracked_independently() {
+		let viewport = viewport();
+		let mut tracker = GestureTracker::new();
+		tracker.pinch((50., 25.), 1., &viewport);
+		tracker.rotate((50., 25.), 0., &viewport);
+		tracker.drag_begin((50., 25.), &viewport);
+		assert!(tracker.rotate_end(&viewport).is_some());
+		match tracker.pinch((50., 25.), 2., &viewport) {
+			Gesture::Pinch { phase, .. } => assert_eq!(phase, GesturePhase::Update),
+			gesture => panic!("unexpected {:?}", gesture),
+		}
+		assert!(tracker.drag_update((1., 0.), &viewport).is_some());
+	}
+}
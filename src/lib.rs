@@ -136,28 +136,118 @@
 //! ```
 //! After this, every time Gtk refreshes the `GlArea` content, it will invoke the `render_callback` to paint itself.
 //!
+//! ### Or let [GfxGlArea] do it
+//!
+//! [GfxGlArea] wraps a `gtk::GLArea`, owns the [GlRenderContext] and the render callback and connects the
+//! signals above (plus `unrealize`). The callback is created from a closure every time the widget is realized
+//!
+//! ```ignore
+//!	let glarea = gfx_gtk::GfxGlArea::<RenderColorFormat, RenderDepthFormat>::new(
+//!		MSAA,
+//!		SimpleRenderCallback::new,
+//!	);
+//!	v_box.pack_start(glarea.widget(), true, true, 0);
+//!	...
+//!	glarea.with_render_callback(|render_callback: &mut SimpleRenderCallback| { ... });
+//!	glarea.queue_render();
+//! ```
+//!
+//! Implementing [GlInputCallback] as well and calling `glarea.enable_input::<SimpleRenderCallback>()`
+//! forwards the pointer, scroll and key events of the widget to the callback, already converted into
+//! [Viewport] coordinates
+//!
+//! [GfxGlArea] renders on demand only: redraw requests via `glarea.queue_render()` are coalesced into one
+//! frame, and [GlRenderCallback::settle_frames()] keeps rendering for a few more frames while the contents settle
+//!
+//! Worker threads can request a redraw via the `Send` [RedrawHandle] returned by `glarea.redraw_handle()`, and
+//! publish snapshots of their state through a [triple_buffer()], which the render callback picks up
+//! at the start of `render` without locking
+//!
+//! Large meshes and textures can be uploaded off the main thread by the [ResourceLoader] returned by
+//! `glarea.resource_loader()`, which runs its own gfx factory on a Gl context shared with the GlArea.
+//! It is only available where Gdk Gl contexts can be current on another thread, e.g. not on X11
+//!
+//! Several GlAreas in the same window, e.g. a quad view, can draw the same meshes and textures by joining
+//! a [ShareGroup] via `glarea.set_share_group()`, and creating them once with its factory
+//!
+//! [GfxGlArea] requests a Gl 3.2 core context by default. `glarea.set_context_config()` changes the required
+//! version, profile and flags, see [GlContextConfig]. When they can't be satisfied, the reason is shown in place of
+//! the contents of the widget
+//!
+//! For continuous animation, `glarea.start_animation()` renders a frame at every tick of the Gdk frame clock,
+//! in sync with the display, invoking [GlRenderCallback::animate()] with the [FrameInfo] of each frame.
+//! Once stopped, [GlRenderCallback::resume_animation()] can start it again after a frame rendered on demand
+//!
 
 extern crate epoxy;
 extern crate gdk;
+extern crate gdk_sys;
 #[macro_use]
 extern crate gfx;
 extern crate gfx_device_gl;
 extern crate gl;
+extern crate glib;
 extern crate gtk;
 extern crate libc;
 extern crate shared_library;
 
+pub mod animation;
+pub mod attachments;
+pub mod blit;
+pub mod camera;
+pub mod color_picker;
+pub mod depth;
+pub mod dither;
 mod dl;
+pub mod glarea;
+pub mod glcontext;
+pub mod hud;
+pub mod input;
+mod lifecycle;
+pub mod loader;
+pub mod lut;
+pub mod outline;
+pub mod picking;
+pub mod redraw;
+pub mod scheduler;
 pub mod shaders;
+pub mod share;
+pub mod stats;
+pub mod triple_buffer;
 
 use gfx::Factory;
 use std::ops::Fn;
 use std::path::Path;
 
+pub use animation::{AnimationStatus, FrameInfo};
+pub use attachments::{ColorAttachment, ColorAttachments};
+pub use blit::BlitPath;
+pub use camera::{Camera, FlyCamera, OrbitCamera, PanZoomCamera};
+pub use color_picker::PickedColor;
+pub use dither::DitherMode;
+pub use glarea::{DefaultGfxGlArea, GfxGlArea};
+pub use glcontext::{GlContextConfig, GlContextError, GlProfile};
+pub use hud::{HudCorner, HudSettings};
+pub use input::{
+	ButtonAction, Gesture, GesturePhase, Key, PointerButton, PointerMotion, PointerPosition, Scroll,
+};
+pub use loader::{ResourceLoader, Upload};
+pub use lut::CubeLut;
+pub use outline::OutlineSettings;
+pub use picking::{PickId, PickQuery};
+pub use redraw::RedrawHandle;
+pub use scheduler::RenderScheduler;
+pub use shaders::ShaderDialect;
+pub use share::ShareGroup;
+pub use stats::{FrameStats, FrameStatsHistory, StageTimes};
+pub use triple_buffer::{triple_buffer, TripleBufferReader, TripleBufferWriter};
+
 /// Convenience type to express a typical RGBA quantity as [r,g,b,a] f32
 pub type Rgba = [f32; 4];
 /// Convenience type to express a general purpose vec4 [x,y,z,w] f32
 pub type Float4 = [f32; 4];
+/// Convenience type to express a column-major 4x4 matrix as [[f32; 4]; 4]
+pub type Matrix4 = [[f32; 4]; 4];
 /// Convenience type to express a floating point depth value as f32
 pub type Depth = f32;
 
@@ -174,6 +264,31 @@ pub mod formats {
 	pub type DefaultRenderColorFormat = gfx::format::Rgba8;
 	/// Default depth+stencil format, 24/8
 	pub type DefaultRenderDepthFormat = gfx::format::DepthStencil;
+	/// Selection outline mask format, RGBA8888. Any non-zero channel marks a selected pixel
+	pub type OutlineMaskFormat = gfx::format::Rgba8;
+	/// Selection outline mask format [f32;4]
+	pub type OutlineMaskView = <OutlineMaskFormat as gfx::format::Formatted>::View;
+	/// Color grading 3D LUT format, RGBA 16 bit float. Unlike 32 bit float formats,
+	/// it can be sampled bilinearly on Gl ES 3.0 without extensions
+	pub type LutFormat = gfx::format::Rgba16F;
+	/// Color grading 3D LUT format [f32;4]
+	pub type LutView = <LutFormat as gfx::format::Formatted>::View;
+	/// Dithering threshold map format, 32 bit float
+	pub type DitherFormat = (gfx::format::R32, gfx::format::Float);
+	/// Dithering threshold map format f32
+	pub type DitherView = <DitherFormat as gfx::format::Formatted>::View;
+	/// Object ID attachment format for picking, 32 bit unsigned integer
+	pub type PickIdFormat = (gfx::format::R32, gfx::format::Uint);
+	/// Object ID attachment format u32
+	pub type PickIdView = <PickIdFormat as gfx::format::Formatted>::View;
+	/// Format of the target depth values are copied into when read back, 32 bit float
+	pub type DepthReadFormat = (gfx::format::R32, gfx::format::Float);
+	/// Format of the target colors are averaged into when read back, RGBA 32 bit float
+	pub type ColorReadFormat = gfx::format::Rgba32F;
+	/// Glyph atlas format of the performance overlay, 8 bit coverage
+	pub type HudFontFormat = (gfx::format::R8, gfx::format::Unorm);
+	/// Glyph atlas format of the performance overlay f32
+	pub type HudFontView = <HudFontFormat as gfx::format::Formatted>::View;
 
 	/// Convenience type for return values of functions that create offscreen
 	/// render targets
@@ -211,10 +326,17 @@ gfx_vertex_struct!(BlitVertex {
 	tex_coord: [f32; 2] = "a_TexCoord",
 });
 
-// Post-processing gfx pipeline definitions
+// Post-processing gfx pipeline definitions. Since 0.5 it includes the color grading and
+// dithering inputs of the built-in shaders: custom pixel shaders may leave them undeclared,
+// but `postprocess::Data` built by library clients must provide them, see CHANGELOG.md
 gfx_pipeline!(postprocess {
 		vbuf: gfx::VertexBuffer<BlitVertex> = (),
 		src: gfx::TextureSampler<formats::GtkTargetColorView> = "t_Source",
+		lut: gfx::TextureSampler<formats::LutView> = "t_Lut",
+		lut_domain_min: gfx::Global<[f32; 3]> = "u_LutDomainMin",
+		lut_domain_max: gfx::Global<[f32; 3]> = "u_LutDomainMax",
+		lut_strength: gfx::Global<f32> = "u_LutStrength",
+		dither: gfx::TextureSampler<formats::DitherView> = "t_Dither",
 		dst: gfx::RenderTarget<formats::GtkTargetColorFormat> = "o_Color",
 	}
 );
@@ -258,6 +380,70 @@ where
 	}
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Texture filtering applied when sampling the source framebuffer in the post-processing stage.
+/// Has no effect on multisampled sources, which are resolved texel by texel
+pub enum PostprocessFilter {
+	/// Single sample from the nearest texel
+	Nearest,
+	/// Linear interpolation of the 4 nearest texels
+	Bilinear,
+	/// Bicubic B-spline interpolation, performed by the built-in shader on top of a bilinear sampler
+	Bicubic,
+	/// Anisotropic filtering with the given maximum anisotropy
+	Anisotropic(u8),
+}
+
+impl PostprocessFilter {
+	fn filter_method(self) -> gfx::texture::FilterMethod {
+		match self {
+			PostprocessFilter::Nearest => gfx::texture::FilterMethod::Scale,
+			PostprocessFilter::Bilinear | PostprocessFilter::Bicubic => {
+				gfx::texture::FilterMethod::Bilinear
+			}
+			PostprocessFilter::Anisotropic(max) => gfx::texture::FilterMethod::Anisotropic(max),
+		}
+	}
+}
+
+/// Smallest exponent accepted by [OutputTransfer::Gamma]
+pub const MIN_OUTPUT_GAMMA: f32 = 0.1;
+/// Largest exponent accepted by [OutputTransfer::Gamma]
+pub const MAX_OUTPUT_GAMMA: f32 = 10.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Transfer function applied to the output color by the built-in post-processing shaders
+pub enum OutputTransfer {
+	/// Linear to sRGB encoding, suitable for linear render targets and a linear GtkGlArea framebuffer
+	Srgb,
+	/// No conversion, for render targets already holding display-encoded colors
+	/// or a GtkGlArea framebuffer which performs the sRGB encoding itself
+	Passthrough,
+	/// Pure power-law encoding with the given gamma exponent,
+	/// between [MIN_OUTPUT_GAMMA] and [MAX_OUTPUT_GAMMA]
+	Gamma(f32),
+	/// `Passthrough` if the GtkGlArea framebuffer has sRGB color encoding, `Srgb` otherwise.
+	/// The encoding is checked on every frame
+	Auto,
+}
+
+impl Default for OutputTransfer {
+	fn default() -> Self {
+		OutputTransfer::Srgb
+	}
+}
+
+#[derive(Clone, Debug)]
+/// Where the post-processing PSO comes from, so it can be rebuilt when the filter changes
+enum PostprocessProgram {
+	/// One of the shaders in [shaders], picked according to AA mode, filter and output transfer
+	BuiltIn,
+	/// A custom pixel shader paired with [shaders::ShaderDialect::post_vertex_shader()]
+	PixelShader(Vec<u8>),
+	/// A PSO supplied as-is by the library client
+	PipelineState,
+}
+
 /// a container for the pre-built data and state needed to perform
 /// MSAA resolution and sRGB correction in the post-processing stage
 pub struct PostprocessContext<D>
@@ -272,12 +458,368 @@ where
 	pub vbuf: gfx::handle::Buffer<D::Resources, BlitVertex>,
 	/// a single large triangle (indices)
 	pub ibuf: gfx::Slice<D::Resources>,
+	/// Antialiasing mode of the source framebuffer
+	aa: gfx::texture::AaMode,
+	/// Filter used by `sampler`
+	filter: PostprocessFilter,
+	/// Wrap mode used by `sampler`
+	wrap_mode: gfx::texture::WrapMode,
+	/// Transfer function of the built-in shader, never `Auto`
+	transfer: OutputTransfer,
+	/// Color grading LUT, an identity placeholder if color grading is disabled
+	lut: lut::LutTexture<D::Resources>,
+	/// Trilinear sampler for `lut`
+	lut_sampler: gfx::handle::Sampler<D::Resources>,
+	/// Blend factor between the ungraded (0.0) and fully graded (1.0) color
+	lut_strength: f32,
+	/// Whether the built-in shader applies `lut`
+	color_grading: bool,
+	/// Dithering pattern applied by the built-in shader
+	dither_mode: DitherMode,
+	/// Threshold map for `dither_mode`
+	dither_map: gfx::handle::ShaderResourceView<D::Resources, formats::DitherView>,
+	/// Nearest, tiling sampler for `dither_map`. Float textures can't be filtered on Gl ES,
+	/// so it must not follow the filter of `sampler`
+	dither_sampler: gfx::handle::Sampler<D::Resources>,
+	/// Origin of `pso`
+	program: PostprocessProgram,
+	/// Shading language of the built-in shaders
+	dialect: shaders::ShaderDialect,
 }
 
 impl PostprocessContext<GlDevice> {
+	/// Creates the full screen geometry, sampler and PSO used by the post-processing stage
+	/// * `factory` the factory attached to the current Gl context
+	/// * `aa` antialiasing mode of the source framebuffer
+	/// * `postprocess_shader` optional buffer containing the source code of the postprocessing pixel shader.
+	/// A simple default shader will be used if `None`
+	/// * `dialect` shading language of the built-in shaders, see [shaders::ShaderDialect::detect()]
+	pub fn new(
+		factory: &mut GlFactory,
+		aa: gfx::texture::AaMode,
+		postprocess_shader: Option<&[u8]>,
+		dialect: shaders::ShaderDialect,
+	) -> Result<Self> {
+		use gfx::traits::FactoryExt;
+
+		let full_screen_triangle = vec![
+			BlitVertex {
+				pos: [-1., -1.],
+				tex_coord: [0., 0.],
+			},
+			BlitVertex {
+				pos: [-1., 3.],
+				tex_coord: [0., 2.],
+			},
+			BlitVertex {
+				pos: [3., -1.],
+				tex_coord: [2., 0.],
+			},
+		];
+
+		let full_screen_triangle_index = vec![0u16, 2, 1];
+
+		let (vbuf, ibuf) = factory.create_vertex_buffer_with_slice(
+			&full_screen_triangle,
+			&full_screen_triangle_index[..],
+		);
+
+		let filter = PostprocessFilter::Nearest;
+		let wrap_mode = gfx::texture::WrapMode::Clamp;
+		let sampler = factory.create_sampler(gfx::texture::SamplerInfo::new(
+			filter.filter_method(),
+			wrap_mode,
+		));
+
+		let program = match postprocess_shader {
+			Some(pixel_shader) => PostprocessProgram::PixelShader(pixel_shader.to_vec()),
+			None => PostprocessProgram::BuiltIn,
+		};
+		let lut = lut::CubeLut::identity(2).create_texture(factory)?;
+		let lut_sampler = factory.create_sampler(gfx::texture::SamplerInfo::new(
+			gfx::texture::FilterMethod::Bilinear,
+			gfx::texture::WrapMode::Clamp,
+		));
+
+		let dither_mode = DitherMode::None;
+		let dither_map = dither::create_texture(factory, dither_mode)?;
+		let dither_sampler = factory.create_sampler(gfx::texture::SamplerInfo::new(
+			gfx::texture::FilterMethod::Scale,
+			gfx::texture::WrapMode::Tile,
+		));
+
+		let transfer = OutputTransfer::Srgb;
+		let pso = Self::create_program_pipeline_state(
+			factory,
+			&program,
+			shaders::PostPixelShaderVariant {
+				aa,
+				filter,
+				transfer,
+				color_grading: false,
+				dithering: false,
+				dialect,
+			},
+		)?;
+
+		Ok(PostprocessContext {
+			vbuf,
+			ibuf,
+			pso,
+			sampler,
+			aa,
+			filter,
+			wrap_mode,
+			transfer,
+			lut,
+			lut_sampler,
+			lut_strength: 1.,
+			color_grading: false,
+			dither_mode,
+			dither_map,
+			dither_sampler,
+			program,
+			dialect,
+		})
+	}
+
+	/// Describes the built-in shader matching the current settings
+	fn variant(&self) -> shaders::PostPixelShaderVariant {
+		shaders::PostPixelShaderVariant {
+			aa: self.aa,
+			filter: self.filter,
+			transfer: self.transfer,
+			color_grading: self.color_grading,
+			dithering: self.dither_mode != DitherMode::None,
+			dialect: self.dialect,
+		}
+	}
+
+	/// Returns the shading language of the built-in shaders
+	pub fn shader_dialect(&self) -> shaders::ShaderDialect {
+		self.dialect
+	}
+
+	/// Returns the filter currently used to sample the source framebuffer
+	pub fn filter(&self) -> PostprocessFilter {
+		self.filter
+	}
+
+	/// Returns the wrap mode currently used to sample the source framebuffer
+	pub fn wrap_mode(&self) -> gfx::texture::WrapMode {
+		self.wrap_mode
+	}
+
+	/// Returns the transfer function applied by the built-in shader
+	pub fn output_transfer(&self) -> OutputTransfer {
+		self.transfer
+	}
+
+	/// Selects the built-in shader variant applying the given transfer function.
+	/// The PSO is only rebuilt if the transfer function has changed and no custom shader or PSO is in use
+	/// * `factory` the factory attached to the current Gl context
+	/// * `transfer` the new transfer function. `Auto` is resolved to `Srgb`, use
+	/// [RenderContext::set_output_transfer()] for framebuffer detection. A `Gamma` exponent
+	/// must be within [MIN_OUTPUT_GAMMA] and [MAX_OUTPUT_GAMMA]
+	pub fn set_output_transfer(
+		&mut self,
+		factory: &mut GlFactory,
+		transfer: OutputTransfer,
+	) -> Result<()> {
+		let transfer = match transfer {
+			OutputTransfer::Auto => OutputTransfer::Srgb,
+			OutputTransfer::Gamma(gamma)
+				if !(MIN_OUTPUT_GAMMA..=MAX_OUTPUT_GAMMA).contains(&gamma) =>
+			{
+				return Err(Error::GenericError(format!(
+					"Invalid output gamma {}, must be between {} and {}",
+					gamma, MIN_OUTPUT_GAMMA, MAX_OUTPUT_GAMMA
+				)))
+			}
+			transfer => transfer,
+		};
+		if transfer != self.transfer {
+			if let PostprocessProgram::BuiltIn = self.program {
+				self.pso = Self::create_program_pipeline_state(
+					factory,
+					&self.program,
+					shaders::PostPixelShaderVariant {
+						transfer,
+						..self.variant()
+					},
+				)?;
+			}
+			self.transfer = transfer;
+		}
+		Ok(())
+	}
+
+	/// Returns `true` if the built-in shader applies a color grading LUT
+	pub fn color_grading(&self) -> bool {
+		self.color_grading
+	}
+
+	/// Replaces the color grading LUT, applied by the built-in shader after the output transfer function.
+	/// The PSO is rebuilt when color grading is turned on or off, unless a custom shader or PSO is in use
+	/// * `factory` the factory attached to the current Gl context
+	/// * `lut` the new lookup table, `None` to disable color grading
+	pub fn set_color_grading(
+		&mut self,
+		factory: &mut GlFactory,
+		lut: Option<&lut::CubeLut>,
+	) -> Result<()> {
+		let color_grading = lut.is_some();
+		let lut = match lut {
+			Some(lut) => lut.create_texture(factory)?,
+			None => lut::CubeLut::identity(2).create_texture(factory)?,
+		};
+		if color_grading != self.color_grading {
+			if let PostprocessProgram::BuiltIn = self.program {
+				self.pso = Self::create_program_pipeline_state(
+					factory,
+					&self.program,
+					shaders::PostPixelShaderVariant {
+						color_grading,
+						..self.variant()
+					},
+				)?;
+			}
+			self.color_grading = color_grading;
+		}
+		self.lut = lut;
+		Ok(())
+	}
+
+	/// Returns the blend factor between the ungraded and the graded color
+	pub fn color_grading_strength(&self) -> f32 {
+		self.lut_strength
+	}
+
+	/// Sets the blend factor between the ungraded (0.0) and fully graded (1.0) color
+	pub fn set_color_grading_strength(&mut self, strength: f32) {
+		self.lut_strength = strength.max(0.).min(1.);
+	}
+
+	/// Returns the dithering pattern applied by the built-in shader
+	pub fn dithering(&self) -> DitherMode {
+		self.dither_mode
+	}
+
+	/// Selects the dithering pattern added to the output color, in display space, before it is
+	/// quantised to the GtkGlArea framebuffer format. The PSO is rebuilt when dithering is turned
+	/// on or off, unless a custom shader or PSO is in use
+	/// * `factory` the factory attached to the current Gl context
+	/// * `dither_mode` the new dithering pattern
+	pub fn set_dithering(
+		&mut self,
+		factory: &mut GlFactory,
+		dither_mode: DitherMode,
+	) -> Result<()> {
+		if dither_mode == self.dither_mode {
+			return Ok(());
+		}
+		let dither_map = dither::create_texture(factory, dither_mode)?;
+		let dithering = dither_mode != DitherMode::None;
+		if let PostprocessProgram::BuiltIn = self.program {
+			self.pso = Self::create_program_pipeline_state(
+				factory,
+				&self.program,
+				shaders::PostPixelShaderVariant {
+					dithering,
+					..self.variant()
+				},
+			)?;
+		}
+		self.dither_mode = dither_mode;
+		self.dither_map = dither_map;
+		Ok(())
+	}
+
+	/// Replaces the sampler used to read the source framebuffer. Switching to or from `Bicubic`
+	/// also swaps the built-in pixel shader, unless a custom shader or PSO is in use
+	/// * `factory` the factory attached to the current Gl context
+	/// * `filter` the new filtering method
+	/// * `wrap_mode` the new wrap mode
+	pub fn set_sampler(
+		&mut self,
+		factory: &mut GlFactory,
+		filter: PostprocessFilter,
+		wrap_mode: gfx::texture::WrapMode,
+	) -> Result<()> {
+		if let PostprocessProgram::BuiltIn = self.program {
+			if (filter == PostprocessFilter::Bicubic) != (self.filter == PostprocessFilter::Bicubic)
+			{
+				self.pso = Self::create_program_pipeline_state(
+					factory,
+					&self.program,
+					shaders::PostPixelShaderVariant {
+						filter,
+						..self.variant()
+					},
+				)?;
+			}
+		}
+		self.sampler = factory.create_sampler(gfx::texture::SamplerInfo::new(
+			filter.filter_method(),
+			wrap_mode,
+		));
+		self.filter = filter;
+		self.wrap_mode = wrap_mode;
+		Ok(())
+	}
+
+	/// Replaces the post-processing pixel shader, keeping [shaders::POST_VERTEX_SHADER]
+	/// (or [shaders::POST_VERTEX_SHADER_ES] on Gl ES) as the vertex shader. The shader must be
+	/// compatible with the [postprocess] pipeline and written in the dialect of the context
+	/// * `factory` the factory attached to the current Gl context
+	/// * `postprocess_shader` source code of the new pixel shader, `None` restores the built-in one
+	pub fn set_pixel_shader(
+		&mut self,
+		factory: &mut GlFactory,
+		postprocess_shader: Option<&[u8]>,
+	) -> Result<()> {
+		let program = match postprocess_shader {
+			Some(pixel_shader) => PostprocessProgram::PixelShader(pixel_shader.to_vec()),
+			None => PostprocessProgram::BuiltIn,
+		};
+		self.pso = Self::create_program_pipeline_state(factory, &program, self.variant())?;
+		self.program = program;
+		Ok(())
+	}
+
+	/// Replaces the post-processing PSO with one created by the library client, for full control
+	/// over shaders and rasterizer state. It will be kept until the next [set_pixel_shader()] call
+	/// * `pso` a pipeline state object built from [postprocess::new()] or a compatible initializer
+	pub fn set_pipeline_state(&mut self, pso: gfx::PipelineState<GlResources, postprocess::Meta>) {
+		self.pso = pso;
+		self.program = PostprocessProgram::PipelineState;
+	}
+
+	fn create_program_pipeline_state(
+		factory: &mut GlFactory,
+		program: &PostprocessProgram,
+		variant: shaders::PostPixelShaderVariant,
+	) -> Result<gfx::PipelineState<GlResources, postprocess::Meta>> {
+		use gfx::traits::FactoryExt;
+
+		let built_in_shader;
+		let pixel_shader_code = match *program {
+			PostprocessProgram::PixelShader(ref pixel_shader) => pixel_shader.as_slice(),
+			_ => {
+				built_in_shader = variant.source();
+				built_in_shader.as_bytes()
+			}
+		};
+
+		Ok(factory.create_pipeline_simple(
+			variant.dialect.post_vertex_shader().as_bytes(),
+			pixel_shader_code,
+			postprocess::new(),
+		)?)
+	}
+
 	/// performs a full screen pass using the original render screen as the source
-	/// and the GTK framebuffer as the target, using baked-in settings
-	/// TODO: make this easy to override
+	/// and the GTK framebuffer as the target, using the current sampler and PSO
 	pub fn full_screen_blit<CF>(
 		&self,
 		encoder: &mut gfx::Encoder<GlResources, GlCommandBuffer>,
@@ -294,6 +836,11 @@ impl PostprocessContext<GlDevice> {
 			&postprocess::Data {
 				vbuf: self.vbuf.clone(),
 				src: (render_screen.clone(), self.sampler.clone()),
+				lut: (self.lut.view.clone(), self.lut_sampler.clone()),
+				lut_domain_min: self.lut.domain_min,
+				lut_domain_max: self.lut.domain_max,
+				lut_strength: self.lut_strength,
+				dither: (self.dither_map.clone(), self.dither_sampler.clone()),
 				dst: (post_target.clone()),
 			},
 		);
@@ -311,6 +858,47 @@ where
 	}
 }
 
+/// The [GfxContext] owned by a [RenderContext], which cleans up the device when dropped
+struct OwnedGfxContext<D, F>(GfxContext<D, F>)
+where
+	D: gfx::Device,
+	F: gfx::Factory<D::Resources>;
+
+impl<D, F> std::ops::Deref for OwnedGfxContext<D, F>
+where
+	D: gfx::Device,
+	F: gfx::Factory<D::Resources>,
+{
+	type Target = GfxContext<D, F>;
+
+	fn deref(&self) -> &Self::Target {
+		&self.0
+	}
+}
+
+impl<D, F> std::ops::DerefMut for OwnedGfxContext<D, F>
+where
+	D: gfx::Device,
+	F: gfx::Factory<D::Resources>,
+{
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.0
+	}
+}
+
+impl<D, F> Drop for OwnedGfxContext<D, F>
+where
+	D: gfx::Device,
+	F: gfx::Factory<D::Resources>,
+{
+	/// Deletes the Gl objects of all the resources released so far, which requires
+	/// the Gl context to be current. Resources still referenced elsewhere are leaked
+	fn drop(&mut self) {
+		self.0.encoder.reset();
+		self.0.device.cleanup();
+	}
+}
+
 #[allow(unused)]
 /// Structure encapsulating all the GFX state needed for rendering within
 /// a GL context in GTK
@@ -319,21 +907,80 @@ where
 	D: gfx::Device,
 	F: gfx::Factory<D::Resources>,
 	CF: gfx::format::Formatted,
+	DF: gfx::format::Formatted,
 {
-	/// GFX factory, device and commands
-	gfx_context: GfxContext<D, F>,
 	/// Describes the gtk GlArea size and AA capability
 	viewport: Viewport,
 	/// Resources used by the postprocess step
 	postprocess_context: PostprocessContext<D>,
 	/// Render target, destination of the post-process stage
 	postprocess_target: gfx::handle::RenderTargetView<D::Resources, formats::GtkTargetColorFormat>,
+	/// Texture of the postprocess target, source of color read backs
+	postprocess_texture: gfx::handle::Texture<
+		D::Resources,
+		<formats::GtkTargetColorFormat as gfx::format::Formatted>::Surface,
+	>,
 	/// Off-screen texture view of the render target, source of the post-process stage
 	render_target_source: gfx::handle::ShaderResourceView<D::Resources, CF::View>,
 	/// Render target, destination of the main render stage
 	render_target: gfx::handle::RenderTargetView<D::Resources, CF>,
 	/// Depth buffer, used by the main render stage
 	depth_buffer: gfx::handle::DepthStencilView<D::Resources, DF>,
+	/// Off-screen texture view of the depth buffer, source of depth read backs
+	depth_buffer_source: gfx::handle::ShaderResourceView<D::Resources, DF::View>,
+	/// Depth read back resources, created on first use
+	depth_read_context: Option<depth::DepthReadContext<D>>,
+	/// Color read back resources, created on first use
+	color_picker_context: Option<color_picker::ColorPickerContext<D>>,
+	/// Extra color attachments, allocated along with `render_target`
+	color_attachments: attachments::ColorAttachments<D::Resources>,
+	/// Object ID attachment and read back resources, if enabled
+	picking_context: Option<picking::PickingContext<D>>,
+	/// Selection outline mask and resources, if enabled
+	outline_context: Option<outline::OutlineContext<D>>,
+	/// Requested output transfer function, possibly `Auto`
+	output_transfer: OutputTransfer,
+	/// The entry point reporting resets of the Gl context, if any
+	get_reset_status: Option<GetGraphicsResetStatus>,
+	/// Entry points used by the final copy onto the GlArea framebuffer
+	blit_path: blit::BlitPath,
+	/// Set once a reset of the Gl context has been detected
+	context_reset: Option<ContextReset>,
+	/// The last error raised while rendering a frame, until taken by the library client
+	last_error: Option<Error>,
+	/// Coalesces redraw requests and counts the frames still settling
+	scheduler: scheduler::RenderScheduler,
+	/// Per-frame statistics and GPU timers, if enabled
+	frame_stats: Option<stats::FrameStatsHistory>,
+	/// Whether the frame statistics have been enabled by [RenderContext::set_hud()]
+	hud_frame_stats: bool,
+	/// Performance overlay resources, if enabled
+	hud_context: Option<hud::HudContext<D>>,
+	/// The group of render contexts sharing Gl objects with this one, if any
+	share_group: Option<share::ShareGroup>,
+	/// GFX factory, device and commands. Declared after all the other resources,
+	/// so that it is dropped after them and can delete their Gl objects
+	gfx_context: OwnedGfxContext<D, F>,
+	/// The Gdk Gl context the resources belong to, current while they are dropped
+	owner_context: lifecycle::OwnerContext,
+}
+
+impl<D, F, CF, DF> Drop for RenderContext<D, F, CF, DF>
+where
+	D: gfx::Device,
+	F: gfx::Factory<D::Resources>,
+	CF: gfx::format::Formatted,
+	DF: gfx::format::Formatted,
+{
+	/// Makes the Gl context the resources were created in current, so that they are released
+	/// in the right context even if the owning GlArea is gone, or another one is being rendered.
+	/// The previous context is restored once all the fields have been dropped
+	fn drop(&mut self) {
+		self.owner_context.enter();
+		if let Some(share_group) = self.share_group.take() {
+			share_group.leave();
+		}
+	}
 }
 
 /// gfx device, Gl backend
@@ -353,6 +1000,8 @@ pub type GlFrameBufferTextureSrc<F> =
 pub type GlFrameBuffer<CF> = gfx::handle::RenderTargetView<GlResources, CF>;
 /// gfx main depth buffer, Gl backend
 pub type GlDepthBuffer<DF> = gfx::handle::DepthStencilView<GlResources, DF>;
+/// extra color attachments of the main render target, Gl backend
+pub type GlColorAttachments = attachments::ColorAttachments<GlResources>;
 /// render context, specialized for the gfx Gl backend
 pub type GlRenderContext<CF, DF> = RenderContext<GlDevice, GlFactory, CF, DF>;
 
@@ -362,6 +1011,8 @@ pub enum Error {
 	/// Used to convert any error into this one by encapsulating the original error into
 	/// a string message
 	GenericError(String),
+	/// The Gl context of a GlArea doesn't satisfy its [GlContextConfig]
+	GlContextError(glcontext::GlContextError),
 }
 
 /// Result which produces an [Error] on failure
@@ -476,7 +1127,9 @@ pub trait FactoryExt<R: gfx::Resources>: gfx::traits::FactoryExt<R> {
 		let tex = self.create_texture(
 			kind,
 			1,
-			gfx::memory::Bind::SHADER_RESOURCE | gfx::memory::Bind::RENDER_TARGET,
+			gfx::memory::Bind::SHADER_RESOURCE
+				| gfx::memory::Bind::RENDER_TARGET
+				| gfx::memory::Bind::TRANSFER_SRC,
 			gfx::memory::Usage::Data,
 			Some(<F as gfx::format::Formatted>::get_format().1),
 		)?;
@@ -557,6 +1210,26 @@ pub enum GlRenderCallbackStatus {
 	Skip,
 }
 
+/// `glGetGraphicsResetStatus()`, from Gl 4.5, Gl ES 3.2 or one of the robustness extensions
+type GetGraphicsResetStatus = extern "system" fn() -> gl::types::GLenum;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Cause of a Gl context reset, as reported by Gl 4.5 or the `GL_ARB_robustness`,
+/// `GL_KHR_robustness` and `GL_EXT_robustness` extensions. All the Gl objects are lost
+/// after a reset, the context and every resource must be re-created
+///
+/// Resets are only reported by contexts created with the `LOSE_CONTEXT_ON_RESET` notification
+/// strategy, which Gdk doesn't request: with the contexts of a GlArea, drivers report no reset
+/// and the recovery path is inactive, unless the driver enables notifications by default
+pub enum ContextReset {
+	/// The reset was caused by this context
+	Guilty,
+	/// The reset was caused by another context
+	Innocent,
+	/// The cause of the reset is unknown
+	Unknown,
+}
+
 /// Specialization of the GlRenderContext to be used with a Gl device
 pub type GlGfxContext = GfxContext<GlDevice, GlFactory>;
 /// Specalization of the GlCallbackContext to be used with a Gl device
@@ -575,6 +1248,8 @@ pub struct Viewport {
 	pub target_height: i32,
 	/// Antialiasing mode (supported `Single` and `Multi(4)`)
 	pub aa: gfx::texture::AaMode,
+	/// Number of GlArea framebuffer pixels per widget coordinate unit (2 on HiDPI screens)
+	pub scale_factor: i32,
 }
 
 impl Viewport {
@@ -597,9 +1272,75 @@ impl Viewport {
 			target_width,
 			target_height,
 			aa,
+			scale_factor: 1,
 		}
 	}
 
+	/// Converts a point in widget coordinates (origin top left, as in Gdk events) into
+	/// framebuffer pixel coordinates (origin bottom left, as in Gl), taking into account
+	/// the `scale_factor`. Returns `None` if the point falls outside the framebuffer
+	/// * `x` horizontal widget coordinate
+	/// * `y` vertical widget coordinate
+	pub fn widget_to_pixel(&self, x: f64, y: f64) -> Option<(i32, i32)> {
+		let px = (x * self.scale_factor as f64).floor() as i32;
+		let py = self.target_height - 1 - (y * self.scale_factor as f64).floor() as i32;
+		if px >= 0 && py >= 0 && px < self.target_width && py < self.target_height {
+			Some((px, py))
+		} else {
+			None
+		}
+	}
+
+	/// Converts a rectangle in widget coordinates (origin top left, as in Gdk events) into a
+	/// `(x, y, width, height)` rectangle in framebuffer pixel coordinates (origin bottom left, as in Gl),
+	/// clipped to the framebuffer. Returns `None` if the intersection is empty
+	/// * `rect` the rectangle in widget coordinates
+	pub fn widget_rect_to_pixels(&self, rect: &gdk::Rectangle) -> Option<(i32, i32, i32, i32)> {
+		let scale = self.scale_factor;
+		let x0 = (rect.x * scale).max(0);
+		let x1 = ((rect.x + rect.width) * scale).min(self.target_width);
+		let y0 = (self.target_height - (rect.y + rect.height) * scale).max(0);
+		let y1 = (self.target_height - rect.y * scale).min(self.target_height);
+		if x1 > x0 && y1 > y0 {
+			Some((x0, y0, x1 - x0, y1 - y0))
+		} else {
+			None
+		}
+	}
+
+	/// Converts a point in widget coordinates and a depth value, as returned by
+	/// [RenderContext::read_depth()], into normalized device coordinates, each axis in the range -1..1
+	/// * `x` horizontal widget coordinate
+	/// * `y` vertical widget coordinate
+	/// * `depth` window space depth, in the range 0..1
+	pub fn widget_to_ndc(&self, x: f64, y: f64, depth: Depth) -> [f32; 3] {
+		let scale = self.scale_factor as f64;
+		[
+			(2. * x * scale / self.target_width as f64 - 1.) as f32,
+			(1. - 2. * y * scale / self.target_height as f64) as f32,
+			2. * depth - 1.,
+		]
+	}
+
+	/// Reconstructs the world space position of the surface seen at a point of the widget,
+	/// given the depth stored there and the matrices it was rendered with.
+	/// Returns `None` if the matrices are singular
+	/// * `x` horizontal widget coordinate
+	/// * `y` vertical widget coordinate
+	/// * `depth` window space depth, as returned by [RenderContext::read_depth()]
+	/// * `projection` column-major projection matrix
+	/// * `view` column-major view matrix
+	pub fn unproject(
+		&self,
+		x: f64,
+		y: f64,
+		depth: Depth,
+		projection: &Matrix4,
+		view: &Matrix4,
+	) -> Option<[f32; 3]> {
+		depth::unproject(self.widget_to_ndc(x, y, depth), projection, view)
+	}
+
 	/// Computes the `width` and `height` of the offscreen render and depth target
 	/// from the `width` and `height` of the GlArea widget client area, taking into
 	/// account the `aa` hint, if the
@@ -659,6 +1400,91 @@ where
 	) -> Result<GlRenderCallbackStatus> {
 		Ok(GlRenderCallbackStatus::Continue)
 	}
+
+	/// Invoked before the Gl context is destroyed (e.g. when the GlArea is unrealized while being moved
+	/// to another container), with the context still current. Returns:
+	/// * `Ok(Continue)` if the callback has dropped all its Gl resources, and can rebuild them in `restore`
+	/// * `Ok(Skip)` or `Err(_)` if the callback must be dropped, and re-created from scratch
+	///
+	/// By default, returns `Skip`
+	fn release(&mut self, _gfx_context: &mut GlGfxContext) -> Result<GlRenderCallbackStatus> {
+		Ok(GlRenderCallbackStatus::Skip)
+	}
+
+	/// Invoked after a successful `release`, once a new Gl context has been created, to re-create the
+	/// Gl resources of the callback. Returns `Err(_)` if the callback must be re-created from scratch
+	/// * `gfx_context` Gfx device, factory, encoder attached to the new Gl context
+	/// * `viewport` size of the GlArea
+	fn restore(
+		&mut self,
+		_gfx_context: &mut GlGfxContext,
+		_viewport: &Viewport,
+	) -> Result<GlRenderCallbackStatus> {
+		Ok(GlRenderCallbackStatus::Continue)
+	}
+
+	/// Invoked instead of `render` by [RenderContext::with_gfx()], with the extra color attachments
+	/// declared via [RenderContext::add_color_attachment()]. They have the same size and AA mode as
+	/// `render_target` so they can be bound together in a multiple render target pipeline.
+	///
+	/// By default forwards to `render`, ignoring the attachments
+	fn render_with_attachments(
+		&mut self,
+		gfx_context: &mut GlGfxContext,
+		viewport: &Viewport,
+		render_target: &GlFrameBuffer<CF>,
+		depth_buffer: &GlDepthBuffer<DF>,
+		_color_attachments: &GlColorAttachments,
+	) -> Result<GlRenderCallbackStatus> {
+		self.render(gfx_context, viewport, render_target, depth_buffer)
+	}
+
+	/// Invoked after `render` when the selection outline is enabled, to mark the selected objects
+	/// * `gfx_context` Gfx device, factory, encoder attached to the current Gl context
+	/// * `viewport` size of the GlArea
+	/// * `mask_target` the outline mask, already cleared to zero. Any non-zero pixel is considered selected
+	/// * `depth_buffer` the depth buffer filled by `render`, which can be used to hide occluded selections
+	///
+	/// By default nothing is selected
+	fn render_outline_mask(
+		&mut self,
+		_gfx_context: &mut GlGfxContext,
+		_viewport: &Viewport,
+		_mask_target: &GlFrameBuffer<formats::OutlineMaskFormat>,
+		_depth_buffer: &GlDepthBuffer<DF>,
+	) -> Result<GlRenderCallbackStatus> {
+		Ok(GlRenderCallbackStatus::Continue)
+	}
+
+	/// Invoked at every tick of the frame clock while the animation of the [GfxGlArea] is running,
+	/// before the frame is rendered. Returns `Stop` to end the animation, after which frames are
+	/// only rendered on demand, until [GfxGlArea::start_animation()] is called again or
+	/// `resume_animation` returns `Continue`
+	/// * `frame` time of the frame, time elapsed since the previous one and frame counter
+	///
+	/// By default, keeps animating
+	fn animate(&mut self, _frame: &FrameInfo) -> AnimationStatus {
+		AnimationStatus::Continue
+	}
+
+	/// Invoked after each frame rendered on demand while the animation of the [GfxGlArea] is
+	/// stopped, e.g. after an input event. Returns `Continue` to start animating again from the next
+	/// tick of the frame clock, e.g. when the input has set something in motion. `render` receives
+	/// no [FrameInfo], the first `animate` after resuming has a `delta` of `0`
+	///
+	/// By default, stays stopped until [GfxGlArea::start_animation()] is called
+	fn resume_animation(&mut self) -> AnimationStatus {
+		AnimationStatus::Stop
+	}
+
+	/// Invoked after `render`, returns the number of frames still needed after this one while the
+	/// contents settle, e.g. during damped motion or progressive accumulation. They are rendered
+	/// without any further redraw request, see [RenderScheduler::settle()]
+	///
+	/// By default nothing is settling, and no more frames are rendered until requested
+	fn settle_frames(&self) -> u32 {
+		0
+	}
 }
 
 /// Implement custom post-processing behaviour for the GlArea
@@ -698,6 +1524,106 @@ where
 		gfx_context.flush();
 		Ok(GlRenderCallbackStatus::Continue)
 	}
+
+	/// Invoked instead of `postprocess` by [RenderContext::with_gfx()], with the extra color attachments
+	/// declared via [RenderContext::add_color_attachment()], as written by the render stage.
+	///
+	/// By default forwards to `postprocess`, ignoring the attachments
+	fn postprocess_with_attachments(
+		&mut self,
+		gfx_context: &mut GlGfxContext,
+		postprocess_context: &GlPostprocessContext,
+		viewport: &Viewport,
+		render_screen: &GlFrameBufferTextureSrc<CF>,
+		post_target: &GlFrameBuffer<formats::GtkTargetColorFormat>,
+		_color_attachments: &GlColorAttachments,
+	) -> Result<GlRenderCallbackStatus> {
+		self.postprocess(
+			gfx_context,
+			postprocess_context,
+			viewport,
+			render_screen,
+			post_target,
+		)
+	}
+}
+
+/// A render callback implementing both stages, which can be boxed and owned by a [GfxGlArea]
+/// and recovered as its concrete type via `as_any_mut`. Implemented for every `'static`
+/// type implementing [GlRenderCallback] and [GlPostprocessCallback]
+pub trait GlCallback<CF, DF>: GlRenderCallback<CF, DF> + GlPostprocessCallback<CF, DF>
+where
+	CF: gfx::format::Formatted<View = formats::GtkTargetColorView>,
+	CF::Channel: gfx::format::TextureChannel + gfx::format::RenderChannel,
+	CF::Surface: gfx::format::RenderSurface + gfx::format::TextureSurface,
+	DF: gfx::format::Formatted,
+	DF::Channel: gfx::format::TextureChannel + gfx::format::RenderChannel,
+	DF::Surface: gfx::format::DepthSurface + gfx::format::TextureSurface,
+{
+	/// Returns the callback as `Any`, to downcast it to its concrete type
+	fn as_any_mut(&mut self) -> &mut dyn std::any::Any;
+}
+
+impl<T, CF, DF> GlCallback<CF, DF> for T
+where
+	T: GlRenderCallback<CF, DF> + GlPostprocessCallback<CF, DF> + std::any::Any,
+	CF: gfx::format::Formatted<View = formats::GtkTargetColorView>,
+	CF::Channel: gfx::format::TextureChannel + gfx::format::RenderChannel,
+	CF::Surface: gfx::format::RenderSurface + gfx::format::TextureSurface,
+	DF: gfx::format::Formatted,
+	DF::Channel: gfx::format::TextureChannel + gfx::format::RenderChannel,
+	DF::Surface: gfx::format::DepthSurface + gfx::format::TextureSurface,
+{
+	fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+		self
+	}
+}
+
+/// Implement custom input handling for the GlArea. The events are converted into [Viewport] space,
+/// taking into account the scale factor and the bottom-left origin of the framebuffer.
+/// Each method returns `true` if the GlArea needs to be redrawn, by default events are ignored
+pub trait GlInputCallback {
+	/// Invoked when the pointer moves over the GlArea
+	/// * `viewport` size of the GlArea
+	/// * `motion` position of the pointer and modifiers
+	fn pointer_motion(&mut self, _viewport: &Viewport, _motion: &PointerMotion) -> bool {
+		false
+	}
+
+	/// Invoked when a mouse button is pressed or released over the GlArea
+	/// * `viewport` size of the GlArea
+	/// * `button` position of the pointer, button and action
+	fn pointer_button(&mut self, _viewport: &Viewport, _button: &PointerButton) -> bool {
+		false
+	}
+
+	/// Invoked when the mouse wheel or the touchpad is scrolled over the GlArea
+	/// * `viewport` size of the GlArea
+	/// * `scroll` position of the pointer and scroll amount
+	fn scroll(&mut self, _viewport: &Viewport, _scroll: &Scroll) -> bool {
+		false
+	}
+
+	/// Invoked when a key is pressed or released while the GlArea has the keyboard focus
+	/// * `viewport` size of the GlArea
+	/// * `key` key symbol and action
+	fn key(&mut self, _viewport: &Viewport, _key: &Key) -> bool {
+		false
+	}
+
+	/// Invoked when the GlArea gains or loses the keyboard focus
+	/// * `viewport` size of the GlArea
+	/// * `focused` `true` if the focus has been gained
+	fn focus(&mut self, _viewport: &Viewport, _focused: bool) -> bool {
+		false
+	}
+
+	/// Invoked when a touch gesture is recognised over the GlArea, or progresses
+	/// * `viewport` size of the GlArea
+	/// * `gesture` kind, stage and position of the gesture
+	fn gesture(&mut self, _viewport: &Viewport, _gesture: &Gesture) -> bool {
+		false
+	}
 }
 
 impl<CF, DF> GlRenderContext<CF, DF>
@@ -745,71 +1671,74 @@ where
 		postprocess_shader: Option<&[u8]>,
 	) -> Result<GlRenderContext<CF, DF>> {
 		use self::FactoryExt as LocalFactory;
-		use gfx::traits::FactoryExt;
 
-		let (device, mut factory) = gfx_device_gl::create(get_proc_addr);
+		let owner_context = lifecycle::OwnerContext::current();
+		let (device, mut factory) = if cfg!(debug_assertions) {
+			gfx_device_gl::create(|s| lifecycle::track(s, get_proc_addr(s)))
+		} else {
+			gfx_device_gl::create(get_proc_addr)
+		};
+		let get_reset_status = {
+			let info = device.get_info();
+			let name =
+				if info.is_version_supported(4, 5) || info.is_embedded_version_supported(3, 2) {
+					Some("glGetGraphicsResetStatus")
+				} else if info.is_extension_supported("GL_KHR_robustness") {
+					// the desktop flavour of the extension has no suffix
+					if info.version.is_embedded {
+						Some("glGetGraphicsResetStatusKHR")
+					} else {
+						Some("glGetGraphicsResetStatus")
+					}
+				} else if info.is_extension_supported("GL_ARB_robustness") {
+					Some("glGetGraphicsResetStatusARB")
+				} else if info.is_extension_supported("GL_EXT_robustness") {
+					Some("glGetGraphicsResetStatusEXT")
+				} else {
+					None
+				};
+			name.map(|name| get_proc_addr(name))
+				.filter(|ptr| !ptr.is_null())
+				.map(|ptr| unsafe {
+					std::mem::transmute::<dl::LibPtr, GetGraphicsResetStatus>(ptr)
+				})
+		};
+		let blit_path = blit::BlitPath::detect(device.get_info());
 		let encoder = factory.create_command_buffer().into();
 		let viewport = Viewport::with_aa(aa, widget_width, widget_height);
 
-		let (render_target_source, render_target, depth_buffer) = factory
-			.create_gtk_compatible_targets(aa, viewport.width as u16, viewport.height as u16)?;
-
-		let (_, _, postprocess_target) = factory.create_gtk_compatible_render_target(
-			formats::MSAA_NONE,
-			viewport.target_width as u16,
-			viewport.target_height as u16,
+		let (_, render_target_source, render_target) = factory
+			.create_gtk_compatible_render_target(
+				aa,
+				viewport.width as u16,
+				viewport.height as u16,
+			)?;
+		let (_, depth_buffer_source, depth_buffer) = factory.create_gtk_compatible_depth_target(
+			aa,
+			viewport.width as u16,
+			viewport.height as u16,
 		)?;
 
-		let full_screen_triangle = vec![
-			BlitVertex {
-				pos: [-1., -1.],
-				tex_coord: [0., 0.],
-			},
-			BlitVertex {
-				pos: [-1., 3.],
-				tex_coord: [0., 2.],
-			},
-			BlitVertex {
-				pos: [3., -1.],
-				tex_coord: [2., 0.],
-			},
-		];
-
-		let full_screen_triangle_index = vec![0u16, 2, 1];
-
-		let (vbuf, ibuf) = factory.create_vertex_buffer_with_slice(
-			&full_screen_triangle,
-			&full_screen_triangle_index[..],
-		);
-
-		let nearest_sampler = factory.create_sampler(gfx::texture::SamplerInfo::new(
-			gfx::texture::FilterMethod::Scale,
-			gfx::texture::WrapMode::Clamp,
-		));
-
-		let pixel_shader_code = postprocess_shader.unwrap_or_else(|| match viewport.aa {
-			gfx::texture::AaMode::Multi(4) => shaders::POST_PIXEL_SHADER_MSAA_4X.as_bytes(),
-			_ => shaders::POST_PIXEL_SHADER.as_bytes(),
-		});
-
-		let post_pso = factory.create_pipeline_simple(
-			shaders::POST_VERTEX_SHADER.as_bytes(),
-			pixel_shader_code,
-			postprocess::new(),
+		let (postprocess_texture, _, postprocess_target) = factory
+			.create_gtk_compatible_render_target(
+				formats::MSAA_NONE,
+				viewport.target_width as u16,
+				viewport.target_height as u16,
+			)?;
+
+		let shader_dialect = shaders::ShaderDialect::detect(device.get_info());
+		let postprocess_context = PostprocessContext::new(
+			&mut factory,
+			viewport.aa,
+			postprocess_shader,
+			shader_dialect,
 		)?;
 
-		let postprocess_context = PostprocessContext {
-			vbuf,
-			ibuf,
-			pso: post_pso,
-			sampler: nearest_sampler,
-		};
-
-		let gfx_context = GfxContext {
+		let gfx_context = OwnedGfxContext(GfxContext {
 			device,
 			factory,
 			encoder,
-		};
+		});
 
 		Ok(RenderContext {
 			gfx_context,
@@ -818,7 +1747,25 @@ where
 			render_target_source,
 			render_target,
 			depth_buffer,
+			depth_buffer_source,
+			depth_read_context: None,
+			color_picker_context: None,
 			postprocess_target,
+			postprocess_texture,
+			color_attachments: attachments::ColorAttachments::new(),
+			picking_context: None,
+			outline_context: None,
+			output_transfer: OutputTransfer::Srgb,
+			get_reset_status,
+			blit_path,
+			context_reset: None,
+			last_error: None,
+			scheduler: scheduler::RenderScheduler::new(),
+			frame_stats: None,
+			hud_frame_stats: false,
+			hud_context: None,
+			share_group: None,
+			owner_context,
 		})
 	}
 
@@ -832,6 +1779,476 @@ where
 		self.viewport.clone()
 	}
 
+	/// Returns the cause of the Gl context reset detected while rendering, if any. If `Some(_)`, no
+	/// more frames will be rendered: the context must be dropped and re-created along with all the
+	/// resources of the render callback, after re-realizing the GlArea to obtain a new Gl context.
+	/// Always `None` unless the Gl context has reset notifications enabled, see [ContextReset]
+	pub fn context_reset(&self) -> Option<ContextReset> {
+		self.context_reset
+	}
+
+	/// Returns and clears the last error raised within [with_gfx()], which can't report it
+	/// to its caller, including the ones returned by the render and postprocess callbacks.
+	/// Rendering goes on, falling back to the previous settings
+	pub fn take_error(&mut self) -> Option<Error> {
+		self.last_error.take()
+	}
+
+	/// Returns the entry points used by the final copy onto the GlArea framebuffer,
+	/// according to the capabilities of the Gl context
+	pub fn blit_path(&self) -> blit::BlitPath {
+		self.blit_path
+	}
+
+	/// Overrides the entry points used by the final copy onto the GlArea framebuffer, e.g. to work
+	/// around drivers advertising direct state access without a working implementation
+	/// * `blit_path` the new path. `DirectStateAccess` requires Gl 4.5 or `GL_ARB_direct_state_access`
+	pub fn set_blit_path(&mut self, blit_path: blit::BlitPath) {
+		self.blit_path = blit_path;
+	}
+
+	/// Queries the reset status of the Gl context, if supported
+	fn query_context_reset(&self) -> Option<ContextReset> {
+		let get_reset_status = self.get_reset_status?;
+		match get_reset_status() {
+			gl::GUILTY_CONTEXT_RESET => Some(ContextReset::Guilty),
+			gl::INNOCENT_CONTEXT_RESET => Some(ContextReset::Innocent),
+			gl::UNKNOWN_CONTEXT_RESET => Some(ContextReset::Unknown),
+			_ => None,
+		}
+	}
+
+	/// The render on demand scheduler, which tracks whether a frame is already queued and how many
+	/// more frames are needed while the contents settle
+	pub fn scheduler(&self) -> &scheduler::RenderScheduler {
+		&self.scheduler
+	}
+
+	/// Mutable access to the render on demand scheduler, e.g. to request extra frames via
+	/// [RenderScheduler::settle()]
+	pub fn scheduler_mut(&mut self) -> &mut scheduler::RenderScheduler {
+		&mut self.scheduler
+	}
+
+	/// Marks the contents as changed. Returns `true` if the caller must queue a render of the GlArea,
+	/// `false` if one is already queued. [GfxGlArea::queue_render()] takes care of it
+	pub fn request_redraw(&mut self) -> bool {
+		self.scheduler.request()
+	}
+
+	/// Starts collecting per-frame statistics: CPU time of each stage of [RenderContext::with_gfx()]
+	/// and, if the Gl context supports timer queries (Gl 3.3 or `GL_ARB_timer_query`), GPU time.
+	/// While GPU times are measured, the commands of each stage are flushed before the next one starts
+	/// * `capacity` number of frames retained in the history
+	pub fn enable_frame_stats(&mut self, capacity: usize) {
+		let gpu_timing = {
+			let info = self.gfx_context.device.get_info();
+			info.is_version_supported(3, 3) || info.is_extension_supported("GL_ARB_timer_query")
+		};
+		self.disable_frame_stats();
+		self.frame_stats = Some(stats::FrameStatsHistory::new(capacity, gpu_timing));
+	}
+
+	/// Stops collecting per-frame statistics and releases the GPU timers, in the Gl context
+	/// they were created in, whichever is current
+	pub fn disable_frame_stats(&mut self) {
+		if let Some(frame_stats) = self.frame_stats.take() {
+			self.owner_context.with_current(|| drop(frame_stats));
+		}
+		self.hud_frame_stats = false;
+	}
+
+	/// Joins a group of render contexts sharing Gl objects, leaving the previous one if any.
+	/// Resources created via [ShareGroup::resource()] or [ShareGroup::with_factory()] can then
+	/// be drawn by all the members. Must be invoked with the Gl context of this render context current
+	/// * `share_group` the group to join, `None` to leave the current one
+	pub fn set_share_group(&mut self, share_group: Option<&ShareGroup>) -> Result<()> {
+		if let Some(previous) = self.share_group.take() {
+			previous.leave();
+		}
+		if let Some(share_group) = share_group {
+			share_group.join()?;
+			self.share_group = Some(share_group.clone());
+		}
+		Ok(())
+	}
+
+	/// The group of render contexts sharing Gl objects with this one, if any
+	pub fn share_group(&self) -> Option<&ShareGroup> {
+		self.share_group.as_ref()
+	}
+
+	/// The statistics of the last frames, if enabled
+	pub fn frame_stats(&self) -> Option<&stats::FrameStatsHistory> {
+		self.frame_stats.as_ref()
+	}
+
+	/// Ends a stage of the frame statistics, if enabled
+	fn next_stats_stage(&mut self) {
+		if let Some(ref mut frame_stats) = self.frame_stats {
+			if frame_stats.is_gpu_timed() {
+				// the GPU only sees the commands of the stage once submitted
+				self.gfx_context.flush();
+			}
+			frame_stats.next_stage();
+		}
+	}
+
+	/// Declares an extra color attachment of format `T`, allocated and resized along with the main
+	/// render target and passed to [GlRenderCallback::render_with_attachments()] and
+	/// [GlPostprocessCallback::postprocess_with_attachments()]. Returns its index
+	pub fn add_color_attachment<T>(&mut self) -> Result<usize>
+	where
+		T: gfx::format::RenderFormat + gfx::format::TextureFormat,
+	{
+		self.add_raw_color_attachment(T::get_format())
+	}
+
+	/// Declares an extra color attachment given its runtime format. See [add_color_attachment()]
+	/// * `format` surface and channel type of the attachment
+	pub fn add_raw_color_attachment(&mut self, format: gfx::format::Format) -> Result<usize> {
+		self.color_attachments
+			.add(&mut self.gfx_context.factory, &self.viewport, format)
+	}
+
+	/// Releases all the extra color attachments. Also disables object picking, whose ID attachment
+	/// is one of them
+	pub fn clear_color_attachments(&mut self) {
+		self.picking_context = None;
+		self.color_attachments.clear();
+	}
+
+	/// Returns the extra color attachments, in declaration order
+	pub fn color_attachments(&self) -> &GlColorAttachments {
+		&self.color_attachments
+	}
+
+	/// Sets the number of GlArea framebuffer pixels per widget coordinate unit, as returned by
+	/// `gtk::WidgetExt::get_scale_factor()`. Used to convert widget coordinates, e.g. when picking
+	pub fn set_scale_factor(&mut self, scale_factor: i32) {
+		self.viewport.scale_factor = scale_factor.max(1);
+	}
+
+	/// Enables object picking by declaring an object ID attachment of format [formats::PickIdFormat].
+	/// Returns its index in the [GlColorAttachments] passed to
+	/// [GlRenderCallback::render_with_attachments()], where the callback is expected to write a
+	/// [PickId] for every pixel. The attachment is cleared to `0` (no object) before each frame.
+	/// Not available on Gl ES contexts
+	pub fn enable_picking(&mut self) -> Result<usize> {
+		if let Some(ref picking_context) = self.picking_context {
+			return Ok(picking_context.attachment());
+		}
+		self.require_desktop_shaders("object picking")?;
+		let picking_context = picking::PickingContext::new(
+			&mut self.gfx_context.factory,
+			&mut self.color_attachments,
+			&self.viewport,
+		)?;
+		let attachment = picking_context.attachment();
+		self.picking_context = Some(picking_context);
+		Ok(attachment)
+	}
+
+	/// Returns the index of the object ID attachment, `None` if picking is not enabled
+	pub fn picking_attachment(&self) -> Option<usize> {
+		self.picking_context.as_ref().map(|p| p.attachment())
+	}
+
+	/// Returns the ID of the object under the given point of the last rendered frame, if any.
+	/// Blocks until the GPU has completed the read back. The Gl context must be current
+	/// * `x` horizontal widget coordinate
+	/// * `y` vertical widget coordinate
+	pub fn pick(&mut self, x: f64, y: f64) -> Result<Option<PickId>> {
+		let rect = match self.viewport.widget_to_pixel(x, y) {
+			Some((px, py)) => (px, py, 1, 1),
+			None => return Ok(None),
+		};
+		let query = self.request_pick(rect)?;
+		Ok(
+			picking::PickingContext::collect(&mut self.gfx_context.factory, &query)?
+				.first()
+				.cloned(),
+		)
+	}
+
+	/// Returns the distinct IDs of the objects within the given rectangle of the last rendered frame,
+	/// sorted. Blocks until the GPU has completed the read back. The Gl context must be current
+	/// * `rect` the rectangle in widget coordinates
+	pub fn pick_rect(&mut self, rect: &gdk::Rectangle) -> Result<Vec<PickId>> {
+		let query = self.pick_rect_async(rect)?;
+		picking::PickingContext::collect(&mut self.gfx_context.factory, &query)
+	}
+
+	/// Starts reading back the IDs within the given rectangle of the last rendered frame, without
+	/// waiting for the GPU. Use [pick_result()] to collect them once the query is ready.
+	/// The Gl context must be current
+	/// * `rect` the rectangle in widget coordinates
+	pub fn pick_rect_async(&mut self, rect: &gdk::Rectangle) -> Result<PickQuery> {
+		let rect = self
+			.viewport
+			.widget_rect_to_pixels(rect)
+			.unwrap_or((0, 0, 0, 0));
+		self.request_pick(rect)
+	}
+
+	/// Collects the result of a [pick_rect_async()] query: the distinct IDs within the rectangle,
+	/// sorted, or `None` if the GPU has not completed the read back yet
+	/// * `query` a pending query
+	pub fn pick_result(&mut self, query: &PickQuery) -> Result<Option<Vec<PickId>>> {
+		if query.is_ready() {
+			picking::PickingContext::collect(&mut self.gfx_context.factory, query).map(Some)
+		} else {
+			Ok(None)
+		}
+	}
+
+	fn request_pick(&mut self, rect: (i32, i32, i32, i32)) -> Result<PickQuery> {
+		let (x, y, width, height) = rect;
+		match self.picking_context {
+			Some(ref picking_context) => picking_context.request(
+				&mut self.gfx_context,
+				&self.postprocess_context,
+				&self.color_attachments,
+				(x as u16, y as u16, width as u16, height as u16),
+			),
+			None => Err(Error::GenericError("picking is not enabled".to_string())),
+		}
+	}
+
+	/// Returns the depth value stored under the given point by the last rendered frame,
+	/// in the range 0..1 (the value the depth buffer was cleared to where nothing was drawn),
+	/// or `None` if the point is outside the widget. Multisampled depth buffers are resolved
+	/// by taking the nearest sample. Blocks until the GPU has completed the read back.
+	/// The Gl context must be current. Not available on Gl ES contexts
+	/// * `x` horizontal widget coordinate
+	/// * `y` vertical widget coordinate
+	pub fn read_depth(&mut self, x: f64, y: f64) -> Result<Option<Depth>> {
+		use gfx::memory::Typed;
+
+		let (px, py) = match self.viewport.widget_to_pixel(x, y) {
+			Some(pixel) => pixel,
+			None => return Ok(None),
+		};
+		if self.depth_read_context.is_none() {
+			self.require_desktop_shaders("depth read back")?;
+			self.depth_read_context = Some(depth::DepthReadContext::new(
+				&mut self.gfx_context.factory,
+				self.viewport.aa,
+			)?);
+		}
+		match self.depth_read_context {
+			Some(ref depth_read_context) => depth_read_context
+				.read(
+					&mut self.gfx_context,
+					&self.postprocess_context,
+					self.depth_buffer_source.raw(),
+					(px, py),
+				)
+				.map(Some),
+			None => Ok(None),
+		}
+	}
+
+	/// Returns the color of the pixel under the given point of the last rendered frame, both as
+	/// presented and before postprocessing, or `None` if the point is outside the widget.
+	/// Blocks until the GPU has completed the read back. The Gl context must be current.
+	/// Not available on Gl ES contexts
+	/// * `x` horizontal widget coordinate
+	/// * `y` vertical widget coordinate
+	pub fn read_color(&mut self, x: f64, y: f64) -> Result<Option<PickedColor>> {
+		self.read_color_average(x, y, 1)
+	}
+
+	/// As [read_color()], averaging the `size` x `size` pixels centered on the given point,
+	/// clipped to the framebuffer. Not available on Gl ES contexts
+	/// * `x` horizontal widget coordinate
+	/// * `y` vertical widget coordinate
+	/// * `size` edge of the averaged neighbourhood, in framebuffer pixels
+	pub fn read_color_average(&mut self, x: f64, y: f64, size: u32) -> Result<Option<PickedColor>> {
+		use gfx::memory::Typed;
+
+		let (px, py) = match self.viewport.widget_to_pixel(x, y) {
+			Some(pixel) => pixel,
+			None => return Ok(None),
+		};
+		let size = size.max(1) as i32;
+		let (x0, y0) = (px - (size - 1) / 2, py - (size - 1) / 2);
+		let (x1, y1) = (
+			(x0 + size).min(self.viewport.target_width),
+			(y0 + size).min(self.viewport.target_height),
+		);
+		let (x0, y0) = (x0.max(0), y0.max(0));
+		if self.color_picker_context.is_none() {
+			self.require_desktop_shaders("color read back")?;
+			self.color_picker_context = Some(color_picker::ColorPickerContext::new(
+				&mut self.gfx_context.factory,
+				self.viewport.aa,
+			)?);
+		}
+		match self.color_picker_context {
+			Some(ref color_picker_context) => color_picker_context
+				.read(
+					&mut self.gfx_context,
+					&self.postprocess_context,
+					&self.render_target_source,
+					self.postprocess_texture.raw(),
+					(x0, y0, x1 - x0, y1 - y0),
+				)
+				.map(Some),
+			None => Ok(None),
+		}
+	}
+
+	/// Enables, updates or disables the selection outline drawn in the postprocessing stage.
+	/// The mask target is allocated the first time the outline is enabled.
+	/// Not available on Gl ES contexts
+	/// * `settings` appearance of the outline, `None` to disable it
+	pub fn set_outline(&mut self, settings: Option<OutlineSettings>) -> Result<()> {
+		match (settings, self.outline_context.as_mut()) {
+			(Some(settings), Some(outline_context)) => outline_context.settings = settings,
+			(Some(settings), None) => {
+				self.require_desktop_shaders("the selection outline")?;
+				self.outline_context = Some(outline::OutlineContext::new(
+					&mut self.gfx_context.factory,
+					&self.viewport,
+					settings,
+				)?)
+			}
+			(None, _) => self.outline_context = None,
+		}
+		Ok(())
+	}
+
+	/// Fails on Gl ES contexts, for the features whose shaders exist in GLSL 1.50 core only
+	/// * `feature` name of the feature, for the error message
+	fn require_desktop_shaders(&self, feature: &str) -> Result<()> {
+		if self.postprocess_context.shader_dialect().is_es() {
+			Err(Error::GenericError(format!(
+				"{} is not supported on Gl ES contexts, its shaders are GLSL 1.50 core",
+				feature
+			)))
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Returns the current selection outline settings, `None` if disabled
+	pub fn outline(&self) -> Option<OutlineSettings> {
+		self.outline_context.as_ref().map(|o| o.settings)
+	}
+
+	/// Enables, updates or disables the performance overlay drawn after the postprocessing stage,
+	/// at the native resolution of the GlArea. Frame statistics are enabled along with it if needed,
+	/// and disabled along with it unless enabled via [RenderContext::enable_frame_stats()] meanwhile
+	/// * `settings` appearance of the overlay, `None` to hide it
+	pub fn set_hud(&mut self, settings: Option<HudSettings>) -> Result<()> {
+		match (settings, self.hud_context.as_mut()) {
+			(Some(settings), Some(hud_context)) => hud_context.settings = settings,
+			(Some(settings), None) => {
+				self.hud_context = Some(hud::HudContext::new(
+					&mut self.gfx_context.factory,
+					settings,
+					self.postprocess_context.shader_dialect(),
+				)?);
+				if self.frame_stats.is_none() {
+					self.enable_frame_stats(hud::DEFAULT_STATS_CAPACITY);
+					self.hud_frame_stats = true;
+				}
+			}
+			(None, _) => self.hide_hud(),
+		}
+		Ok(())
+	}
+
+	/// Drops the performance overlay, and the frame statistics enabled for it
+	fn hide_hud(&mut self) {
+		self.hud_context = None;
+		if self.hud_frame_stats {
+			self.disable_frame_stats();
+		}
+	}
+
+	/// Returns the current performance overlay settings, `None` if hidden
+	pub fn hud(&self) -> Option<HudSettings> {
+		self.hud_context.as_ref().map(|h| h.settings)
+	}
+
+	/// Returns a reference to the current postprocessing context
+	pub fn postprocess_context(&self) -> &GlPostprocessContext {
+		&self.postprocess_context
+	}
+
+	/// Replaces the sampler used by the postprocessing stage to read the render target.
+	/// See [PostprocessContext::set_sampler()]
+	/// * `filter` the new filtering method
+	/// * `wrap_mode` the new wrap mode
+	pub fn set_postprocess_sampler(
+		&mut self,
+		filter: PostprocessFilter,
+		wrap_mode: gfx::texture::WrapMode,
+	) -> Result<()> {
+		self.postprocess_context
+			.set_sampler(&mut self.gfx_context.factory, filter, wrap_mode)
+	}
+
+	/// Replaces the postprocessing pixel shader. See [PostprocessContext::set_pixel_shader()]
+	/// * `postprocess_shader` source code of the new pixel shader, `None` restores the built-in one
+	pub fn set_postprocess_shader(&mut self, postprocess_shader: Option<&[u8]>) -> Result<()> {
+		self.postprocess_context
+			.set_pixel_shader(&mut self.gfx_context.factory, postprocess_shader)
+	}
+
+	/// Returns the requested output transfer function
+	pub fn output_transfer(&self) -> OutputTransfer {
+		self.output_transfer
+	}
+
+	/// Selects the transfer function applied by the built-in postprocessing shaders.
+	/// With `Auto`, the color encoding of the GtkGlArea framebuffer is checked at every frame
+	/// and the matching shader variant is selected
+	/// * `transfer` the new transfer function. A `Gamma` exponent must be within
+	/// [MIN_OUTPUT_GAMMA] and [MAX_OUTPUT_GAMMA]
+	pub fn set_output_transfer(&mut self, transfer: OutputTransfer) -> Result<()> {
+		self.postprocess_context
+			.set_output_transfer(&mut self.gfx_context.factory, transfer)?;
+		self.output_transfer = transfer;
+		Ok(())
+	}
+
+	/// Enables, replaces or disables the color grading LUT. See [PostprocessContext::set_color_grading()]
+	/// * `lut` the new lookup table, `None` to disable color grading
+	/// * `strength` blend factor between the ungraded (0.0) and fully graded (1.0) color
+	pub fn set_color_grading(&mut self, lut: Option<&CubeLut>, strength: f32) -> Result<()> {
+		self.postprocess_context
+			.set_color_grading_strength(strength);
+		self.postprocess_context
+			.set_color_grading(&mut self.gfx_context.factory, lut)
+	}
+
+	/// Changes the blend factor of the current color grading LUT, without reloading it
+	/// * `strength` blend factor between the ungraded (0.0) and fully graded (1.0) color
+	pub fn set_color_grading_strength(&mut self, strength: f32) {
+		self.postprocess_context
+			.set_color_grading_strength(strength);
+	}
+
+	/// Selects the dithering pattern applied by the built-in postprocessing shaders.
+	/// See [PostprocessContext::set_dithering()]
+	/// * `dither_mode` the new dithering pattern
+	pub fn set_dithering(&mut self, dither_mode: DitherMode) -> Result<()> {
+		self.postprocess_context
+			.set_dithering(&mut self.gfx_context.factory, dither_mode)
+	}
+
+	/// Replaces the postprocessing PSO. See [PostprocessContext::set_pipeline_state()]
+	/// * `pso` a pipeline state object built from [postprocess::new()] or a compatible initializer
+	pub fn set_postprocess_pipeline_state(
+		&mut self,
+		pso: gfx::PipelineState<GlResources, postprocess::Meta>,
+	) {
+		self.postprocess_context.set_pipeline_state(pso);
+	}
+
 	/// Re-allocates render buffers and textures if the size has changed since last resize or creation of the context
 	/// * `widget_width` width of the client area of the containing widget
 	/// * `widget_height` height of the client area of the containing widget`
@@ -843,19 +2260,32 @@ where
 		mut render_callback: Option<&mut R>,
 	) -> Result<()>
 	where
-		R: GlRenderCallback<CF, DF>,
+		R: GlRenderCallback<CF, DF> + ?Sized,
 	{
-		let new_viewport = Viewport::with_aa(self.viewport.aa, widget_width, widget_height);
+		let new_viewport = Viewport {
+			scale_factor: self.viewport.scale_factor,
+			..Viewport::with_aa(self.viewport.aa, widget_width, widget_height)
+		};
 		if new_viewport.width != self.viewport.width || new_viewport.height != self.viewport.height
 		{
-			let (frame_buffer_source, frame_buffer, depth_buffer) =
-				self.gfx_context.factory.create_gtk_compatible_targets(
+			let (_, frame_buffer_source, frame_buffer) = self
+				.gfx_context
+				.factory
+				.create_gtk_compatible_render_target(
+					self.viewport.aa,
+					new_viewport.width as u16,
+					new_viewport.height as u16,
+				)?;
+			let (_, depth_buffer_source, depth_buffer) = self
+				.gfx_context
+				.factory
+				.create_gtk_compatible_depth_target(
 					self.viewport.aa,
 					new_viewport.width as u16,
 					new_viewport.height as u16,
 				)?;
 
-			let (_, _, postprocess_target) = self
+			let (postprocess_texture, _, postprocess_target) = self
 				.gfx_context
 				.factory
 				.create_gtk_compatible_render_target(
@@ -868,7 +2298,20 @@ where
 			self.render_target_source = frame_buffer_source;
 			self.render_target = frame_buffer;
 			self.postprocess_target = postprocess_target;
+			self.postprocess_texture = postprocess_texture;
 			self.depth_buffer = depth_buffer;
+			self.depth_buffer_source = depth_buffer_source;
+
+			self.color_attachments
+				.resize(&mut self.gfx_context.factory, &self.viewport)?;
+
+			if let Some(ref mut picking_context) = self.picking_context {
+				picking_context.resize(&mut self.gfx_context.factory, &self.viewport)?;
+			}
+
+			if let Some(ref mut outline_context) = self.outline_context {
+				outline_context.resize(&mut self.gfx_context.factory, &self.viewport)?;
+			}
 
 			if let Some(ref mut render_callback) = render_callback {
 				render_callback.resize(&mut self.gfx_context, self.viewport.clone())?;
@@ -885,87 +2328,161 @@ where
 	/// * `render_callback` a reference of the render callback implementing the actual drawing
 	pub fn with_gfx<R>(&mut self, render_callback: &mut R)
 	where
-		R: GlRenderCallback<CF, DF> + GlPostprocessCallback<CF, DF>,
+		R: GlRenderCallback<CF, DF> + GlPostprocessCallback<CF, DF> + ?Sized,
 	{
-		fn get_current_draw_framebuffer_name() -> u32 {
-			let mut framebuffer_name = 0;
+		fn get_framebuffer_color_encoding(framebuffer_name: u32, es: bool) -> u32 {
+			// Gl ES only names the back buffer of the default framebuffer as a whole
+			let attachment = if framebuffer_name == 0 {
+				if es {
+					gl::BACK
+				} else {
+					gl::BACK_LEFT
+				}
+			} else {
+				gl::COLOR_ATTACHMENT0
+			};
+			let mut color_encoding = gl::LINEAR as i32;
 			unsafe {
-				gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut framebuffer_name);
+				gl::GetFramebufferAttachmentParameteriv(
+					gl::DRAW_FRAMEBUFFER,
+					attachment,
+					gl::FRAMEBUFFER_ATTACHMENT_COLOR_ENCODING,
+					&mut color_encoding,
+				);
 			}
-			framebuffer_name as u32
+			color_encoding as u32
 		}
 
-		fn get_current_renderbuffer_binding() -> u32 {
-			let mut renderbuffer_binding = 0;
-			unsafe {
-				gl::GetIntegerv(gl::RENDERBUFFER_BINDING, &mut renderbuffer_binding);
-			}
-			renderbuffer_binding as u32
+		// requests from now on belong to the next frame
+		self.scheduler.begin_frame();
+
+		// after a reset all the Gl objects are gone, rendering must stop until re-created
+		if self.context_reset.is_none() {
+			self.context_reset = self.query_context_reset();
+		}
+		if self.context_reset.is_some() {
+			return;
 		}
 
 		// we need to keep track of the framebuffer Gtk wants to render to,
 		// which has been bound in the current gl_context, by the GlArea machinery
-		let gtk_framebuffer_name = get_current_draw_framebuffer_name();
-		let gtk_renderbuffer_binding = get_current_renderbuffer_binding();
+		let gtk_framebuffer = blit::GtkFramebuffer::current();
+
+		// if the Gtk framebuffer does its own sRGB encoding, the postprocessing must not
+		if let OutputTransfer::Auto = self.output_transfer {
+			let transfer = match get_framebuffer_color_encoding(
+				gtk_framebuffer.name,
+				self.postprocess_context.shader_dialect().is_es(),
+			) {
+				gl::SRGB => OutputTransfer::Passthrough,
+				_ => OutputTransfer::Srgb,
+			};
+			if let Err(e) = self
+				.postprocess_context
+				.set_output_transfer(&mut self.gfx_context.factory, transfer)
+			{
+				// stop checking, the same error would be raised at every frame
+				self.output_transfer = self.postprocess_context.output_transfer();
+				self.last_error = Some(e);
+			}
+		}
 		// we do some GFX rendering, will knacker the buffer bindings but end up with a surface
 		// we can blit from
-		let render_result = GlRenderCallback::render(
+		if let Some(ref mut frame_stats) = self.frame_stats {
+			frame_stats.begin_frame();
+		}
+		if let Some(ref picking_context) = self.picking_context {
+			picking_context.clear(&mut self.gfx_context.encoder, &self.color_attachments);
+		}
+
+		let render_result = GlRenderCallback::render_with_attachments(
 			render_callback,
 			&mut self.gfx_context,
 			&self.viewport,
 			&self.render_target,
 			&self.depth_buffer,
+			&self.color_attachments,
 		);
 
+		let render_result = match (render_result, self.outline_context.as_ref()) {
+			(Ok(GlRenderCallbackStatus::Continue), Some(outline_context)) => {
+				outline_context.clear_mask(&mut self.gfx_context.encoder);
+				GlRenderCallback::render_outline_mask(
+					render_callback,
+					&mut self.gfx_context,
+					&self.viewport,
+					&outline_context.mask_target,
+					&self.depth_buffer,
+				)
+			}
+			(render_result, _) => render_result,
+		};
+		self.scheduler.settle(render_callback.settle_frames());
+		self.next_stats_stage();
+
 		let postprocess_result = match render_result {
-			Ok(GlRenderCallbackStatus::Continue) => GlPostprocessCallback::postprocess(
-				render_callback,
-				&mut self.gfx_context,
-				&self.postprocess_context,
-				&self.viewport,
-				&self.render_target_source,
-				&self.postprocess_target,
-			), // TODO: handle error
+			Ok(GlRenderCallbackStatus::Continue) => {
+				GlPostprocessCallback::postprocess_with_attachments(
+					render_callback,
+					&mut self.gfx_context,
+					&self.postprocess_context,
+					&self.viewport,
+					&self.render_target_source,
+					&self.postprocess_target,
+					&self.color_attachments,
+				)
+				.map(|status| {
+					if let Some(ref outline_context) = self.outline_context {
+						outline_context.draw(
+							&mut self.gfx_context.encoder,
+							&self.postprocess_context,
+							&self.postprocess_target,
+						);
+						self.gfx_context.flush();
+					}
+					status
+				})
+			}
 			Ok(_) => {
 				self.gfx_context.flush();
 				Ok(GlRenderCallbackStatus::Skip)
 			}
 			Err(e) => Err(e),
 		};
-		if postprocess_result.is_ok() {
-			// we have a full frame here and GFX shouldn't have thrown away the current
-			// framebuffer bindings, yet, so we can grab it
-			let gfx_framebuffer_name = get_current_draw_framebuffer_name();
-			unsafe {
-				// we want the framebuffer from Gfx (which we have just got) as the blit source
-				gl::BindFramebuffer(gl::READ_FRAMEBUFFER, gfx_framebuffer_name);
-				// and the framebuffer from Gtk (which we have saved earlier) as the destination
-				gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, gtk_framebuffer_name);
-				// we need to re-attach the color attachment buffer as well
-				gl::NamedFramebufferRenderbuffer(
-					gtk_framebuffer_name,
-					gl::COLOR_ATTACHMENT0,
-					gl::RENDERBUFFER,
-					gtk_renderbuffer_binding,
-				);
-				// And finally, we blit the GFX framebuffer onto the GlArea framebuffer.
-				// This is wasteful as the GlArea code already does this for its own off-screen
-				// framebuffer target but we have no means to blit directly to the screen backbuffer
-				// as it happens under the hood within the GlArea rendering code
-				gl::BlitFramebuffer(
-					0,
-					0,
-					self.viewport.target_width,
-					self.viewport.target_height,
-					0,
-					0,
+		self.next_stats_stage();
+		if let (Ok(GlRenderCallbackStatus::Continue), Some(hud_context)) =
+			(postprocess_result.as_ref(), self.hud_context.as_mut())
+		{
+			let hud_result = hud_context.draw(
+				&mut self.gfx_context.encoder,
+				&self.postprocess_target,
+				&self.viewport,
+				self.frame_stats.as_ref(),
+				CF::get_format(),
+				DF::get_format(),
+			);
+			self.gfx_context.flush();
+			// the overlay is hidden rather than failing every frame, the error is kept for take_error()
+			if let Err(e) = hud_result {
+				self.last_error = Some(e);
+				self.hide_hud();
+			}
+		}
+		match postprocess_result {
+			Ok(_) => {
+				// we have a full frame here and GFX shouldn't have thrown away the current
+				// framebuffer bindings, yet, so we can blit from it onto the Gtk framebuffer
+				gtk_framebuffer.blit_from_current(
+					self.blit_path,
 					self.viewport.target_width,
 					self.viewport.target_height,
-					gl::COLOR_BUFFER_BIT,
-					gl::NEAREST,
 				);
-				gl::Flush();
 			}
+			// a failed render or postprocessing step leaves the Gtk framebuffer untouched
+			Err(e) => self.last_error = Some(e),
+		}
+		if let Some(ref mut frame_stats) = self.frame_stats {
+			frame_stats.end_frame();
 		}
 		self.cleanup();
 	}
@@ -973,5 +2490,8 @@ where
 	fn cleanup(&mut self) {
 		use gfx::Device;
 		self.gfx_context.device.cleanup();
+		if let Some(ref share_group) = self.share_group {
+			share_group.cleanup();
+		}
 	}
 }
@@ -0,0 +1,246 @@
+//! Context-aware destruction of a [RenderContext]: the Gdk Gl context owning its Gl objects is
+//! made current while they are deleted, and the previous context restored afterwards.
+//!
+//! In debug builds, the Gl objects created and deleted by gfx are counted per Gdk Gl context
+//! (by wrapping the Gl function pointers handed to the gfx device), and the ones still alive
+//! once a [RenderContext] has been dropped are reported on stderr
+
+use super::*;
+use gdk::GLContextExt;
+use gl::types::{GLenum, GLsizei, GLuint};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+/// Number of live Gl objects of each kind
+pub struct GlObjectCounts {
+	/// Buffer objects
+	pub buffers: isize,
+	/// Texture objects
+	pub textures: isize,
+	/// Renderbuffer objects
+	pub renderbuffers: isize,
+	/// Sampler objects
+	pub samplers: isize,
+	/// Program objects
+	pub programs: isize,
+	/// Shader objects
+	pub shaders: isize,
+}
+
+impl GlObjectCounts {
+	fn since(&self, baseline: &GlObjectCounts) -> GlObjectCounts {
+		GlObjectCounts {
+			buffers: self.buffers - baseline.buffers,
+			textures: self.textures - baseline.textures,
+			renderbuffers: self.renderbuffers - baseline.renderbuffers,
+			samplers: self.samplers - baseline.samplers,
+			programs: self.programs - baseline.programs,
+			shaders: self.shaders - baseline.shaders,
+		}
+	}
+
+	fn is_empty(&self) -> bool {
+		*self == GlObjectCounts::default()
+	}
+}
+
+thread_local! {
+	static LIVE_OBJECTS: RefCell<Vec<(Option<gdk::GLContext>, GlObjectCounts)>> = RefCell::new(Vec::new());
+}
+
+fn record<F>(update: F)
+where
+	F: FnOnce(&mut GlObjectCounts),
+{
+	let current = gdk::GLContext::get_current();
+	LIVE_OBJECTS.with(|live_objects| {
+		let mut live_objects = live_objects.borrow_mut();
+		let index = match live_objects.iter().position(|&(ref c, _)| *c == current) {
+			Some(index) => index,
+			None => {
+				live_objects.push((current, GlObjectCounts::default()));
+				live_objects.len() - 1
+			}
+		};
+		update(&mut live_objects[index].1);
+	});
+}
+
+/// Returns the number of live Gl objects created by gfx in `context`
+pub fn live_objects(context: &Option<gdk::GLContext>) -> GlObjectCounts {
+	LIVE_OBJECTS.with(|live_objects| {
+		live_objects
+			.borrow()
+			.iter()
+			.find(|&&(ref c, _)| c == context)
+			.map(|&(_, counts)| counts)
+			.unwrap_or_default()
+	})
+}
+
+// glGen*/glDelete* wrappers, forwarding to the function pointer stored in `$real`
+macro_rules! names_shim {
+	($real:ident, $shim:ident, $counter:ident, $sign:expr) => {
+		static $real: AtomicUsize = AtomicUsize::new(0);
+		extern "system" fn $shim(n: GLsizei, names: *mut GLuint) {
+			let real: extern "system" fn(GLsizei, *mut GLuint) =
+				unsafe { std::mem::transmute($real.load(Ordering::Relaxed)) };
+			real(n, names);
+			record(|counts| counts.$counter += $sign * n as isize);
+		}
+	};
+}
+
+names_shim!(GEN_BUFFERS, gen_buffers, buffers, 1);
+names_shim!(DELETE_BUFFERS, delete_buffers, buffers, -1);
+names_shim!(GEN_TEXTURES, gen_textures, textures, 1);
+names_shim!(DELETE_TEXTURES, delete_textures, textures, -1);
+names_shim!(GEN_RENDERBUFFERS, gen_renderbuffers, renderbuffers, 1);
+names_shim!(
+	DELETE_RENDERBUFFERS,
+	delete_renderbuffers,
+	renderbuffers,
+	-1
+);
+names_shim!(GEN_SAMPLERS, gen_samplers, samplers, 1);
+names_shim!(DELETE_SAMPLERS, delete_samplers, samplers, -1);
+
+static CREATE_PROGRAM: AtomicUsize = AtomicUsize::new(0);
+extern "system" fn create_program() -> GLuint {
+	let real: extern "system" fn() -> GLuint =
+		unsafe { std::mem::transmute(CREATE_PROGRAM.load(Ordering::Relaxed)) };
+	let name = real();
+	record(|counts| counts.programs += 1);
+	name
+}
+
+static DELETE_PROGRAM: AtomicUsize = AtomicUsize::new(0);
+extern "system" fn delete_program(name: GLuint) {
+	let real: extern "system" fn(GLuint) =
+		unsafe { std::mem::transmute(DELETE_PROGRAM.load(Ordering::Relaxed)) };
+	real(name);
+	record(|counts| counts.programs -= 1);
+}
+
+static CREATE_SHADER: AtomicUsize = AtomicUsize::new(0);
+extern "system" fn create_shader(kind: GLenum) -> GLuint {
+	let real: extern "system" fn(GLenum) -> GLuint =
+		unsafe { std::mem::transmute(CREATE_SHADER.load(Ordering::Relaxed)) };
+	let name = real(kind);
+	record(|counts| counts.shaders += 1);
+	name
+}
+
+static DELETE_SHADER: AtomicUsize = AtomicUsize::new(0);
+extern "system" fn delete_shader(name: GLuint) {
+	let real: extern "system" fn(GLuint) =
+		unsafe { std::mem::transmute(DELETE_SHADER.load(Ordering::Relaxed)) };
+	real(name);
+	record(|counts| counts.shaders -= 1);
+}
+
+/// Wraps a Gl function pointer looked up for the gfx device, so that the creation and deletion
+/// of objects is counted. Other functions are returned unchanged
+/// * `name` name of the Gl function
+/// * `real` the function pointer, as returned by the loader
+pub fn track(name: &str, real: dl::LibPtr) -> dl::LibPtr {
+	if real.is_null() {
+		return real;
+	}
+	let (slot, shim) = match name {
+		"glGenBuffers" => (&GEN_BUFFERS, gen_buffers as dl::LibPtr),
+		"glDeleteBuffers" => (&DELETE_BUFFERS, delete_buffers as dl::LibPtr),
+		"glGenTextures" => (&GEN_TEXTURES, gen_textures as dl::LibPtr),
+		"glDeleteTextures" => (&DELETE_TEXTURES, delete_textures as dl::LibPtr),
+		"glGenRenderbuffers" => (&GEN_RENDERBUFFERS, gen_renderbuffers as dl::LibPtr),
+		"glDeleteRenderbuffers" => (&DELETE_RENDERBUFFERS, delete_renderbuffers as dl::LibPtr),
+		"glGenSamplers" => (&GEN_SAMPLERS, gen_samplers as dl::LibPtr),
+		"glDeleteSamplers" => (&DELETE_SAMPLERS, delete_samplers as dl::LibPtr),
+		"glCreateProgram" => (&CREATE_PROGRAM, create_program as dl::LibPtr),
+		"glDeleteProgram" => (&DELETE_PROGRAM, delete_program as dl::LibPtr),
+		"glCreateShader" => (&CREATE_SHADER, create_shader as dl::LibPtr),
+		"glDeleteShader" => (&DELETE_SHADER, delete_shader as dl::LibPtr),
+		_ => return real,
+	};
+	slot.store(real as usize, Ordering::Relaxed);
+	shim
+}
+
+/// The Gdk Gl context owning the Gl objects of a [RenderContext]. Declared as its last field,
+/// so that it is dropped after the gfx device has deleted all the other resources
+pub struct OwnerContext {
+	/// Context current when the render context was created
+	context: Option<gdk::GLContext>,
+	/// Context to restore once dropped, if it had to be switched
+	previous: Option<Option<gdk::GLContext>>,
+	/// Live objects before the render context created any
+	baseline: GlObjectCounts,
+}
+
+impl OwnerContext {
+	/// Captures the current Gdk Gl context, must be invoked before creating the gfx device
+	pub fn current() -> Self {
+		let context = gdk::GLContext::get_current();
+		let baseline = live_objects(&context);
+		OwnerContext {
+			context,
+			previous: None,
+			baseline,
+		}
+	}
+
+	/// Invokes `f` with the owning context current, then restores the previous one, e.g. to delete
+	/// Gl objects outside of [RenderContext::with_gfx()]
+	pub fn with_current<T, F>(&self, f: F) -> T
+	where
+		F: FnOnce() -> T,
+	{
+		let current = gdk::GLContext::get_current();
+		let switch = match self.context {
+			Some(ref context) if current.as_ref() != Some(context) => {
+				context.make_current();
+				true
+			}
+			_ => false,
+		};
+		let result = f();
+		if switch {
+			match current {
+				Some(previous) => previous.make_current(),
+				None => gdk::GLContext::clear_current(),
+			}
+		}
+		result
+	}
+
+	/// Makes the owning context current, until dropped
+	pub fn enter(&mut self) {
+		if let Some(ref context) = self.context {
+			let current = gdk::GLContext::get_current();
+			if current.as_ref() != Some(context) {
+				context.make_current();
+				self.previous = Some(current);
+			}
+		}
+	}
+}
+
+impl Drop for OwnerContext {
+	fn drop(&mut self) {
+		if cfg!(debug_assertions) {
+			let leaked = live_objects(&self.context).since(&self.baseline);
+			if !leaked.is_empty() {
+				eprintln!(
+					"gfx_gtk: Gl objects still alive after dropping the render context: {:?}",
+					leaked
+				);
+			}
+		}
+		match self.previous.take() {
+			Some(Some(previous)) => previous.make_current(),
+			Some(None) => gdk::GLContext::clear_current(),
+			None => {}
+		}
+	}
+}
@@ -0,0 +1,237 @@
+//! Background uploads of GPU resources. A [ResourceLoader] owns a Gl context sharing its objects
+//! with the GlArea ones, made current on a worker thread running its own gfx device and factory.
+//! Meshes and textures are created there, and their handles handed back to the render callback
+//! once the Gl commands filling them are complete, so `render` never stalls on a large upload
+//!
+//! A Gdk Gl context can only be made current on another thread when the windowing system
+//! allows it: Wayland (EGL) does, X11 (GLX) doesn't unless Xlib has been initialized for
+//! multiple threads via `XInitThreads()` before Gtk, which Gdk doesn't do. The loader refuses
+//! to start on X11, see [ResourceLoader::with_context_unchecked()]
+
+use super::*;
+use gdk::{GLContextExt, WindowExt};
+use glib::ObjectExt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+/// How long the worker waits for a job before deleting the objects of dropped handles
+const CLEANUP_INTERVAL: Duration = Duration::from_millis(500);
+/// How long a single wait for the upload fence lasts, in nanoseconds
+const FENCE_TIMEOUT: u64 = 100_000_000;
+
+/// Creates resources with the factory of the worker
+type UploadJob = Box<dyn FnOnce(&mut GlFactory) + Send>;
+
+/// The Gl context of the worker. Only made current on the worker thread, and handed back to
+/// the thread dropping the loader once the worker is done with it
+struct WorkerContext(gdk::GLContext);
+
+unsafe impl Send for WorkerContext {}
+
+/// Fails on the windowing systems whose Gl contexts can't be current on the worker thread
+fn check_backend(display: &gdk::Display) -> Result<()> {
+	if display.get_type().name() == "GdkX11Display" {
+		Err(Error::GenericError(
+			"Background uploads are not supported on X11, GLX contexts can't be made current \
+			 on another thread"
+				.to_string(),
+		))
+	} else {
+		Ok(())
+	}
+}
+
+/// Waits until the Gl commands issued so far on the current context are complete
+fn wait_for_gpu() {
+	unsafe {
+		let fence = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+		while gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, FENCE_TIMEOUT)
+			== gl::TIMEOUT_EXPIRED
+		{}
+		gl::DeleteSync(fence);
+	}
+}
+
+/// Resources of type `T` being created by a [ResourceLoader]
+pub struct Upload<T> {
+	receiver: mpsc::Receiver<Result<T>>,
+}
+
+impl<T> Upload<T> {
+	/// Returns the resources once ready, `None` while the upload is still in progress.
+	/// Usually polled at the start of `render`. Returns `Some(_)` only once, after which the
+	/// upload should be dropped
+	pub fn try_take(&self) -> Option<Result<T>> {
+		match self.receiver.try_recv() {
+			Ok(result) => Some(result),
+			Err(mpsc::TryRecvError::Empty) => None,
+			Err(mpsc::TryRecvError::Disconnected) => Some(Err(Error::GenericError(
+				"The resource loader has stopped".to_string(),
+			))),
+		}
+	}
+
+	/// Blocks until the resources are ready, e.g. while loading the first frame
+	pub fn wait(self) -> Result<T> {
+		self.receiver.recv().unwrap_or_else(|_| {
+			Err(Error::GenericError(
+				"The resource loader has stopped".to_string(),
+			))
+		})
+	}
+}
+
+/// Creates GPU resources on a worker thread, with a Gl context shared with the GlArea ones.
+/// See [GfxGlArea::resource_loader()]
+///
+/// The Gl objects of the handles it returns are deleted by the worker once all the handles are
+/// dropped. The ones still alive when the loader is dropped are only released along with the
+/// Gl contexts sharing them
+pub struct ResourceLoader {
+	jobs: Option<mpsc::Sender<UploadJob>>,
+	/// set when the loader is dropped, the jobs still queued are skipped
+	cancelled: Arc<AtomicBool>,
+	worker: Option<thread::JoinHandle<WorkerContext>>,
+}
+
+impl ResourceLoader {
+	/// Creates a Gl context for `window`, sharing its objects with the contexts of any GlArea
+	/// in the same window, and starts the worker thread. Must be called on the Gtk main thread.
+	/// Fails on X11, see the [module documentation](index.html)
+	/// * `window` the Gdk window of a realized GlArea
+	pub fn new(window: &gdk::Window) -> Result<Self> {
+		check_backend(&window.get_display())?;
+		let context = window.create_gl_context()?;
+		context.realize()?;
+		unsafe { Self::with_context_unchecked(context) }
+	}
+
+	/// Starts the worker thread on an already realized Gl context, which is only used by the worker
+	/// from now on. Must be called on the Gtk main thread. Fails on X11, see the
+	/// [module documentation](index.html)
+	/// * `context` a Gdk Gl context sharing objects with the GlArea contexts
+	pub fn with_context(context: gdk::GLContext) -> Result<Self> {
+		check_backend(
+			&context
+				.get_display()
+				.ok_or_else(|| Error::GenericError("The Gl context has no display".to_string()))?,
+		)?;
+		unsafe { Self::with_context_unchecked(context) }
+	}
+
+	/// Starts the worker thread on an already realized Gl context, on any windowing system.
+	/// Must be called on the Gtk main thread
+	/// * `context` a Gdk Gl context sharing objects with the GlArea contexts
+	///
+	/// # Safety
+	/// The context must be allowed to be current on the worker thread while the main thread
+	/// renders, e.g. on X11 `XInitThreads()` must have been called before initializing Gtk
+	pub unsafe fn with_context_unchecked(context: gdk::GLContext) -> Result<Self> {
+		let (jobs, receiver) = mpsc::channel::<UploadJob>();
+		let cancelled = Arc::new(AtomicBool::new(false));
+		let context = WorkerContext(context);
+		let worker = thread::Builder::new()
+			.name("gfx-gtk-loader".to_string())
+			.spawn({
+				let cancelled = cancelled.clone();
+				move || {
+					use gfx::Device;
+
+					context.0.make_current();
+					let (mut device, mut factory) = gfx_device_gl::create(epoxy::get_proc_addr);
+					loop {
+						match receiver.recv_timeout(CLEANUP_INTERVAL) {
+							// dropping a skipped job reports the loader as stopped to its upload
+							Ok(job) => {
+								if !cancelled.load(Ordering::Acquire) {
+									job(&mut factory)
+								}
+							}
+							Err(mpsc::RecvTimeoutError::Timeout) => {}
+							Err(mpsc::RecvTimeoutError::Disconnected) => break,
+						}
+						// deletes the Gl objects whose handles have all been dropped
+						device.cleanup();
+					}
+					// the safe binding asserts it is called on the main thread
+					gdk_sys::gdk_gl_context_clear_current();
+					context
+				}
+			})?;
+		Ok(ResourceLoader {
+			jobs: Some(jobs),
+			cancelled,
+			worker: Some(worker),
+		})
+	}
+
+	/// Queues the creation of some resources on the worker thread
+	/// * `upload` invoked on the worker thread with its factory, to create buffers, textures
+	/// and pipeline states. Their handles are returned once the Gl commands filling them are
+	/// complete, and can be used by the render callback from then on
+	pub fn upload<T, F>(&self, upload: F) -> Upload<T>
+	where
+		T: Send + 'static,
+		F: FnOnce(&mut GlFactory) -> Result<T> + Send + 'static,
+	{
+		let (sender, receiver) = mpsc::channel();
+		let job: UploadJob = Box::new(move |factory| {
+			let result = upload(factory);
+			wait_for_gpu();
+			// the upload may have been dropped in the meantime
+			sender.send(result).ok();
+		});
+		if let Some(ref jobs) = self.jobs {
+			// if the worker is gone, the upload reports it
+			jobs.send(job).ok();
+		}
+		Upload { receiver }
+	}
+
+	/// Cancels the pending uploads, which report the loader as stopped, waits for the one in
+	/// progress if any, stops the worker thread and releases its Gl context on the current thread.
+	/// Fails if the worker thread has panicked
+	fn stop(&mut self) -> thread::Result<()> {
+		self.cancelled.store(true, Ordering::Release);
+		self.jobs = None;
+		match self.worker.take() {
+			Some(worker) => worker.join().map(|context| drop(context.0)),
+			None => Ok(()),
+		}
+	}
+}
+
+impl Drop for ResourceLoader {
+	/// Cancels the pending uploads and stops the worker thread
+	fn drop(&mut self) {
+		self.stop().ok();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use gtk::prelude::*;
+
+	#[test]
+	fn the_worker_stops_cleanly() {
+		// needs a display with Gl support, skipped otherwise
+		if gtk::init().is_err() {
+			return;
+		}
+		let window = gtk::OffscreenWindow::new();
+		window.show();
+		let context = match window.get_window().map(|w| w.create_gl_context()) {
+			Some(Ok(context)) => context,
+			_ => return,
+		};
+		if context.realize().is_err() {
+			return;
+		}
+		// the main thread doesn't use Gl meanwhile, which makes the context safe to share on X11
+		let mut loader = unsafe { ResourceLoader::with_context_unchecked(context) }.unwrap();
+		assert!(loader.stop().is_ok());
+	}
+}
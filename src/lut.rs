@@ -0,0 +1,348 @@
+//! 3D color lookup tables for color grading in the postprocessing stage, including
+//! a loader for the Adobe/Resolve `.cube` file format
+
+use super::*;
+use std::fs;
+use std::io::Read;
+
+/// Largest 3D LUT edge accepted by the loader
+pub const MAX_LUT_SIZE: usize = 256;
+
+#[derive(Clone, Debug, PartialEq)]
+/// A 3D color lookup table held in memory
+pub struct CubeLut {
+	/// Optional title, from the `TITLE` keyword
+	pub title: Option<String>,
+	/// Number of entries along each edge of the cube
+	pub size: usize,
+	/// Input value mapped onto the first entry of each axis
+	pub domain_min: [f32; 3],
+	/// Input value mapped onto the last entry of each axis
+	pub domain_max: [f32; 3],
+	/// `size`³ output colors, red varying fastest, then green, then blue
+	pub table: Vec<[f32; 3]>,
+}
+
+impl CubeLut {
+	/// Creates a LUT which maps every color onto itself
+	/// * `size` number of entries along each edge of the cube, at least 2
+	pub fn identity(size: usize) -> Self {
+		let size = size.max(2).min(MAX_LUT_SIZE);
+		let scale = 1. / (size - 1) as f32;
+		let mut table = Vec::with_capacity(size * size * size);
+		for b in 0..size {
+			for g in 0..size {
+				for r in 0..size {
+					table.push([r as f32 * scale, g as f32 * scale, b as f32 * scale]);
+				}
+			}
+		}
+		CubeLut {
+			title: None,
+			size,
+			domain_min: [0., 0., 0.],
+			domain_max: [1., 1., 1.],
+			table,
+		}
+	}
+
+	/// Parses the content of a `.cube` file. Only 3D tables are supported
+	/// * `source` the text content of the file
+	pub fn parse(source: &str) -> Result<Self> {
+		fn parse_floats(line_number: usize, values: &[&str]) -> Result<Vec<f32>> {
+			values
+				.iter()
+				.map(|v| {
+					v.parse::<f32>().map_err(|e| {
+						Error::GenericError(format!(
+							"line {}: invalid number {}: {}",
+							line_number, v, e
+						))
+					})
+				})
+				.collect()
+		}
+
+		fn parse_triple(line_number: usize, values: &[&str]) -> Result<[f32; 3]> {
+			match parse_floats(line_number, values)?.as_slice() {
+				[r, g, b] => Ok([*r, *g, *b]),
+				_ => Err(Error::GenericError(format!(
+					"line {}: expected 3 values, found {}",
+					line_number,
+					values.len()
+				))),
+			}
+		}
+
+		let mut title = None;
+		let mut size = None;
+		let mut domain_min = [0., 0., 0.];
+		let mut domain_max = [1., 1., 1.];
+		let mut table = Vec::new();
+
+		for (index, line) in source.lines().enumerate() {
+			let line_number = index + 1;
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			let tokens: Vec<&str> = line.split_whitespace().collect();
+			match tokens[0] {
+				"TITLE" => {
+					title = Some(line["TITLE".len()..].trim().trim_matches('"').to_string());
+				}
+				"LUT_3D_SIZE" => {
+					let n = tokens
+						.get(1)
+						.and_then(|n| n.parse::<usize>().ok())
+						.filter(|n| *n >= 2 && *n <= MAX_LUT_SIZE)
+						.ok_or_else(|| {
+							Error::GenericError(format!(
+								"line {}: invalid LUT_3D_SIZE",
+								line_number
+							))
+						})?;
+					size = Some(n);
+				}
+				"LUT_1D_SIZE" | "LUT_1D_INPUT_RANGE" => {
+					return Err(Error::GenericError(format!(
+						"line {}: 1D LUTs are not supported",
+						line_number
+					)));
+				}
+				"DOMAIN_MIN" => domain_min = parse_triple(line_number, &tokens[1..])?,
+				"DOMAIN_MAX" => domain_max = parse_triple(line_number, &tokens[1..])?,
+				"LUT_3D_INPUT_RANGE" => match parse_floats(line_number, &tokens[1..])?.as_slice() {
+					[min, max] => {
+						domain_min = [*min; 3];
+						domain_max = [*max; 3];
+					}
+					_ => {
+						return Err(Error::GenericError(format!(
+							"line {}: expected 2 values",
+							line_number
+						)))
+					}
+				},
+				keyword if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+					// unknown keywords are vendor extensions, safe to ignore
+				}
+				_ => table.push(parse_triple(line_number, &tokens)?),
+			}
+		}
+
+		let size = size.ok_or_else(|| Error::GenericError("missing LUT_3D_SIZE".to_string()))?;
+		if table.len() != size * size * size {
+			return Err(Error::GenericError(format!(
+				"expected {} table entries, found {}",
+				size * size * size,
+				table.len()
+			)));
+		}
+		if (0..3).any(|i| domain_max[i] <= domain_min[i]) {
+			return Err(Error::GenericError("empty LUT domain".to_string()));
+		}
+
+		Ok(CubeLut {
+			title,
+			size,
+			domain_min,
+			domain_max,
+			table,
+		})
+	}
+
+	/// Loads and parses a `.cube` file
+	/// * `path` location of the file
+	pub fn load(path: &Path) -> Result<Self> {
+		let mut source = String::new();
+		fs::File::open(path)?.read_to_string(&mut source)?;
+		Self::parse(&source)
+	}
+
+	/// Uploads the table into a 3D texture
+	/// * `factory` the factory attached to the current Gl context
+	pub fn create_texture<R, F>(&self, factory: &mut F) -> Result<LutTexture<R>>
+	where
+		R: gfx::Resources,
+		F: gfx::Factory<R>,
+	{
+		let texels: Vec<[u16; 4]> = self
+			.table
+			.iter()
+			.map(|c| [to_half(c[0]), to_half(c[1]), to_half(c[2]), to_half(1.)])
+			.collect();
+		let size = self.size as gfx::texture::Size;
+		let (_, view) = factory.create_texture_immutable_u8::<formats::LutFormat>(
+			gfx::texture::Kind::D3(size, size, size),
+			gfx::texture::Mipmap::Provided,
+			&[gfx::memory::cast_slice(&texels)],
+		)?;
+		Ok(LutTexture {
+			view,
+			size: self.size,
+			domain_min: self.domain_min,
+			domain_max: self.domain_max,
+		})
+	}
+}
+
+/// Converts to the nearest half precision float, as stored in a [formats::LutFormat] texel.
+/// Values beyond the half precision range are clamped to its largest finite value
+fn to_half(value: f32) -> u16 {
+	let bits = value.to_bits();
+	let sign = ((bits >> 16) & 0x8000) as u16;
+	let abs = value.abs();
+	if abs.is_nan() {
+		sign | 0x7e00
+	} else if abs >= 65504. {
+		sign | 0x7bff
+	} else if abs < 6.103_515_6e-5 {
+		// subnormal, in units of 2^-24. Rounding up to 1024 yields the smallest normal value
+		sign | (abs * 16_777_216.).round() as u16
+	} else {
+		// rebias the exponent from 127 to 15, then round the mantissa to nearest even
+		let rebiased = (bits & 0x7fff_ffff) - ((127 - 15) << 23);
+		let (half, rest) = (rebiased >> 13, rebiased & 0x1fff);
+		let half = if rest > 0x1000 || (rest == 0x1000 && half & 1 == 1) {
+			half + 1
+		} else {
+			half
+		};
+		sign | half as u16
+	}
+}
+
+#[derive(Clone)]
+/// A [CubeLut] uploaded into a 3D texture, ready for sampling in the postprocessing stage
+pub struct LutTexture<R>
+where
+	R: gfx::Resources,
+{
+	/// Shader view of the 3D texture
+	pub view: gfx::handle::ShaderResourceView<R, formats::LutView>,
+	/// Number of entries along each edge of the cube
+	pub size: usize,
+	/// Input value mapped onto the first entry of each axis
+	pub domain_min: [f32; 3],
+	/// Input value mapped onto the last entry of each axis
+	pub domain_max: [f32; 3],
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SMALL_CUBE: &str = r#"
+# a 2x2x2 table swapping red and blue
+TITLE "swap"
+LUT_3D_SIZE 2
+DOMAIN_MIN 0.0 0.0 0.0
+DOMAIN_MAX 1.0 1.0 1.0
+
+0.0 0.0 0.0
+0.0 0.0 1.0
+0.0 1.0 0.0
+0.0 1.0 1.0
+1.0 0.0 0.0
+1.0 0.0 1.0
+1.0 1.0 0.0
+1.0 1.0 1.0
+"#;
+
+	fn parse_error(source: &str) -> String {
+		match CubeLut::parse(source) {
+			Err(Error::GenericError(message)) => message,
+			other => panic!("expected an error, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn parses_small_cube() {
+		let lut = CubeLut::parse(SMALL_CUBE).unwrap();
+		assert_eq!(lut.title, Some("swap".to_string()));
+		assert_eq!(lut.size, 2);
+		assert_eq!(lut.domain_min, [0., 0., 0.]);
+		assert_eq!(lut.domain_max, [1., 1., 1.]);
+		assert_eq!(lut.table.len(), 8);
+		// red varies fastest
+		assert_eq!(lut.table[1], [0., 0., 1.]);
+		assert_eq!(lut.table[4], [1., 0., 0.]);
+	}
+
+	#[test]
+	fn parses_input_range_and_vendor_keywords() {
+		let source = "LUT_3D_SIZE 2\nLUT_3D_INPUT_RANGE -1 2\nVENDOR_THING 42\n".to_string()
+			+ &"0 0 0\n".repeat(8);
+		let lut = CubeLut::parse(&source).unwrap();
+		assert_eq!(lut.title, None);
+		assert_eq!(lut.domain_min, [-1.; 3]);
+		assert_eq!(lut.domain_max, [2.; 3]);
+	}
+
+	#[test]
+	fn identity_maps_corners_onto_themselves() {
+		let lut = CubeLut::identity(3);
+		assert_eq!(lut.table.len(), 27);
+		assert_eq!(lut.table[0], [0., 0., 0.]);
+		assert_eq!(lut.table[2], [1., 0., 0.]);
+		assert_eq!(lut.table[26], [1., 1., 1.]);
+		assert_eq!(CubeLut::identity(0).size, 2);
+	}
+
+	#[test]
+	fn converts_to_half_precision() {
+		assert_eq!(to_half(0.), 0x0000);
+		assert_eq!(to_half(-0.), 0x8000);
+		assert_eq!(to_half(1.), 0x3c00);
+		assert_eq!(to_half(-2.), 0xc000);
+		assert_eq!(to_half(0.5), 0x3800);
+		assert_eq!(to_half(65504.), 0x7bff);
+		assert_eq!(to_half(1e9), 0x7bff);
+		assert_eq!(to_half(6.103_515_6e-5), 0x0400);
+		assert_eq!(to_half(5.960_464_5e-8), 0x0001);
+		assert_eq!(to_half(f32::NAN) & 0x7fff, 0x7e00);
+		// 1 + 2^-11 is halfway between 1 and the next half, rounds to even
+		assert_eq!(to_half(1. + 1. / 2048.), 0x3c00);
+		assert_eq!(to_half(1. + 3. / 2048.), 0x3c02);
+		// 1/3 rounds to the nearest of 0x3555 and 0x3556
+		assert_eq!(to_half(1. / 3.), 0x3555);
+	}
+
+	#[test]
+	fn rejects_missing_size() {
+		assert!(parse_error("0 0 0\n").contains("missing LUT_3D_SIZE"));
+	}
+
+	#[test]
+	fn rejects_invalid_size() {
+		assert!(parse_error("LUT_3D_SIZE 1\n").contains("line 1: invalid LUT_3D_SIZE"));
+		assert!(parse_error("LUT_3D_SIZE 257\n").contains("invalid LUT_3D_SIZE"));
+		assert!(parse_error("LUT_3D_SIZE two\n").contains("invalid LUT_3D_SIZE"));
+	}
+
+	#[test]
+	fn rejects_1d_tables() {
+		assert!(parse_error("LUT_1D_SIZE 16\n").contains("1D LUTs are not supported"));
+	}
+
+	#[test]
+	fn rejects_wrong_entry_count() {
+		let source = "LUT_3D_SIZE 2\n".to_string() + &"0 0 0\n".repeat(7);
+		assert!(parse_error(&source).contains("expected 8 table entries, found 7"));
+	}
+
+	#[test]
+	fn rejects_malformed_entries() {
+		assert!(parse_error("LUT_3D_SIZE 2\n0 0\n").contains("line 2: expected 3 values, found 2"));
+		assert!(parse_error("LUT_3D_SIZE 2\n0 x 0\n").contains("line 2: invalid number x"));
+		assert!(parse_error("LUT_3D_INPUT_RANGE 0\n").contains("line 1: expected 2 values"));
+	}
+
+	#[test]
+	fn rejects_empty_domain() {
+		let source = "LUT_3D_SIZE 2\nDOMAIN_MIN 0 0 1\nDOMAIN_MAX 1 1 1\n".to_string()
+			+ &"0 0 0\n".repeat(8);
+		assert!(parse_error(&source).contains("empty LUT domain"));
+	}
+}
@@ -0,0 +1,129 @@
+//! Selection outline effect, drawn in the postprocessing stage around the pixels
+//! marked in a mask target written by the render callback
+
+use super::*;
+
+// Outline gfx pipeline definitions
+gfx_pipeline!(outline {
+		vbuf: gfx::VertexBuffer<BlitVertex> = (),
+		mask: gfx::TextureSampler<formats::OutlineMaskView> = "t_Mask",
+		color: gfx::Global<[f32; 4]> = "u_OutlineColor",
+		width: gfx::Global<f32> = "u_OutlineWidth",
+		dst: gfx::BlendTarget<formats::GtkTargetColorFormat> = ("o_Color", gfx::state::ColorMask::all(), gfx::preset::blend::ALPHA),
+	}
+);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Appearance of the selection outline
+pub struct OutlineSettings {
+	/// Color of the outline, already in display (sRGB) space. Alpha is used for blending
+	pub color: Rgba,
+	/// Width of the outline in pixels of the GlArea client area
+	pub width: f32,
+}
+
+impl Default for OutlineSettings {
+	fn default() -> Self {
+		OutlineSettings {
+			color: [1., 0.5, 0., 1.],
+			width: 2.,
+		}
+	}
+}
+
+/// A container for the state needed to draw the selection outline: the mask target
+/// the render callback writes into, and the edge detection PSO reading from it
+pub struct OutlineContext<D>
+where
+	D: gfx::Device,
+{
+	/// current appearance of the outline
+	pub settings: OutlineSettings,
+	/// pipeline state object with the edge detection shaders
+	pub pso: gfx::PipelineState<D::Resources, outline::Meta>,
+	/// Off-screen texture view of the mask target, source of the outline pass
+	pub mask_source: gfx::handle::ShaderResourceView<D::Resources, formats::OutlineMaskView>,
+	/// Mask target, written by [GlRenderCallback::render_outline_mask]
+	pub mask_target: gfx::handle::RenderTargetView<D::Resources, formats::OutlineMaskFormat>,
+}
+
+impl OutlineContext<GlDevice> {
+	/// Creates the outline PSO and a mask target matching the size and AA mode of the main render target
+	/// * `factory` the factory attached to the current Gl context
+	/// * `viewport` size and AA mode of the main render target
+	/// * `settings` initial appearance of the outline
+	pub fn new(
+		factory: &mut GlFactory,
+		viewport: &Viewport,
+		settings: OutlineSettings,
+	) -> Result<Self> {
+		use self::FactoryExt as LocalFactory;
+		use gfx::traits::FactoryExt;
+
+		let pixel_shader_code = match viewport.aa {
+			gfx::texture::AaMode::Multi(_) => shaders::OUTLINE_PIXEL_SHADER_MSAA.as_bytes(),
+			_ => shaders::OUTLINE_PIXEL_SHADER.as_bytes(),
+		};
+		let pso = factory.create_pipeline_simple(
+			shaders::POST_VERTEX_SHADER.as_bytes(),
+			pixel_shader_code,
+			outline::new(),
+		)?;
+		let (_, mask_source, mask_target) = factory.create_gtk_compatible_render_target(
+			viewport.aa,
+			viewport.width as u16,
+			viewport.height as u16,
+		)?;
+		Ok(OutlineContext {
+			settings,
+			pso,
+			mask_source,
+			mask_target,
+		})
+	}
+
+	/// Re-allocates the mask target to match the new size of the main render target
+	/// * `factory` the factory attached to the current Gl context
+	/// * `viewport` size and AA mode of the main render target after resizing
+	pub fn resize(&mut self, factory: &mut GlFactory, viewport: &Viewport) -> Result<()> {
+		use self::FactoryExt as LocalFactory;
+
+		let (_, mask_source, mask_target) = factory.create_gtk_compatible_render_target(
+			viewport.aa,
+			viewport.width as u16,
+			viewport.height as u16,
+		)?;
+		self.mask_source = mask_source;
+		self.mask_target = mask_target;
+		Ok(())
+	}
+
+	/// Clears the mask target, ready for the render callback to mark the selected pixels
+	pub fn clear_mask(&self, encoder: &mut GlEncoder) {
+		encoder.clear(&self.mask_target, [0., 0., 0., 0.]);
+	}
+
+	/// Blends the outline onto the postprocessing target. The mask is sampled at full
+	/// resolution (the first sample is used if the mask is multisampled)
+	pub fn draw(
+		&self,
+		encoder: &mut GlEncoder,
+		postprocess_context: &GlPostprocessContext,
+		post_target: &GlFrameBuffer<formats::GtkTargetColorFormat>,
+	) {
+		encoder.draw(
+			&postprocess_context.ibuf,
+			&self.pso,
+			&outline::Data {
+				vbuf: postprocess_context.vbuf.clone(),
+				mask: (
+					self.mask_source.clone(),
+					postprocess_context.sampler.clone(),
+				),
+				color: self.settings.color,
+				width: self.settings.width,
+				dst: post_target.clone(),
+			},
+		);
+	}
+}
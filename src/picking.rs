@@ -0,0 +1,244 @@
+//! GPU object picking: the render callback writes an object ID for every pixel into a
+//! dedicated integer color attachment, which is read back on request
+
+use super::*;
+use gfx::memory::Typed;
+
+// MSAA resolution of the ID attachment, picks the first sample as IDs can't be averaged
+gfx_pipeline!(picking_resolve {
+		vbuf: gfx::VertexBuffer<BlitVertex> = (),
+		src: gfx::TextureSampler<formats::PickIdView> = "t_Ids",
+		dst: gfx::RenderTarget<formats::PickIdFormat> = "o_Id",
+	}
+);
+
+/// Object identifier written by the render callback. `0` is reserved for the background
+pub type PickId = u32;
+
+/// Single-sampled copy of a multisampled ID attachment
+struct PickingResolve<R>
+where
+	R: gfx::Resources,
+{
+	pso: gfx::PipelineState<R, picking_resolve::Meta>,
+	sampler: gfx::handle::Sampler<R>,
+	texture: gfx::handle::Texture<R, <formats::PickIdFormat as gfx::format::Formatted>::Surface>,
+	target: gfx::handle::RenderTargetView<R, formats::PickIdFormat>,
+}
+
+/// Tracks the ID attachment and the resources needed to read it back
+pub struct PickingContext<D>
+where
+	D: gfx::Device,
+{
+	/// index of the ID attachment in the [ColorAttachments] of the render context
+	attachment: usize,
+	/// only needed if the ID attachment is multisampled
+	resolve: Option<PickingResolve<D::Resources>>,
+}
+
+/// The download buffer of a [PickQuery] and the fence signalled once it is filled
+struct PickReadback {
+	buffer: gfx::handle::Buffer<GlResources, PickId>,
+	fence: gl::types::GLsync,
+}
+
+impl Drop for PickReadback {
+	fn drop(&mut self) {
+		unsafe {
+			gl::DeleteSync(self.fence);
+		}
+	}
+}
+
+/// A pending read back of object IDs, see [RenderContext::pick_rect_async()]
+pub struct PickQuery {
+	/// `None` if the rectangle is empty, in which case there's nothing to read back
+	readback: Option<PickReadback>,
+}
+
+impl PickQuery {
+	/// `true` once the GPU has completed the copy, and the result can be collected without stalling
+	pub fn is_ready(&self) -> bool {
+		match self.readback {
+			Some(ref readback) => {
+				let status = unsafe { gl::ClientWaitSync(readback.fence, 0, 0) };
+				status == gl::ALREADY_SIGNALED || status == gl::CONDITION_SATISFIED
+			}
+			None => true,
+		}
+	}
+}
+
+impl PickingContext<GlDevice> {
+	/// Declares the ID attachment and creates the resources to resolve it if multisampled
+	/// * `factory` the factory attached to the current Gl context
+	/// * `color_attachments` where the ID attachment is added
+	/// * `viewport` size and AA mode of the main render target
+	pub fn new(
+		factory: &mut GlFactory,
+		color_attachments: &mut GlColorAttachments,
+		viewport: &Viewport,
+	) -> Result<Self> {
+		let attachment = color_attachments.add(
+			factory,
+			viewport,
+			<formats::PickIdFormat as gfx::format::Formatted>::get_format(),
+		)?;
+		let resolve = Self::create_resolve(factory, viewport)?;
+		Ok(PickingContext {
+			attachment,
+			resolve,
+		})
+	}
+
+	fn create_resolve(
+		factory: &mut GlFactory,
+		viewport: &Viewport,
+	) -> Result<Option<PickingResolve<GlResources>>> {
+		use gfx::traits::FactoryExt;
+
+		if let gfx::texture::AaMode::Single = viewport.aa {
+			return Ok(None);
+		}
+		let pso = factory.create_pipeline_simple(
+			shaders::POST_VERTEX_SHADER.as_bytes(),
+			shaders::PICKING_RESOLVE_PIXEL_SHADER.as_bytes(),
+			picking_resolve::new(),
+		)?;
+		let texture = factory.create_texture(
+			gfx::texture::Kind::D2(
+				viewport.width as gfx::texture::Size,
+				viewport.height as gfx::texture::Size,
+				gfx::texture::AaMode::Single,
+			),
+			1,
+			gfx::memory::Bind::RENDER_TARGET | gfx::memory::Bind::TRANSFER_SRC,
+			gfx::memory::Usage::Data,
+			Some(<formats::PickIdFormat as gfx::format::Formatted>::get_format().1),
+		)?;
+		let target = factory.view_texture_as_render_target(&texture, 0, None)?;
+		let sampler = factory.create_sampler(gfx::texture::SamplerInfo::new(
+			gfx::texture::FilterMethod::Scale,
+			gfx::texture::WrapMode::Clamp,
+		));
+		Ok(Some(PickingResolve {
+			pso,
+			sampler,
+			texture,
+			target,
+		}))
+	}
+
+	/// Index of the ID attachment in the [ColorAttachments] of the render context
+	pub fn attachment(&self) -> usize {
+		self.attachment
+	}
+
+	/// Re-allocates the resolve target to match a new viewport. The ID attachment itself
+	/// is resized along with the other attachments
+	pub fn resize(&mut self, factory: &mut GlFactory, viewport: &Viewport) -> Result<()> {
+		self.resolve = Self::create_resolve(factory, viewport)?;
+		Ok(())
+	}
+
+	/// Clears the ID attachment to the background ID
+	pub fn clear(&self, encoder: &mut GlEncoder, color_attachments: &GlColorAttachments) {
+		if let Some(ids) = color_attachments.target::<formats::PickIdFormat>(self.attachment) {
+			encoder.clear(&ids, 0);
+		}
+	}
+
+	/// Queues the copy of the IDs within `rect` (in framebuffer pixels) into a download buffer,
+	/// resolving the ID attachment first if needed, and submits the commands
+	/// * `gfx_context` Gfx device, factory, encoder attached to the current Gl context
+	/// * `postprocess_context` provides the full screen geometry for the resolve pass
+	/// * `color_attachments` the attachments holding the IDs
+	/// * `rect` `(x, y, width, height)` of the region to read, bottom-left origin, already clipped.
+	/// If empty, the query is ready straight away with no IDs
+	pub fn request(
+		&self,
+		gfx_context: &mut GlGfxContext,
+		postprocess_context: &GlPostprocessContext,
+		color_attachments: &GlColorAttachments,
+		rect: (u16, u16, u16, u16),
+	) -> Result<PickQuery> {
+		use gfx::traits::FactoryExt;
+
+		let (x, y, width, height) = rect;
+		if width == 0 || height == 0 {
+			return Ok(PickQuery { readback: None });
+		}
+		let attachment = color_attachments
+			.get(self.attachment)
+			.ok_or_else(|| Error::GenericError("picking attachment not found".to_string()))?;
+		let source = match self.resolve {
+			Some(ref resolve) => {
+				let ids = attachment
+					.source::<formats::PickIdFormat>()
+					.ok_or_else(|| Error::GenericError("invalid picking attachment".to_string()))?;
+				gfx_context.encoder.draw(
+					&postprocess_context.ibuf,
+					&resolve.pso,
+					&picking_resolve::Data {
+						vbuf: postprocess_context.vbuf.clone(),
+						src: (ids, resolve.sampler.clone()),
+						dst: resolve.target.clone(),
+					},
+				);
+				resolve.texture.raw().clone()
+			}
+			None => attachment.raw_texture().clone(),
+		};
+		let buffer = gfx_context
+			.factory
+			.create_download_buffer::<PickId>(width as usize * height as usize)?;
+		gfx_context
+			.encoder
+			.copy_texture_to_buffer_raw(
+				&source,
+				None,
+				gfx::texture::RawImageInfo {
+					xoffset: x,
+					yoffset: y,
+					zoffset: 0,
+					width,
+					height,
+					depth: 1,
+					format: <formats::PickIdFormat as gfx::format::Formatted>::get_format(),
+					mipmap: 0,
+				},
+				buffer.raw(),
+				0,
+			)
+			.map_err(|e| Error::GenericError(format!("{:?}", e)))?;
+		gfx_context.flush();
+		let fence = unsafe {
+			let fence = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+			// submits the fence, otherwise it may not signal until some later flush
+			gl::Flush();
+			fence
+		};
+		Ok(PickQuery {
+			readback: Some(PickReadback { buffer, fence }),
+		})
+	}
+
+	/// Reads the IDs copied by `query`, blocking until the copy has completed.
+	/// Returns the distinct non-background IDs, sorted
+	pub fn collect(factory: &mut GlFactory, query: &PickQuery) -> Result<Vec<PickId>> {
+		use gfx::Factory;
+
+		let readback = match query.readback {
+			Some(ref readback) => readback,
+			None => return Ok(Vec::new()),
+		};
+		let reader = factory
+			.read_mapping(&readback.buffer)
+			.map_err(|e| Error::GenericError(format!("{:?}", e)))?;
+		let mut ids: Vec<PickId> = reader.iter().cloned().filter(|id| *id != 0).collect();
+		ids.sort();
+		ids.dedup();
+		Ok(ids)
+	}
+}
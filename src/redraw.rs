@@ -0,0 +1,116 @@
+//! Redraw requests from worker threads. The render context, the render callback and the GlArea
+//! must stay on the Gtk main thread, so a [RedrawHandle] only carries an identifier: requests are
+//! forwarded to the main loop via `glib::idle_add()`, and at most one is in flight at any time
+
+use super::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+
+/// Source of the identifiers of the redraw targets
+static NEXT_TARGET_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A redraw target registered on the main thread, forgotten once all its handles are gone
+struct RedrawTarget {
+	shared: Weak<RedrawShared>,
+	redraw: Box<dyn Fn()>,
+}
+
+thread_local! {
+	/// Redraw targets of the main thread, by identifier
+	static REDRAW_TARGETS: RefCell<HashMap<usize, RedrawTarget>> = RefCell::new(HashMap::new());
+}
+
+/// State shared by all the clones of a [RedrawHandle]
+struct RedrawShared {
+	id: usize,
+	/// set while a request is waiting for the main loop, further requests are coalesced into it
+	queued: AtomicBool,
+}
+
+#[derive(Clone)]
+/// A `Send + Clone` handle that any thread can use to request a redraw of a GlArea, see
+/// [GfxGlArea::redraw_handle()]
+pub struct RedrawHandle {
+	shared: Arc<RedrawShared>,
+}
+
+impl RedrawHandle {
+	/// Creates a handle invoking `redraw` on the current thread whenever requested. Must be called
+	/// on the Gtk main thread, the one running the default `glib::MainContext`, panics otherwise
+	/// * `redraw` queues the render, e.g. `GfxGlArea::queue_render()`
+	pub fn new<F>(redraw: F) -> Self
+	where
+		F: Fn() + 'static,
+	{
+		// the idle callbacks run where the main context is iterated, which may not have started yet
+		let main_context = glib::MainContext::default();
+		let owner = main_context.is_owner() || {
+			let acquired = main_context.acquire();
+			if acquired {
+				main_context.release();
+			}
+			acquired
+		};
+		assert!(
+			owner,
+			"RedrawHandle::new() must be called on the thread running the default glib::MainContext"
+		);
+		let shared = Arc::new(RedrawShared {
+			id: NEXT_TARGET_ID.fetch_add(1, Ordering::Relaxed),
+			queued: AtomicBool::new(false),
+		});
+		REDRAW_TARGETS.with(|targets| {
+			let mut targets = targets.borrow_mut();
+			targets.retain(|_, target| target.shared.upgrade().is_some());
+			targets.insert(
+				shared.id,
+				RedrawTarget {
+					shared: Arc::downgrade(&shared),
+					redraw: Box::new(redraw),
+				},
+			);
+		});
+		RedrawHandle { shared }
+	}
+
+	/// Requests a redraw from any thread. Returns immediately, the render is queued on the main
+	/// thread the next time its main loop is idle. Requests made in the meantime are coalesced
+	pub fn request_redraw(&self) {
+		if self.shared.queued.swap(true, Ordering::AcqRel) {
+			return;
+		}
+		let shared = self.shared.clone();
+		glib::idle_add(move || {
+			// requests from now on need a new idle callback
+			shared.queued.store(false, Ordering::Release);
+			REDRAW_TARGETS.with(|targets| {
+				if let Some(target) = targets.borrow().get(&shared.id) {
+					(target.redraw)();
+				}
+			});
+			glib::Continue(false)
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::thread;
+
+	#[test]
+	fn new_must_be_called_where_the_main_context_runs() {
+		let main_context = glib::MainContext::default();
+		assert!(main_context.acquire());
+		let owner = RedrawHandle::new(|| {});
+		let other_thread = thread::spawn(|| {
+			RedrawHandle::new(|| {});
+		})
+		.join();
+		main_context.release();
+		assert!(other_thread.is_err());
+		drop(owner);
+	}
+}
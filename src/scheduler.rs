@@ -0,0 +1,183 @@
+//! Render on demand: redraw requests from any number of sources are coalesced into a single
+//! `queue_render` per frame, and a few extra frames can be requested while something settles,
+//! e.g. damped camera motion or progressive accumulation. Nothing is rendered otherwise
+
+#[derive(Clone, Debug, Default)]
+/// Tracks whether a frame is already scheduled, and how many more frames are needed without
+/// being asked, see [RenderContext::scheduler()]
+pub struct RenderScheduler {
+	/// a render has been queued and the frame hasn't started yet
+	pending: bool,
+	/// frames still to be rendered after the current one
+	settle_frames: u32,
+	/// redraws requested so far
+	requests: u64,
+	/// redraws requested while a render was already queued
+	coalesced: u64,
+	/// frames rendered so far
+	frames: u64,
+}
+
+impl RenderScheduler {
+	/// Creates an idle scheduler
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Marks the contents as changed. Returns `true` if the caller must queue a render, `false`
+	/// if one is already queued and the request has been merged into it
+	pub fn request(&mut self) -> bool {
+		self.requests += 1;
+		if self.pending {
+			self.coalesced += 1;
+			false
+		} else {
+			self.pending = true;
+			true
+		}
+	}
+
+	/// Keeps rendering for at least `frames` more frames after the current one, without any
+	/// further request. Shorter requests don't cut a longer one short
+	/// * `frames` number of extra frames, e.g. while a damped motion settles
+	pub fn settle(&mut self, frames: u32) {
+		self.settle_frames = self.settle_frames.max(frames);
+	}
+
+	/// Cancels the extra frames requested via [RenderScheduler::settle()]
+	pub fn cancel_settle(&mut self) {
+		self.settle_frames = 0;
+	}
+
+	/// Forgets the queued render, when it will never start, e.g. because the GlArea has been
+	/// unmapped. The next request queues a new one
+	pub fn cancel_pending(&mut self) {
+		self.pending = false;
+	}
+
+	/// Invoked when a frame starts, later requests queue a new frame
+	pub fn begin_frame(&mut self) {
+		self.pending = false;
+		self.frames += 1;
+	}
+
+	/// Invoked when a frame is complete. Returns `true` if the caller must queue a render
+	/// because the contents are still settling
+	pub fn end_frame(&mut self) -> bool {
+		if self.settle_frames > 0 {
+			self.settle_frames -= 1;
+			self.request()
+		} else {
+			false
+		}
+	}
+
+	/// `true` if a render is queued
+	pub fn is_pending(&self) -> bool {
+		self.pending
+	}
+
+	/// `true` if no render is queued and nothing is settling
+	pub fn is_idle(&self) -> bool {
+		!self.pending && self.settle_frames == 0
+	}
+
+	/// Number of extra frames still to be rendered
+	pub fn settle_frames(&self) -> u32 {
+		self.settle_frames
+	}
+
+	/// Number of redraws requested, including the coalesced ones
+	pub fn requests(&self) -> u64 {
+		self.requests
+	}
+
+	/// Number of redraws requested while a render was already queued, which didn't cause a frame
+	pub fn coalesced(&self) -> u64 {
+		self.coalesced
+	}
+
+	/// Number of frames rendered
+	pub fn frames(&self) -> u64 {
+		self.frames
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn requests_are_coalesced_until_the_frame_starts() {
+		let mut scheduler = RenderScheduler::new();
+		assert!(scheduler.is_idle());
+		assert!(scheduler.request());
+		assert!(!scheduler.request());
+		assert!(!scheduler.request());
+		assert!(scheduler.is_pending());
+		assert_eq!((scheduler.requests(), scheduler.coalesced()), (3, 2));
+		scheduler.begin_frame();
+		assert!(!scheduler.is_pending());
+		// requests made while rendering queue the next frame
+		assert!(scheduler.request());
+		assert!(!scheduler.end_frame());
+		scheduler.begin_frame();
+		assert!(!scheduler.end_frame());
+		assert!(scheduler.is_idle());
+		assert_eq!(scheduler.frames(), 2);
+	}
+
+	#[test]
+	fn settling_renders_the_extra_frames() {
+		let mut scheduler = RenderScheduler::new();
+		scheduler.settle(3);
+		scheduler.settle(2);
+		assert_eq!(scheduler.settle_frames(), 3);
+		assert!(!scheduler.is_idle());
+		assert!(scheduler.request());
+		let mut frames = 0;
+		while scheduler.is_pending() {
+			scheduler.begin_frame();
+			frames += 1;
+			scheduler.end_frame();
+		}
+		assert_eq!(frames, 4);
+		assert!(scheduler.is_idle());
+		assert_eq!(scheduler.frames(), 4);
+	}
+
+	#[test]
+	fn settling_frames_merge_with_requests() {
+		let mut scheduler = RenderScheduler::new();
+		scheduler.settle(1);
+		scheduler.request();
+		scheduler.begin_frame();
+		// a request during the frame has already queued the next one
+		assert!(scheduler.request());
+		assert!(!scheduler.end_frame());
+		assert_eq!(scheduler.settle_frames(), 0);
+		assert_eq!(scheduler.coalesced(), 1);
+	}
+
+	#[test]
+	fn cancel_settle_stops_after_the_current_frame() {
+		let mut scheduler = RenderScheduler::new();
+		scheduler.settle(10);
+		scheduler.request();
+		scheduler.begin_frame();
+		scheduler.cancel_settle();
+		assert!(!scheduler.end_frame());
+		assert!(scheduler.is_idle());
+	}
+
+	#[test]
+	fn cancel_pending_lets_the_next_request_queue_a_render() {
+		let mut scheduler = RenderScheduler::new();
+		assert!(scheduler.request());
+		assert!(!scheduler.request());
+		scheduler.cancel_pending();
+		assert!(scheduler.is_idle());
+		assert!(scheduler.request());
+		assert_eq!(scheduler.frames(), 0);
+	}
+}
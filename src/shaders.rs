@@ -1,5 +1,59 @@
 //! Predefined shaders used in postprocessing
- 
+
+use super::{OutputTransfer, PostprocessFilter};
+use gfx;
+use gfx_device_gl;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Shading language of the built-in postprocessing shaders, according to the API of the Gl context
+pub enum ShaderDialect {
+	/// GLSL 1.50 core, for desktop Gl 3.2 and later
+	Glsl150,
+	/// GLSL ES 3.00, for Gl ES 3.0 and later. Multisampled sources require GLSL ES 3.10 (Gl ES 3.1)
+	GlslEs300,
+}
+
+impl Default for ShaderDialect {
+	fn default() -> Self {
+		ShaderDialect::Glsl150
+	}
+}
+
+impl ShaderDialect {
+	/// Picks the dialect supported by the context a device has been created with
+	/// * `info` the information returned by `GlDevice::get_info()`
+	pub fn detect(info: &gfx_device_gl::Info) -> Self {
+		if info.version.is_embedded {
+			ShaderDialect::GlslEs300
+		} else {
+			ShaderDialect::Glsl150
+		}
+	}
+
+	/// Returns `true` for the Gl ES dialects
+	pub fn is_es(self) -> bool {
+		self == ShaderDialect::GlslEs300
+	}
+
+	/// The full screen vertex shader of the postprocessing stage:
+	/// [POST_VERTEX_SHADER] or [POST_VERTEX_SHADER_ES]
+	pub fn post_vertex_shader(self) -> &'static str {
+		match self {
+			ShaderDialect::Glsl150 => POST_VERTEX_SHADER,
+			ShaderDialect::GlslEs300 => POST_VERTEX_SHADER_ES,
+		}
+	}
+
+	/// The vertex and pixel shaders of the performance overlay:
+	/// [HUD_VERTEX_SHADER] and [HUD_PIXEL_SHADER], or their `_ES` variants
+	pub fn hud_shaders(self) -> (&'static str, &'static str) {
+		match self {
+			ShaderDialect::Glsl150 => (HUD_VERTEX_SHADER, HUD_PIXEL_SHADER),
+			ShaderDialect::GlslEs300 => (HUD_VERTEX_SHADER_ES, HUD_PIXEL_SHADER_ES),
+		}
+	}
+}
+
 /// Postprocessing 2d passthrough shader
 pub const POST_VERTEX_SHADER: &str = r"
 #version 150 core
@@ -14,6 +68,20 @@ void main() {
 }
 ";
 
+/// As [POST_VERTEX_SHADER], for Gl ES 3.0 contexts
+pub const POST_VERTEX_SHADER_ES: &str = r"
+#version 300 es
+
+in vec2 a_Pos;
+in vec2 a_TexCoord;
+out vec2 v_TexCoord;
+
+void main() {
+	v_TexCoord = a_TexCoord;
+	gl_Position = vec4(a_Pos, 0.0, 1.0);
+}
+";
+
 /// Postprocessing 2d shader, does a linear to sRGB conversion
 pub const POST_PIXEL_SHADER: &str = r"
 #version 150 core
@@ -64,3 +132,498 @@ void main() {
 	o_Color = vec4(to_sRGB(sampled_color).rgb, sampled_color.a);
 }
 ";
+
+/// Selection outline shader, blends `u_OutlineColor` onto the pixels within `u_OutlineWidth`
+/// of any non-zero texel of the mask
+pub const OUTLINE_PIXEL_SHADER: &str = r"
+#version 150 core
+
+uniform sampler2D t_Mask;
+uniform vec4 u_OutlineColor;
+uniform float u_OutlineWidth;
+
+in vec2 v_TexCoord;
+out vec4 o_Color;
+
+bool is_selected(ivec2 p, ivec2 size) {
+	ivec2 c = clamp(p, ivec2(0), size - ivec2(1));
+	return any(greaterThan(texelFetch(t_Mask, c, 0), vec4(0.0)));
+}
+
+void main() {
+	ivec2 size = textureSize(t_Mask, 0);
+	ivec2 center = ivec2(vec2(size) * v_TexCoord);
+	if (is_selected(center, size)) {
+		discard;
+	}
+	int radius = int(ceil(u_OutlineWidth));
+	for (int y = -radius; y <= radius; ++y) {
+		for (int x = -radius; x <= radius; ++x) {
+			if (length(vec2(x, y)) <= u_OutlineWidth && is_selected(center + ivec2(x, y), size)) {
+				o_Color = u_OutlineColor;
+				return;
+			}
+		}
+	}
+	discard;
+}
+";
+
+/// Selection outline shader, as [OUTLINE_PIXEL_SHADER] but reading the first sample of a multisampled mask
+pub const OUTLINE_PIXEL_SHADER_MSAA: &str = r"
+#version 150 core
+
+uniform sampler2DMS t_Mask;
+uniform vec4 u_OutlineColor;
+uniform float u_OutlineWidth;
+
+in vec2 v_TexCoord;
+out vec4 o_Color;
+
+bool is_selected(ivec2 p, ivec2 size) {
+	ivec2 c = clamp(p, ivec2(0), size - ivec2(1));
+	return any(greaterThan(texelFetch(t_Mask, c, 0), vec4(0.0)));
+}
+
+void main() {
+	ivec2 size = textureSize(t_Mask);
+	ivec2 center = ivec2(vec2(size) * v_TexCoord);
+	if (is_selected(center, size)) {
+		discard;
+	}
+	int radius = int(ceil(u_OutlineWidth));
+	for (int y = -radius; y <= radius; ++y) {
+		for (int x = -radius; x <= radius; ++x) {
+			if (length(vec2(x, y)) <= u_OutlineWidth && is_selected(center + ivec2(x, y), size)) {
+				o_Color = u_OutlineColor;
+				return;
+			}
+		}
+	}
+	discard;
+}
+";
+
+/// Resolves a multisampled object ID attachment by copying its first sample,
+/// as IDs can't be averaged
+pub const PICKING_RESOLVE_PIXEL_SHADER: &str = r"
+#version 150 core
+
+uniform usampler2DMS t_Ids;
+
+in vec2 v_TexCoord;
+out uint o_Id;
+
+void main() {
+	ivec2 i = ivec2(vec2(textureSize(t_Ids)) * v_TexCoord);
+	o_Id = texelFetch(t_Ids, i, 0).r;
+}
+";
+
+/// Copies the depth value at `u_Pixel` into a single texel float target
+pub const DEPTH_READ_PIXEL_SHADER: &str = r"
+#version 150 core
+
+uniform sampler2D t_Depth;
+uniform ivec2 u_Pixel;
+
+out float o_Depth;
+
+void main() {
+	o_Depth = texelFetch(t_Depth, u_Pixel, 0).r;
+}
+";
+
+/// As [DEPTH_READ_PIXEL_SHADER], resolving a multisampled depth buffer by keeping the nearest sample
+pub const DEPTH_READ_PIXEL_SHADER_MSAA: &str = r"
+#version 150 core
+
+uniform sampler2DMS t_Depth;
+uniform ivec2 u_Pixel;
+uniform int u_Samples;
+
+out float o_Depth;
+
+void main() {
+	float depth = 1.0;
+	for (int s = 0; s < u_Samples; ++s) {
+		depth = min(depth, texelFetch(t_Depth, u_Pixel, s).r);
+	}
+	o_Depth = depth;
+}
+";
+
+/// Averages the texels within `u_Rect` (x, y, width, height) into a single texel float target
+pub const COLOR_READ_PIXEL_SHADER: &str = r"
+#version 150 core
+
+uniform sampler2D t_Source;
+uniform ivec4 u_Rect;
+
+out vec4 o_Color;
+
+void main() {
+	vec4 sum = vec4(0.0);
+	for (int y = 0; y < u_Rect.w; ++y) {
+		for (int x = 0; x < u_Rect.z; ++x) {
+			sum += texelFetch(t_Source, u_Rect.xy + ivec2(x, y), 0);
+		}
+	}
+	o_Color = sum / float(u_Rect.z * u_Rect.w);
+}
+";
+
+/// As [COLOR_READ_PIXEL_SHADER], also averaging the samples of a multisampled source
+pub const COLOR_READ_PIXEL_SHADER_MSAA: &str = r"
+#version 150 core
+
+uniform sampler2DMS t_Source;
+uniform ivec4 u_Rect;
+uniform int u_Samples;
+
+out vec4 o_Color;
+
+void main() {
+	vec4 sum = vec4(0.0);
+	for (int y = 0; y < u_Rect.w; ++y) {
+		for (int x = 0; x < u_Rect.z; ++x) {
+			for (int s = 0; s < u_Samples; ++s) {
+				sum += texelFetch(t_Source, u_Rect.xy + ivec2(x, y), s);
+			}
+		}
+	}
+	o_Color = sum / float(u_Rect.z * u_Rect.w * u_Samples);
+}
+";
+
+/// Describes one of the built-in postprocessing pixel shaders, assembled from snippets
+/// according to the source framebuffer and output settings
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PostPixelShaderVariant {
+	/// Antialiasing mode of the source framebuffer. `Multi(n)` sources are resolved by averaging n samples
+	pub aa: gfx::texture::AaMode,
+	/// Filter used to sample single-sampled sources
+	pub filter: PostprocessFilter,
+	/// Transfer function applied to the output color
+	pub transfer: OutputTransfer,
+	/// Whether the output color is graded through the `t_Lut` 3D texture
+	pub color_grading: bool,
+	/// Whether the output color is dithered with the `t_Dither` threshold map
+	pub dithering: bool,
+	/// Shading language of the generated source
+	pub dialect: ShaderDialect,
+}
+
+const POST_PIXEL_VERSION: &str = r"
+#version 150 core
+";
+
+const POST_PIXEL_VERSION_ES: &str = r"
+#version 300 es
+precision highp float;
+precision highp int;
+precision highp sampler2D;
+precision mediump sampler3D;
+";
+
+const POST_PIXEL_VERSION_ES_MSAA: &str = r"
+#version 310 es
+precision highp float;
+precision highp int;
+precision highp sampler2D;
+precision highp sampler2DMS;
+precision mediump sampler3D;
+";
+
+const POST_PIXEL_HEADER: &str = r"
+in vec2 v_TexCoord;
+out vec4 o_Color;
+";
+
+const POST_SAMPLE_SINGLE: &str = r"
+uniform sampler2D t_Source;
+
+vec4 sample_source(vec2 tex_coord) {
+	return texture(t_Source, tex_coord);
+}
+";
+
+const POST_SAMPLE_BICUBIC: &str = r"
+uniform sampler2D t_Source;
+
+vec4 cubic(float v)
+{
+	vec4 n = vec4(1.0, 2.0, 3.0, 4.0) - v;
+	vec4 s = n * n * n;
+	float x = s.x;
+	float y = s.y - 4.0 * s.x;
+	float z = s.z - 4.0 * s.y + 6.0 * s.x;
+	float w = 6.0 - x - y - z;
+	return vec4(x, y, z, w) * (1.0 / 6.0);
+}
+
+vec4 sample_source(vec2 tex_coord)
+{
+	vec2 size = vec2(textureSize(t_Source, 0));
+	vec2 uv = tex_coord * size - 0.5;
+	vec2 fxy = fract(uv);
+	uv -= fxy;
+
+	vec4 xcubic = cubic(fxy.x);
+	vec4 ycubic = cubic(fxy.y);
+
+	vec4 c = uv.xxyy + vec2(-0.5, 1.5).xyxy;
+	vec4 s = vec4(xcubic.xz + xcubic.yw, ycubic.xz + ycubic.yw);
+	vec4 offset = (c + vec4(xcubic.yw, ycubic.yw) / s) / size.xxyy;
+
+	vec4 sample0 = texture(t_Source, offset.xz);
+	vec4 sample1 = texture(t_Source, offset.yz);
+	vec4 sample2 = texture(t_Source, offset.xw);
+	vec4 sample3 = texture(t_Source, offset.yw);
+
+	float sx = s.x / (s.x + s.y);
+	float sy = s.z / (s.z + s.w);
+
+	return mix(mix(sample3, sample2, sx), mix(sample1, sample0, sx), sy);
+}
+";
+
+const POST_SAMPLE_MSAA: &str = r"
+uniform sampler2DMS t_Source;
+
+vec4 sample_source(vec2 tex_coord) {
+	vec2 d = vec2(textureSize(t_Source));
+	ivec2 i = ivec2(d * tex_coord);
+	vec4 sum = vec4(0.0);
+	for (int s = 0; s < SAMPLES; ++s) {
+		sum += texelFetch(t_Source, i, s);
+	}
+	return sum / float(SAMPLES);
+}
+";
+
+const POST_TRANSFER_SRGB: &str = r"
+vec4 encode_output(vec4 linearRGB)
+{
+    bvec4 cutoff = lessThan(linearRGB, vec4(0.0031308));
+    vec4 higher = vec4(1.055)*pow(linearRGB, vec4(1.0/2.4)) - vec4(0.055);
+    vec4 lower = linearRGB * vec4(12.92);
+
+    return mix(higher, lower, cutoff);
+}
+";
+
+const POST_TRANSFER_PASSTHROUGH: &str = r"
+vec4 encode_output(vec4 linearRGB)
+{
+	return linearRGB;
+}
+";
+
+const POST_TRANSFER_GAMMA: &str = r"
+vec4 encode_output(vec4 linearRGB)
+{
+	return pow(max(linearRGB, vec4(0.0)), vec4(1.0 / GAMMA));
+}
+";
+
+const POST_GRADE_NONE: &str = r"
+vec3 grade_output(vec3 color)
+{
+	return color;
+}
+";
+
+const POST_GRADE_LUT: &str = r"
+uniform sampler3D t_Lut;
+uniform vec3 u_LutDomainMin;
+uniform vec3 u_LutDomainMax;
+uniform float u_LutStrength;
+
+vec3 grade_output(vec3 color)
+{
+	float size = float(textureSize(t_Lut, 0).x);
+	vec3 uvw = clamp((color - u_LutDomainMin) / (u_LutDomainMax - u_LutDomainMin), 0.0, 1.0);
+	// sample at texel centres, so that the domain bounds map onto the first and last entries
+	uvw = uvw * ((size - 1.0) / size) + 0.5 / size;
+	return mix(color, texture(t_Lut, uvw).rgb, u_LutStrength);
+}
+";
+
+const POST_DITHER_NONE: &str = r"
+vec3 dither_output(vec3 color)
+{
+	return color;
+}
+";
+
+const POST_DITHER_MAP: &str = r"
+uniform sampler2D t_Dither;
+
+vec3 dither_output(vec3 color)
+{
+	ivec2 size = textureSize(t_Dither, 0);
+	float threshold = texelFetch(t_Dither, ivec2(gl_FragCoord.xy) % size, 0).r;
+	// one 8 bit quantisation step, centered on zero
+	return color + vec3((threshold - 0.5) / 255.0);
+}
+";
+
+const POST_PIXEL_MAIN: &str = r"
+void main() {
+	vec4 sampled_color = sample_source(v_TexCoord);
+	vec3 output_color = dither_output(grade_output(encode_output(sampled_color).rgb));
+	o_Color = vec4(output_color, sampled_color.a);
+}
+";
+
+impl PostPixelShaderVariant {
+	/// Returns the GLSL source code of the pixel shader
+	pub fn source(&self) -> String {
+		let multisampled = match self.aa {
+			gfx::texture::AaMode::Multi(_) => true,
+			_ => false,
+		};
+		let mut source = String::from(match (self.dialect, multisampled) {
+			(ShaderDialect::Glsl150, _) => POST_PIXEL_VERSION,
+			(ShaderDialect::GlslEs300, false) => POST_PIXEL_VERSION_ES,
+			(ShaderDialect::GlslEs300, true) => POST_PIXEL_VERSION_ES_MSAA,
+		});
+		source.push_str(POST_PIXEL_HEADER);
+		match (self.aa, self.filter) {
+			(gfx::texture::AaMode::Multi(samples), _) => {
+				source.push_str(&format!("#define SAMPLES {}\n", samples.max(1)));
+				source.push_str(POST_SAMPLE_MSAA);
+			}
+			(_, PostprocessFilter::Bicubic) => source.push_str(POST_SAMPLE_BICUBIC),
+			_ => source.push_str(POST_SAMPLE_SINGLE),
+		}
+		match self.transfer {
+			OutputTransfer::Passthrough => source.push_str(POST_TRANSFER_PASSTHROUGH),
+			OutputTransfer::Gamma(gamma) => {
+				source.push_str(&format!("#define GAMMA {:.6}\n", gamma));
+				source.push_str(POST_TRANSFER_GAMMA);
+			}
+			OutputTransfer::Srgb | OutputTransfer::Auto => source.push_str(POST_TRANSFER_SRGB),
+		}
+		if self.color_grading {
+			source.push_str(POST_GRADE_LUT);
+		} else {
+			source.push_str(POST_GRADE_NONE);
+		}
+		if self.dithering {
+			source.push_str(POST_DITHER_MAP);
+		} else {
+			source.push_str(POST_DITHER_NONE);
+		}
+		source.push_str(POST_PIXEL_MAIN);
+		source
+	}
+}
+
+/// Performance overlay vertex shader, positions are already in normalized device coordinates
+pub const HUD_VERTEX_SHADER: &str = r"
+#version 150 core
+
+in vec2 a_Pos;
+in vec2 a_TexCoord;
+in vec4 a_Color;
+out vec2 v_TexCoord;
+out vec4 v_Color;
+
+void main() {
+	v_TexCoord = a_TexCoord;
+	v_Color = a_Color;
+	gl_Position = vec4(a_Pos, 0.0, 1.0);
+}
+";
+
+/// Performance overlay pixel shader, glyphs are masked by the font atlas coverage,
+/// quads with negative texture coordinates are solid
+pub const HUD_PIXEL_SHADER: &str = r"
+#version 150 core
+
+uniform sampler2D t_Font;
+
+in vec2 v_TexCoord;
+in vec4 v_Color;
+out vec4 o_Color;
+
+void main() {
+	float coverage = v_TexCoord.x < 0.0 ? 1.0 : texture(t_Font, v_TexCoord).r;
+	o_Color = vec4(v_Color.rgb, v_Color.a * coverage);
+}
+";
+
+/// Performance overlay vertex shader, GLSL ES 3.00
+pub const HUD_VERTEX_SHADER_ES: &str = r"
+#version 300 es
+
+in vec2 a_Pos;
+in vec2 a_TexCoord;
+in vec4 a_Color;
+out vec2 v_TexCoord;
+out vec4 v_Color;
+
+void main() {
+	v_TexCoord = a_TexCoord;
+	v_Color = a_Color;
+	gl_Position = vec4(a_Pos, 0.0, 1.0);
+}
+";
+
+/// Performance overlay pixel shader, GLSL ES 3.00
+pub const HUD_PIXEL_SHADER_ES: &str = r"
+#version 300 es
+precision mediump float;
+
+uniform sampler2D t_Font;
+
+in vec2 v_TexCoord;
+in vec4 v_Color;
+out vec4 o_Color;
+
+void main() {
+	float coverage = v_TexCoord.x < 0.0 ? 1.0 : texture(t_Font, v_TexCoord).r;
+	o_Color = vec4(v_Color.rgb, v_Color.a * coverage);
+}
+";
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn variant() -> PostPixelShaderVariant {
+		PostPixelShaderVariant {
+			aa: gfx::texture::AaMode::Single,
+			filter: PostprocessFilter::Nearest,
+			transfer: OutputTransfer::Srgb,
+			color_grading: false,
+			dithering: false,
+			dialect: ShaderDialect::Glsl150,
+		}
+	}
+
+	#[test]
+	fn color_grading_adds_the_lut() {
+		let ungraded = variant().source();
+		assert!(!ungraded.contains("t_Lut"));
+		let graded = PostPixelShaderVariant {
+			color_grading: true,
+			..variant()
+		}
+		.source();
+		assert!(graded.contains("uniform sampler3D t_Lut;"));
+		assert!(graded.contains("uniform float u_LutStrength;"));
+	}
+
+	#[test]
+	fn hud_shaders_follow_the_dialect() {
+		let (vertex, pixel) = ShaderDialect::Glsl150.hud_shaders();
+		assert!(vertex.trim_start().starts_with("#version 150 core"));
+		assert!(pixel.trim_start().starts_with("#version 150 core"));
+		let (vertex, pixel) = ShaderDialect::GlslEs300.hud_shaders();
+		assert!(vertex.trim_start().starts_with("#version 300 es"));
+		assert!(pixel.trim_start().starts_with("#version 300 es\nprecision"));
+	}
+}
@@ -0,0 +1,255 @@
+//! Gl objects shared by the render contexts of several GlAreas, e.g. the four views of a quad view.
+//! Each [RenderContext] keeps its own device, targets and viewport, while the meshes, textures and
+//! pipeline states created by the factory of a [ShareGroup] are uploaded once and drawn by all of them
+//!
+//! Gdk creates the Gl contexts of the GlAreas in the same toplevel window sharing their objects,
+//! so only GlAreas in the same toplevel window can join the same group
+
+use super::*;
+use gdk::GLContextExt;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The device and factory of a group, created in the context of its first member
+struct SharedDevice {
+	device: GlDevice,
+	factory: GlFactory,
+	/// the context the device has been created in, kept to delete the shared objects
+	context: gdk::GLContext,
+}
+
+/// The members of a group, and the context they share their objects with.
+/// `C` is `gdk::GLContext`, other types stand in for it in the tests
+struct Membership<C> {
+	/// set by the first member to join, never changes afterwards
+	shared_context: Option<Option<C>>,
+	members: usize,
+}
+
+impl<C> Default for Membership<C> {
+	fn default() -> Self {
+		Membership {
+			shared_context: None,
+			members: 0,
+		}
+	}
+}
+
+impl<C> Membership<C>
+where
+	C: PartialEq,
+{
+	/// Counts a member whose Gl context shares objects with `shared_context`.
+	/// Fails if the group shares objects with another context
+	fn join(&mut self, shared_context: Option<C>) -> Result<()> {
+		match self.shared_context {
+			Some(ref group_context) if *group_context != shared_context => {
+				return Err(Error::GenericError(
+					"The Gl context doesn't share objects with the group, the GlAreas of a share group must be in the same toplevel window"
+						.to_string(),
+				));
+			}
+			Some(_) => {}
+			None => self.shared_context = Some(shared_context),
+		}
+		self.members += 1;
+		Ok(())
+	}
+
+	/// Forgets a member, the group keeps sharing objects with the same context
+	fn leave(&mut self) {
+		self.members = self.members.saturating_sub(1);
+	}
+}
+
+#[derive(Default)]
+struct ShareGroupState {
+	shared: Option<SharedDevice>,
+	membership: Membership<gdk::GLContext>,
+	resources: HashMap<String, Box<dyn Any>>,
+}
+
+impl Drop for ShareGroupState {
+	/// Deletes the shared objects in the context the device has been created in, whichever is current
+	fn drop(&mut self) {
+		if let Some(ref mut shared) = self.shared {
+			use gfx::Device;
+			let previous = gdk::GLContext::get_current();
+			shared.context.make_current();
+			self.resources.clear();
+			shared.device.cleanup();
+			match previous {
+				Some(previous) => previous.make_current(),
+				None => gdk::GLContext::clear_current(),
+			}
+		}
+	}
+}
+
+#[derive(Clone, Default)]
+/// A set of render contexts sharing Gl objects, see [RenderContext::set_share_group()].
+/// Clones refer to the same group
+pub struct ShareGroup {
+	state: Rc<RefCell<ShareGroupState>>,
+}
+
+impl ShareGroup {
+	/// Creates an empty group, the shared factory is created when the first render context joins
+	/// and lives as long as the group
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds the render context whose Gl context is current. Invoked by
+	/// [RenderContext::set_share_group()]
+	pub fn join(&self) -> Result<()> {
+		let current = match gdk::GLContext::get_current() {
+			Some(current) => current,
+			None => {
+				return Err(Error::GenericError(
+					"No Gl context is current, can't join the share group".to_string(),
+				))
+			}
+		};
+		let mut state = self.state.borrow_mut();
+		state.membership.join(current.get_shared_context())?;
+		if state.shared.is_none() {
+			// the device binds its own vertex array, which the member's device relies on
+			let mut vertex_array = 0;
+			unsafe {
+				gl::GetIntegerv(gl::VERTEX_ARRAY_BINDING, &mut vertex_array);
+			}
+			let (device, factory) = gfx_device_gl::create(epoxy::get_proc_addr);
+			unsafe {
+				gl::BindVertexArray(vertex_array as u32);
+			}
+			state.shared = Some(SharedDevice {
+				device,
+				factory,
+				context: current,
+			});
+		}
+		Ok(())
+	}
+
+	/// Removes a render context. The shared device and resources are kept as long as the group,
+	/// for the render contexts joining later
+	pub fn leave(&self) {
+		self.state.borrow_mut().membership.leave();
+	}
+
+	/// Number of render contexts in the group
+	pub fn members(&self) -> usize {
+		self.state.borrow().membership.members
+	}
+
+	/// Invokes `f` with the shared factory, which creates Gl objects usable by all the render contexts
+	/// of the group. Must be called with the Gl context of a member current, e.g. from a render callback.
+	/// Fails if no render context has joined yet
+	pub fn with_factory<T, F>(&self, f: F) -> Result<T>
+	where
+		F: FnOnce(&mut GlFactory) -> Result<T>,
+	{
+		let mut state = self.state.borrow_mut();
+		match state.shared {
+			Some(ref mut shared) => f(&mut shared.factory),
+			None => Err(Error::GenericError(
+				"No render context has joined the share group yet".to_string(),
+			)),
+		}
+	}
+
+	/// Returns the shared resources registered as `name`, creating them with the shared factory
+	/// the first time, so that each member doesn't upload its own copy. Must be called with the
+	/// Gl context of a member current, e.g. when creating the render callback. Fails if `name`
+	/// is registered with resources of another type
+	/// * `name` identifies the resources within the group
+	/// * `create` creates the resources, usually gfx handles, which are cloned for each caller
+	pub fn resource<T, F>(&self, name: &str, create: F) -> Result<T>
+	where
+		T: Clone + 'static,
+		F: FnOnce(&mut GlFactory) -> Result<T>,
+	{
+		if let Some(resource) = self.state.borrow().resources.get(name) {
+			return match resource.downcast_ref::<T>() {
+				Some(resource) => Ok(resource.clone()),
+				None => Err(Error::GenericError(format!(
+					"The shared resource {} has another type",
+					name
+				))),
+			};
+		}
+		let resource = self.with_factory(create)?;
+		self.state
+			.borrow_mut()
+			.resources
+			.insert(name.to_string(), Box::new(resource.clone()));
+		Ok(resource)
+	}
+
+	/// Forgets the shared resources registered as `name`. Their Gl objects are deleted once the
+	/// render callbacks have dropped their handles too
+	pub fn remove_resource(&self, name: &str) {
+		self.state.borrow_mut().resources.remove(name);
+	}
+
+	/// Deletes the Gl objects created by the shared factory whose handles have all been dropped.
+	/// Invoked by the members after each frame
+	pub fn cleanup(&self) {
+		use gfx::Device;
+		if let Some(ref mut shared) = self.state.borrow_mut().shared {
+			shared.device.cleanup();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn members_are_counted() {
+		let mut membership = Membership::default();
+		assert_eq!(membership.members, 0);
+		membership.join(Some(1)).unwrap();
+		membership.join(Some(1)).unwrap();
+		assert_eq!(membership.members, 2);
+		membership.leave();
+		assert_eq!(membership.members, 1);
+		membership.leave();
+		membership.leave();
+		assert_eq!(membership.members, 0);
+	}
+
+	#[test]
+	fn members_must_share_the_same_context() {
+		let mut membership = Membership::default();
+		membership.join(Some(1)).unwrap();
+		assert!(membership.join(Some(2)).is_err());
+		assert!(membership.join(None).is_err());
+		assert_eq!(membership.members, 1);
+	}
+
+	#[test]
+	fn the_shared_context_outlives_the_members() {
+		let mut membership = Membership::default();
+		membership.join(Some(1)).unwrap();
+		membership.leave();
+		assert_eq!(membership.members, 0);
+		// the shared objects still belong to the first context
+		assert!(membership.join(Some(2)).is_err());
+		membership.join(Some(1)).unwrap();
+		assert_eq!(membership.members, 1);
+	}
+
+	#[test]
+	fn contexts_sharing_nothing_form_a_group_too() {
+		let mut membership = Membership::<u32>::default();
+		membership.join(None).unwrap();
+		membership.join(None).unwrap();
+		assert!(membership.join(Some(1)).is_err());
+		assert_eq!(membership.members, 2);
+	}
+}
@@ -0,0 +1,417 @@
+//! Per-frame statistics of a [RenderContext]: CPU time spent in each stage of
+//! [RenderContext::with_gfx()] and, where `GL_ARB_timer_query` is available, GPU time measured
+//! with `GL_TIME_ELAPSED` queries, collected a few frames later so the CPU never waits for the GPU
+
+use super::*;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Number of frames whose GPU queries can be in flight, frames beyond this are not GPU timed
+const MAX_PENDING_QUERIES: usize = 4;
+
+/// Stages of a frame, in order
+const STAGES: usize = 3;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+/// Time spent in each stage of a frame
+pub struct StageTimes {
+	/// The `render` callback, including the selection outline mask
+	pub render: Duration,
+	/// The `postprocess` callback, including the selection outline
+	pub postprocess: Duration,
+	/// The performance overlay, if shown, and the final blit into the GlArea framebuffer
+	pub blit: Duration,
+}
+
+impl StageTimes {
+	fn from_array(times: [Duration; STAGES]) -> Self {
+		StageTimes {
+			render: times[0],
+			postprocess: times[1],
+			blit: times[2],
+		}
+	}
+
+	/// Sum of all the stages
+	pub fn total(&self) -> Duration {
+		self.render + self.postprocess + self.blit
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Statistics of a single frame
+pub struct FrameStats {
+	/// Sequence number of the frame, since the statistics were enabled
+	pub frame: u64,
+	/// When the frame was started
+	pub started: Instant,
+	/// Time since the start of the previous frame, `None` for the first one
+	pub interval: Option<Duration>,
+	/// CPU time of each stage
+	pub cpu: StageTimes,
+	/// GPU time of each stage, `None` if timer queries are not supported or the results are not
+	/// available yet. Usually filled in two or three frames later
+	pub gpu: Option<StageTimes>,
+}
+
+/// A set of `GL_TIME_ELAPSED` queries, one per stage. Deleted when dropped, in the Gl context
+/// current at the time, which must be the one they were created in
+struct GpuTimer {
+	queries: [gl::types::GLuint; STAGES],
+}
+
+impl GpuTimer {
+	fn new() -> Self {
+		let mut queries = [0; STAGES];
+		unsafe {
+			gl::GenQueries(STAGES as i32, queries.as_mut_ptr());
+		}
+		GpuTimer { queries }
+	}
+
+	fn begin(&self, stage: usize) {
+		unsafe {
+			gl::BeginQuery(gl::TIME_ELAPSED, self.queries[stage]);
+		}
+	}
+
+	fn end(&self) {
+		unsafe {
+			gl::EndQuery(gl::TIME_ELAPSED);
+		}
+	}
+
+	/// The elapsed times, if all the results are available
+	fn results(&self) -> Option<[Duration; STAGES]> {
+		let mut available = 0;
+		unsafe {
+			// results become available in order
+			gl::GetQueryObjectiv(
+				self.queries[STAGES - 1],
+				gl::QUERY_RESULT_AVAILABLE,
+				&mut available,
+			);
+		}
+		if available == 0 {
+			return None;
+		}
+		let mut times = [Duration::from_secs(0); STAGES];
+		for (time, query) in times.iter_mut().zip(self.queries.iter()) {
+			let mut nanoseconds = 0u64;
+			unsafe {
+				gl::GetQueryObjectui64v(*query, gl::QUERY_RESULT, &mut nanoseconds);
+			}
+			*time = Duration::new(
+				nanoseconds / 1_000_000_000,
+				(nanoseconds % 1_000_000_000) as u32,
+			);
+		}
+		Some(times)
+	}
+}
+
+impl Drop for GpuTimer {
+	fn drop(&mut self) {
+		unsafe {
+			gl::DeleteQueries(STAGES as i32, self.queries.as_ptr());
+		}
+	}
+}
+
+/// The frame being measured
+struct CurrentFrame {
+	stage: usize,
+	marks: [Instant; STAGES + 1],
+	timer: Option<GpuTimer>,
+}
+
+/// Rolling history of [FrameStats], see [RenderContext::enable_frame_stats()]
+pub struct FrameStatsHistory {
+	capacity: usize,
+	history: VecDeque<FrameStats>,
+	next_frame: u64,
+	gpu_timing: bool,
+	current: Option<CurrentFrame>,
+	/// GPU timers in flight, with the frame they belong to
+	pending: VecDeque<(u64, GpuTimer)>,
+	/// GPU timers whose results have been collected, ready to be reused
+	idle: Vec<GpuTimer>,
+}
+
+impl FrameStatsHistory {
+	/// Creates an empty history. No Gl objects are created until the first GPU timed frame,
+	/// which must be measured with the Gl context current
+	/// * `capacity` number of frames retained
+	/// * `gpu_timing` whether timer queries are supported by the Gl context
+	pub fn new(capacity: usize, gpu_timing: bool) -> Self {
+		FrameStatsHistory {
+			capacity: capacity.max(1),
+			history: VecDeque::with_capacity(capacity.max(1)),
+			next_frame: 0,
+			gpu_timing,
+			current: None,
+			pending: VecDeque::new(),
+			idle: Vec::new(),
+		}
+	}
+
+	/// `true` if GPU times are measured
+	pub fn gpu_timing(&self) -> bool {
+		self.gpu_timing
+	}
+
+	/// `true` if the frame being measured has a GPU timer, and the commands of each stage
+	/// must be flushed before moving to the next one
+	pub fn is_gpu_timed(&self) -> bool {
+		self.current
+			.as_ref()
+			.map(|c| c.timer.is_some())
+			.unwrap_or(false)
+	}
+
+	/// Collects the available GPU results, and starts measuring a new frame from its first stage
+	pub fn begin_frame(&mut self) {
+		self.collect();
+		let timer = if self.gpu_timing && self.pending.len() < MAX_PENDING_QUERIES {
+			Some(self.idle.pop().unwrap_or_else(GpuTimer::new))
+		} else {
+			None
+		};
+		if let Some(ref timer) = timer {
+			timer.begin(0);
+		}
+		let now = Instant::now();
+		self.current = Some(CurrentFrame {
+			stage: 0,
+			marks: [now; STAGES + 1],
+			timer,
+		});
+	}
+
+	/// Ends the current stage and starts the next one
+	pub fn next_stage(&mut self) {
+		if let Some(ref mut current) = self.current {
+			if current.stage + 1 < STAGES {
+				current.stage += 1;
+				current.marks[current.stage] = Instant::now();
+				if let Some(ref timer) = current.timer {
+					timer.end();
+					timer.begin(current.stage);
+				}
+			}
+		}
+	}
+
+	/// Ends the frame being measured. Stages that were skipped are recorded as zero
+	pub fn end_frame(&mut self) {
+		let current = match self.current.take() {
+			Some(current) => current,
+			None => return,
+		};
+		let now = Instant::now();
+		let mut marks = current.marks;
+		for mark in marks.iter_mut().skip(current.stage + 1) {
+			*mark = now;
+		}
+		let mut cpu = [Duration::from_secs(0); STAGES];
+		for (i, time) in cpu.iter_mut().enumerate() {
+			*time = marks[i + 1] - marks[i];
+		}
+		let frame = self.next_frame;
+		self.next_frame += 1;
+		if let Some(timer) = current.timer {
+			timer.end();
+			// queries of the skipped stages must still produce a result
+			for stage in current.stage + 1..STAGES {
+				timer.begin(stage);
+				timer.end();
+			}
+			self.pending.push_back((frame, timer));
+		}
+		let interval = self.history.back().map(|last| marks[0] - last.started);
+		if self.history.len() == self.capacity {
+			self.history.pop_front();
+		}
+		self.history.push_back(FrameStats {
+			frame,
+			started: marks[0],
+			interval,
+			cpu: StageTimes::from_array(cpu),
+			gpu: None,
+		});
+	}
+
+	/// Attaches the GPU results that are available to their frames, without waiting
+	fn collect(&mut self) {
+		while let Some(times) = self.pending.front().and_then(|&(_, ref t)| t.results()) {
+			let (frame, timer) = self.pending.pop_front().unwrap();
+			if let Some(stats) = self.history.iter_mut().find(|s| s.frame == frame) {
+				stats.gpu = Some(StageTimes::from_array(times));
+			}
+			self.idle.push(timer);
+		}
+	}
+
+	/// The retained frames, oldest first
+	pub fn history(&self) -> &VecDeque<FrameStats> {
+		&self.history
+	}
+
+	/// The last completed frame
+	pub fn last(&self) -> Option<&FrameStats> {
+		self.history.back()
+	}
+
+	/// The last frame whose GPU times are available
+	pub fn last_gpu(&self) -> Option<&FrameStats> {
+		self.history.iter().rev().find(|s| s.gpu.is_some())
+	}
+
+	/// Frames per second, averaged over the frames of the last second
+	pub fn fps(&self) -> Option<f64> {
+		let last = self.history.back()?;
+		let window = Duration::from_secs(1);
+		let first = self
+			.history
+			.iter()
+			.find(|s| last.started - s.started <= window)?;
+		let frames = last.frame - first.frame;
+		let elapsed = last.started - first.started;
+		let seconds = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9;
+		if frames > 0 && seconds > 0. {
+			Some(frames as f64 / seconds)
+		} else {
+			None
+		}
+	}
+
+	/// The `percentile` (in the range 0..100) of a per-frame time over the retained frames,
+	/// with the nearest rank method. Frames for which `time` returns `None` are ignored
+	/// * `percentile` e.g. `50.` for the median, `99.` for the worst frames
+	/// * `time` extracts the time from the statistics of a frame, e.g. `|s| s.interval`
+	pub fn percentile<F>(&self, percentile: f64, time: F) -> Option<Duration>
+	where
+		F: Fn(&FrameStats) -> Option<Duration>,
+	{
+		let mut times: Vec<Duration> = self.history.iter().filter_map(time).collect();
+		if times.is_empty() {
+			return None;
+		}
+		times.sort();
+		let rank = (percentile.max(0.).min(100.) / 100. * times.len() as f64).ceil() as usize;
+		Some(times[rank.max(1) - 1])
+	}
+
+	/// The `percentile` (in the range 0..100) of the time between frames
+	pub fn frame_time_percentile(&self, percentile: f64) -> Option<Duration> {
+		self.percentile(percentile, |s| s.interval)
+	}
+
+	/// Forgets the retained frames
+	pub fn clear(&mut self) {
+		self.history.clear();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn millis(ms: u64) -> Duration {
+		Duration::from_millis(ms)
+	}
+
+	/// A history of frames started at the given times, in milliseconds
+	fn history_of(starts: &[u64]) -> FrameStatsHistory {
+		let origin = Instant::now();
+		let mut stats = FrameStatsHistory::new(starts.len(), false);
+		for (frame, start) in starts.iter().enumerate() {
+			let interval = stats
+				.history
+				.back()
+				.map(|last| origin + millis(*start) - last.started);
+			stats.history.push_back(FrameStats {
+				frame: frame as u64,
+				started: origin + millis(*start),
+				interval,
+				cpu: StageTimes::default(),
+				gpu: None,
+			});
+		}
+		stats
+	}
+
+	#[test]
+	fn percentiles_use_the_nearest_rank() {
+		// intervals of 1 to 10 ms, in shuffled order
+		let mut starts = vec![0];
+		for interval in &[7, 2, 9, 1, 5, 10, 3, 8, 6, 4] {
+			let last = *starts.last().unwrap();
+			starts.push(last + interval);
+		}
+		let stats = history_of(&starts);
+		assert_eq!(stats.frame_time_percentile(0.), Some(millis(1)));
+		assert_eq!(stats.frame_time_percentile(50.), Some(millis(5)));
+		assert_eq!(stats.frame_time_percentile(51.), Some(millis(6)));
+		assert_eq!(stats.frame_time_percentile(100.), Some(millis(10)));
+		assert_eq!(stats.frame_time_percentile(150.), Some(millis(10)));
+		// the first frame has no interval
+		assert_eq!(history_of(&[0]).frame_time_percentile(50.), None);
+	}
+
+	#[test]
+	fn fps_counts_the_frames_of_the_last_second() {
+		// 20 frames per second for one second, then 100
+		let starts: Vec<u64> = (0..20)
+			.map(|i| i * 50)
+			.chain((0..=100).map(|i| 1000 + i * 10))
+			.collect();
+		let fps = history_of(&starts).fps().unwrap();
+		assert!((fps - 100.).abs() < 1e-6, "{}", fps);
+		assert_eq!(history_of(&[0]).fps(), None);
+		assert_eq!(FrameStatsHistory::new(10, false).fps(), None);
+	}
+
+	#[test]
+	fn skipped_stages_are_recorded_as_zero() {
+		let mut stats = FrameStatsHistory::new(10, false);
+		stats.begin_frame();
+		assert!(!stats.is_gpu_timed());
+		stats.end_frame();
+		stats.begin_frame();
+		stats.next_stage();
+		stats.end_frame();
+		// ending a frame that was never started does nothing
+		stats.end_frame();
+
+		let history = stats.history();
+		assert_eq!(history.len(), 2);
+		assert_eq!(history[0].cpu.postprocess, Duration::from_secs(0));
+		assert_eq!(history[0].cpu.blit, Duration::from_secs(0));
+		assert_eq!(history[1].cpu.blit, Duration::from_secs(0));
+		assert_eq!(history[0].interval, None);
+		assert!(history[1].interval.is_some());
+		assert_eq!(history[1].gpu, None);
+	}
+
+	#[test]
+	fn old_frames_are_evicted() {
+		let mut stats = FrameStatsHistory::new(3, false);
+		for _ in 0..5 {
+			stats.begin_frame();
+			stats.end_frame();
+		}
+		let frames: Vec<u64> = stats.history().iter().map(|s| s.frame).collect();
+		assert_eq!(frames, vec![2, 3, 4]);
+		assert_eq!(stats.last().map(|s| s.frame), Some(4));
+
+		let mut stats = FrameStatsHistory::new(0, false);
+		stats.begin_frame();
+		stats.end_frame();
+		stats.begin_frame();
+		stats.end_frame();
+		assert_eq!(stats.history().len(), 1);
+		stats.clear();
+		assert!(stats.last().is_none());
+	}
+}
@@ -0,0 +1,207 @@
+//! Lock free handoff of state snapshots from a producer thread, e.g. a simulation, to the render
+//! callback. The producer always has a buffer to write to, the consumer always has the latest
+//! complete snapshot to read, and neither ever waits for the other. Intermediate snapshots
+//! published while the consumer is busy are dropped
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use redraw::RedrawHandle;
+
+/// Bits of the shared slot holding the buffer index
+const INDEX_MASK: usize = 0b011;
+/// Bit of the shared slot set when its buffer holds a snapshot the consumer hasn't seen yet
+const FRESH: usize = 0b100;
+
+/// The three buffers, and the index of the one neither side is holding
+struct Shared<T> {
+	buffers: [UnsafeCell<T>; 3],
+	back: AtomicUsize,
+}
+
+// each buffer is only ever accessed by the side holding its index
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+/// The producer side of a triple buffer, see [triple_buffer()]
+pub struct TripleBufferWriter<T> {
+	shared: Arc<Shared<T>>,
+	index: usize,
+	redraw: Option<RedrawHandle>,
+}
+
+/// The consumer side of a triple buffer, see [triple_buffer()]
+pub struct TripleBufferReader<T> {
+	shared: Arc<Shared<T>>,
+	index: usize,
+}
+
+/// Creates a triple buffer, returning its producer and consumer sides, which can be sent to
+/// different threads
+/// * `initial` the snapshot read by the consumer until the first one is published, also the
+/// initial contents of the producer buffers
+pub fn triple_buffer<T>(initial: T) -> (TripleBufferWriter<T>, TripleBufferReader<T>)
+where
+	T: Clone + Send,
+{
+	let shared = Arc::new(Shared {
+		buffers: [
+			UnsafeCell::new(initial.clone()),
+			UnsafeCell::new(initial.clone()),
+			UnsafeCell::new(initial),
+		],
+		back: AtomicUsize::new(2),
+	});
+	(
+		TripleBufferWriter {
+			shared: shared.clone(),
+			index: 0,
+			redraw: None,
+		},
+		TripleBufferReader { shared, index: 1 },
+	)
+}
+
+impl<T> TripleBufferWriter<T>
+where
+	T: Send,
+{
+	/// Requests a redraw via `redraw` every time a snapshot is published
+	/// * `redraw` e.g. from [GfxGlArea::redraw_handle()]
+	pub fn with_redraw_handle(mut self, redraw: RedrawHandle) -> Self {
+		self.redraw = Some(redraw);
+		self
+	}
+
+	/// The buffer of the next snapshot. It holds an older snapshot, not necessarily the last one
+	/// published, so it must be fully updated before [TripleBufferWriter::publish()]
+	pub fn write(&mut self) -> &mut T {
+		unsafe { &mut *self.shared.buffers[self.index].get() }
+	}
+
+	/// Makes the buffer returned by [TripleBufferWriter::write()] the latest snapshot, replacing
+	/// the previous one if the consumer hasn't picked it up yet
+	pub fn publish(&mut self) {
+		let previous = self.shared.back.swap(self.index | FRESH, Ordering::AcqRel);
+		self.index = previous & INDEX_MASK;
+		if let Some(ref redraw) = self.redraw {
+			redraw.request_redraw();
+		}
+	}
+
+	/// Publishes `snapshot` as the latest one
+	pub fn publish_value(&mut self, snapshot: T) {
+		*self.write() = snapshot;
+		self.publish();
+	}
+}
+
+impl<T> TripleBufferReader<T>
+where
+	T: Send,
+{
+	/// Picks up the latest snapshot, if a new one has been published since the last call.
+	/// Returns `true` if the snapshot has changed. Usually invoked at the start of `render`
+	pub fn update(&mut self) -> bool {
+		if self.shared.back.load(Ordering::Relaxed) & FRESH == 0 {
+			return false;
+		}
+		let previous = self.shared.back.swap(self.index, Ordering::AcqRel);
+		self.index = previous & INDEX_MASK;
+		true
+	}
+
+	/// The snapshot picked up by the last [TripleBufferReader::update()]
+	pub fn read(&self) -> &T {
+		unsafe { &*self.shared.buffers[self.index].get() }
+	}
+
+	/// Picks up and returns the latest snapshot
+	pub fn latest(&mut self) -> &T {
+		self.update();
+		self.read()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::thread;
+
+	#[test]
+	fn reads_the_initial_snapshot_until_published() {
+		let (_writer, mut reader) = triple_buffer(7);
+		assert!(!reader.update());
+		assert_eq!(*reader.read(), 7);
+		assert_eq!(*reader.latest(), 7);
+	}
+
+	#[test]
+	fn update_picks_up_the_latest_snapshot_once() {
+		let (mut writer, mut reader) = triple_buffer(0);
+		writer.publish_value(1);
+		assert_eq!(*reader.read(), 0);
+		assert!(reader.update());
+		assert_eq!(*reader.read(), 1);
+		assert!(!reader.update());
+		assert_eq!(*reader.read(), 1);
+	}
+
+	#[test]
+	fn snapshots_published_meanwhile_are_dropped() {
+		let (mut writer, mut reader) = triple_buffer(0);
+		for i in 1..=5 {
+			writer.publish_value(i);
+		}
+		assert!(reader.update());
+		assert_eq!(*reader.read(), 5);
+		assert!(!reader.update());
+	}
+
+	#[test]
+	fn the_writer_never_overwrites_the_snapshot_being_read() {
+		let (mut writer, mut reader) = triple_buffer(vec![0; 4]);
+		writer.publish_value(vec![1; 4]);
+		reader.update();
+		for i in 2..10 {
+			let buffer = writer.write();
+			assert_ne!(*buffer, vec![1; 4]);
+			*buffer = vec![i; 4];
+			writer.publish();
+			assert_eq!(*reader.read(), vec![1; 4]);
+		}
+		assert_eq!(*reader.latest(), vec![9; 4]);
+	}
+
+	#[test]
+	fn snapshots_are_complete_and_in_order_across_threads() {
+		const LENGTH: usize = 64;
+		const SNAPSHOTS: usize = 100_000;
+		let (mut writer, mut reader) = triple_buffer([0usize; LENGTH]);
+		let producer = thread::spawn(move || {
+			for i in 1..=SNAPSHOTS {
+				// every element of a snapshot holds its sequence number
+				for value in writer.write().iter_mut() {
+					*value = i;
+				}
+				writer.publish();
+			}
+		});
+		let mut last = 0;
+		let mut changes = 0;
+		while last < SNAPSHOTS {
+			if reader.update() {
+				let snapshot = reader.read();
+				assert!(snapshot.iter().all(|&v| v == snapshot[0]), "torn snapshot");
+				assert!(snapshot[0] > last, "{} after {}", snapshot[0], last);
+				last = snapshot[0];
+				changes += 1;
+			} else {
+				thread::yield_now();
+			}
+		}
+		producer.join().unwrap();
+		assert!(changes > 0);
+		assert!(!reader.update());
+	}
+}
@@ -0,0 +1,152 @@
+//! Tests of the dithering reference implementation against known values: the standard Bayer
+//! matrix, the rank permutations of the threshold maps and the mean of quantised flat colors
+
+extern crate gfx_gtk;
+
+use gfx_gtk::dither::{quantize, threshold_map};
+use gfx_gtk::DitherMode;
+
+const WIDTH: usize = 256;
+const HEIGHT: usize = 64;
+
+/// A horizontal gradient across the 16 lowest 8 bit levels, so that most pixels fall between two
+fn gradient(mode: DitherMode) -> Vec<u8> {
+	let map = threshold_map(mode);
+	let mut image = Vec::with_capacity(WIDTH * HEIGHT);
+	for y in 0..HEIGHT {
+		for x in 0..WIDTH {
+			let value = x as f32 / (WIDTH - 1) as f32 * 16. / 255.;
+			image.push(quantize(&map, x, y, value));
+		}
+	}
+	image
+}
+
+/// Width of the blocks averaged by [block_means()], a multiple of the Bayer map size
+const BLOCK: usize = 16;
+
+/// Average of the image over blocks of `BLOCK` columns by the whole height
+fn block_means(image: &[u8]) -> Vec<f32> {
+	(0..WIDTH / BLOCK)
+		.map(|b| {
+			let sum: f32 = (0..HEIGHT)
+				.flat_map(|y| (b * BLOCK..(b + 1) * BLOCK).map(move |x| y * WIDTH + x))
+				.map(|i| f32::from(image[i]))
+				.sum();
+			sum / (BLOCK * HEIGHT) as f32
+		})
+		.collect()
+}
+
+/// Recovers the ranks of a threshold map, which holds `(rank + 0.5) / count`
+fn ranks(mode: DitherMode) -> Vec<usize> {
+	let (size, thresholds) = threshold_map(mode);
+	let count = (size * size) as f32;
+	thresholds
+		.iter()
+		.map(|t| {
+			let rank = t * count - 0.5;
+			assert!((rank - rank.round()).abs() < 1e-3, "{:?}: threshold {}", mode, t);
+			rank.round() as usize
+		})
+		.collect()
+}
+
+#[test]
+fn bayer_map_is_the_standard_matrix() {
+	let (size, thresholds) = threshold_map(DitherMode::Bayer);
+	assert_eq!(size, 8);
+	let ranks = ranks(DitherMode::Bayer);
+	assert_eq!(&ranks[0..8], &[0, 32, 8, 40, 2, 34, 10, 42]);
+	assert_eq!(&ranks[8..16], &[48, 16, 56, 24, 50, 18, 58, 26]);
+	assert_eq!(&ranks[56..64], &[63, 31, 55, 23, 61, 29, 53, 21]);
+	assert_eq!(thresholds[0], 0.5 / 64.);
+	assert_eq!(thresholds[1], 32.5 / 64.);
+	assert_eq!(thresholds[63], 21.5 / 64.);
+}
+
+#[test]
+fn threshold_maps_are_rank_permutations() {
+	for &(mode, count) in &[(DitherMode::Bayer, 64), (DitherMode::BlueNoise, 4096)] {
+		let mut ranks = ranks(mode);
+		assert_eq!(ranks.len(), count);
+		ranks.sort();
+		assert!(
+			ranks.iter().enumerate().all(|(i, r)| i == *r),
+			"{:?} is not a permutation of 0..{}",
+			mode,
+			count
+		);
+	}
+}
+
+#[test]
+fn bayer_quantises_at_the_thresholds() {
+	let map = threshold_map(DitherMode::Bayer);
+	// a quarter step above 100 only rounds up where the threshold is above 0.75, rank 48 or more
+	let value = 100.25 / 255.;
+	assert_eq!(quantize(&map, 0, 0, value), 100);
+	assert_eq!(quantize(&map, 0, 1, value), 101);
+	assert_eq!(quantize(&map, 1, 1, value), 100);
+	assert_eq!(quantize(&map, 0, 7, value), 101);
+	// the map tiles the screen
+	assert_eq!(quantize(&map, 8, 9, value), 101);
+}
+
+#[test]
+fn flat_grey_keeps_its_mean() {
+	for mode in &[DitherMode::Bayer, DitherMode::BlueNoise] {
+		let map = threshold_map(*mode);
+		let size = map.0;
+		for step in 0..=10 {
+			let level = 127. + step as f32 / 10.;
+			let sum: u32 = (0..size)
+				.flat_map(|y| (0..size).map(move |x| (x, y)))
+				.map(|(x, y)| u32::from(quantize(&map, x, y, level / 255.)))
+				.sum();
+			let mean = sum as f32 / (size * size) as f32;
+			// one tile has as many distinct thresholds as pixels
+			assert!(
+				(mean - level).abs() <= 1. / (size * size) as f32 + 1e-4,
+				"{:?}: level {} averages {}",
+				mode,
+				level,
+				mean
+			);
+		}
+	}
+}
+
+#[test]
+fn no_dithering_rounds_to_nearest() {
+	let image = gradient(DitherMode::None);
+	for x in 0..WIDTH {
+		let expected = (x as f32 / (WIDTH - 1) as f32 * 16. + 0.5) as u8;
+		assert_eq!(image[x], expected);
+	}
+}
+
+#[test]
+fn dithering_preserves_the_mean() {
+	let expected: Vec<f32> = (0..WIDTH / BLOCK)
+		.map(|b| {
+			(b * BLOCK..(b + 1) * BLOCK)
+				.map(|x| x as f32 / (WIDTH - 1) as f32 * 16.)
+				.sum::<f32>()
+				/ BLOCK as f32
+		})
+		.collect();
+	for mode in &[DitherMode::Bayer, DitherMode::BlueNoise] {
+		let means = block_means(&gradient(*mode));
+		for (b, mean) in means.iter().enumerate() {
+			assert!(
+				(mean - expected[b]).abs() < 0.05,
+				"{:?}: block {} averages {}, expected {}",
+				mode,
+				b,
+				mean,
+				expected[b]
+			);
+		}
+	}
+}